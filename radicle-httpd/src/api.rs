@@ -1,11 +1,9 @@
 pub mod auth;
 
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::num::NonZeroUsize;
 use std::sync::Arc;
-use std::time::Duration;
 
-use axum::http::header::{AUTHORIZATION, CONTENT_TYPE};
-use axum::http::Method;
 use axum::response::{IntoResponse, Json};
 use axum::routing::get;
 use axum::Router;
@@ -14,11 +12,9 @@ use radicle::patch::cache::Patches as _;
 use radicle::storage::git::Repository;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use tokio::sync::RwLock;
-use tower_http::cors::{self, CorsLayer};
 
-use radicle::cob::{issue, patch, Author};
-use radicle::identity::{DocAt, RepoId};
+use radicle::cob::{issue, patch, Author, Label, Timestamp};
+use radicle::identity::{Did, DocAt, RepoId};
 use radicle::node::policy::Scope;
 use radicle::node::routing::Store;
 use radicle::node::AliasStore;
@@ -26,35 +22,62 @@ use radicle::node::{Handle, NodeId};
 use radicle::storage::{ReadRepository, ReadStorage};
 use radicle::{Node, Profile};
 
+mod code_search;
+mod db;
 mod error;
+mod events;
 mod json;
+mod pagination;
+mod search_index;
+mod timeline;
 mod v1;
 
-use crate::api::error::Error;
+use crate::api::auth::store::{self, SessionStore};
+pub use crate::api::error::Error;
+use crate::api::events::Events;
+use crate::api::pagination::{Page, Pagination};
+pub use crate::api::search_index::index_periodically;
+use crate::api::search_index::SearchIndex;
 use crate::cache::Cache;
+use crate::metrics::Metrics;
 use crate::Options;
 
 pub const RADICLE_VERSION: &str = env!("RADICLE_VERSION");
 // This version has to be updated on every breaking change to the radicle-httpd API.
 pub const API_VERSION: &str = "1.0.0";
-
-/// Identifier for sessions
-type SessionId = String;
+/// Header holding the number of items in a list, across all pages.
+pub const TOTAL_COUNT_HEADER: &str = "x-total-count";
 
 #[derive(Clone)]
 pub struct Context {
     profile: Arc<Profile>,
-    sessions: Arc<RwLock<HashMap<SessionId, auth::Session>>>,
+    sessions: Arc<dyn SessionStore>,
     cache: Option<Cache>,
+    events: Events,
+    metrics: Metrics,
+    search: Arc<SearchIndex>,
 }
 
 impl Context {
-    pub fn new(profile: Arc<Profile>, options: &Options) -> Self {
-        Self {
+    pub fn new(profile: Arc<Profile>, options: &Options) -> Result<Self, Error> {
+        let sessions: Arc<dyn SessionStore> = match options.sessions {
+            store::Backend::Memory => Arc::new(store::MemoryStore::default()),
+            store::Backend::Sqlite => {
+                let path = profile.home.node().join(store::SESSIONS_DB_FILE);
+                Arc::new(store::SqliteStore::open(path)?)
+            }
+        };
+        sessions.purge(time::OffsetDateTime::now_utc())?;
+        let search = SearchIndex::open(profile.home.node().join(search_index::SEARCH_DB_FILE))?;
+
+        Ok(Self {
             profile,
-            sessions: Default::default(),
+            sessions,
             cache: options.cache.map(Cache::new),
-        }
+            events: Events::default(),
+            metrics: Metrics::default(),
+            search: Arc::new(search),
+        })
     }
 
     pub fn project_info<R: ReadRepository + radicle::cob::Store>(
@@ -91,7 +114,21 @@ impl Context {
         })
     }
 
+    /// Use a cache of the given size. The entries of the current cache are kept, as far as
+    /// they fit.
+    pub async fn with_cache(self, size: Option<NonZeroUsize>) -> Self {
+        let cache = match (self.cache, size) {
+            (Some(cache), Some(size)) => {
+                cache.tree.lock().await.resize(size);
+                Some(cache)
+            }
+            (_, size) => size.map(Cache::new),
+        };
+        Self { cache, ..self }
+    }
+
     /// Get a repository by RID, checking to make sure we're allowed to view it.
+    #[tracing::instrument(name = "storage.open", skip(self))]
     pub fn repo(&self, rid: RepoId) -> Result<(Repository, DocAt), error::Error> {
         let repo = self.profile.storage.repository(rid)?;
         let doc = repo.identity_doc()?;
@@ -102,34 +139,54 @@ impl Context {
         Ok((repo, doc))
     }
 
-    #[cfg(test)]
     pub fn profile(&self) -> &Arc<Profile> {
         &self.profile
     }
 
-    #[cfg(test)]
-    pub fn sessions(&self) -> &Arc<RwLock<HashMap<SessionId, auth::Session>>> {
+    pub fn sessions(&self) -> &Arc<dyn SessionStore> {
         &self.sessions
     }
+
+    pub fn cache(&self) -> Option<&Cache> {
+        self.cache.as_ref()
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// End the event streams of all clients, so that they don't hold up shutdown.
+    pub fn close_events(&self) {
+        self.events.close();
+    }
+
+    /// Search the given repositories and return the requested page of results, best matches
+    /// first. The index isn't refreshed, that's left to [`index_periodically`].
+    pub async fn search(
+        &self,
+        query: String,
+        kinds: Vec<search_index::Kind>,
+        rids: Vec<RepoId>,
+        pagination: Pagination,
+    ) -> Result<Page<search_index::Hit>, error::Error> {
+        let ctx = self.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let _span = tracing::info_span!("search", repos = rids.len()).entered();
+            let matches = ctx.search.search(&query, &kinds, &rids)?;
+
+            Ok(pagination
+                .paginate(matches, search_index::Match::key, SortDirection::Asc)?
+                .try_filter_map(|m| ctx.search.hit(&query, m))?)
+        })
+        .await?
+    }
 }
 
 pub fn router(ctx: Context) -> Router {
     Router::new()
         .route("/", get(root_handler))
         .merge(v1::router(ctx))
-        .layer(
-            CorsLayer::new()
-                .max_age(Duration::from_secs(86400))
-                .allow_origin(cors::Any)
-                .allow_methods([
-                    Method::GET,
-                    Method::POST,
-                    Method::PATCH,
-                    Method::PUT,
-                    Method::DELETE,
-                ])
-                .allow_headers([CONTENT_TYPE, AUTHORIZATION]),
-        )
 }
 
 async fn root_handler() -> impl IntoResponse {
@@ -147,13 +204,18 @@ async fn root_handler() -> impl IntoResponse {
     Json(response)
 }
 
+/// Number of issues and patches something is used on.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Usage {
+    pub issues: usize,
+    pub patches: usize,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct PaginationQuery {
     #[serde(default)]
     pub show: ProjectQuery,
-    pub page: Option<usize>,
-    pub per_page: Option<usize>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Default)]
@@ -173,9 +235,166 @@ pub struct RawQuery {
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct CobsQuery<T> {
-    pub page: Option<usize>,
-    pub per_page: Option<usize>,
     pub state: Option<T>,
+    /// Only return items with this label.
+    pub label: Option<Label>,
+    /// Only return items assigned to this DID.
+    pub assignee: Option<Did>,
+    /// Only return items opened by this DID.
+    pub author: Option<Did>,
+    /// Only return items created at or after this unix timestamp.
+    pub since: Option<u64>,
+    /// Only return items created at or before this unix timestamp.
+    pub until: Option<u64>,
+    /// Only return items whose title contains this text, ignoring case.
+    pub q: Option<String>,
+    #[serde(default)]
+    pub sort: CobsSort,
+    #[serde(default)]
+    pub direction: SortDirection,
+}
+
+impl<T> CobsQuery<T> {
+    /// Whether an item with the given attributes passes the query's filters.
+    pub fn matches<'a>(
+        &self,
+        title: &str,
+        author: &Did,
+        created: Timestamp,
+        mut labels: impl Iterator<Item = &'a Label>,
+        mut assignees: impl Iterator<Item = Did>,
+    ) -> bool {
+        let created = created.as_secs();
+
+        self.label.as_ref().map_or(true, |l| labels.any(|x| x == l))
+            && self.assignee.map_or(true, |a| assignees.any(|x| x == a))
+            && self.author.map_or(true, |a| *author == a)
+            && self.since.map_or(true, |since| created >= since)
+            && self.until.map_or(true, |until| created <= until)
+            && self
+                .q
+                .as_ref()
+                .map_or(true, |q| title.to_lowercase().contains(&q.to_lowercase()))
+    }
+}
+
+/// Patch specific filters, used alongside [`CobsQuery`].
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PatchesQuery {
+    /// Only return patches whose latest revision was reviewed by this DID.
+    /// With `verdict`, only this DID's review is considered.
+    pub reviewer: Option<Did>,
+    /// Only return patches whose latest revision has a review with this verdict.
+    /// `none` matches patches without any accepting or rejecting review.
+    pub verdict: Option<VerdictQuery>,
+    /// Only return patches merged by this DID.
+    pub merged_by: Option<Did>,
+    /// Only return patches whose latest revision is based on this commit.
+    pub base: Option<radicle::git::Oid>,
+    /// Only return patches targeting this branch.
+    pub target: Option<String>,
+    /// Only return patches with a revision pushed at or after this unix timestamp.
+    pub updated_since: Option<u64>,
+}
+
+impl PatchesQuery {
+    /// Whether the patch passes the filters. Patches target the project's `default_branch`.
+    pub fn matches(&self, patch: &patch::Patch, default_branch: &str) -> bool {
+        let (_, latest) = patch.latest();
+        let verdicts = latest
+            .reviews()
+            .filter(|(author, _)| self.reviewer.map_or(true, |r| Did::from(**author) == r))
+            .map(|(_, review)| review.verdict())
+            .collect::<Vec<_>>();
+        let reviewed = match (self.reviewer, self.verdict) {
+            (_, Some(VerdictQuery::Accepted)) => verdicts.contains(&Some(patch::Verdict::Accept)),
+            (_, Some(VerdictQuery::Rejected)) => verdicts.contains(&Some(patch::Verdict::Reject)),
+            (_, Some(VerdictQuery::None)) => verdicts.iter().all(Option::is_none),
+            (Some(_), None) => !verdicts.is_empty(),
+            (None, None) => true,
+        };
+        let target = match patch.target() {
+            patch::MergeTarget::Delegates => default_branch,
+        };
+
+        reviewed
+            && self.merged_by.map_or(true, |did| {
+                patch.merges().any(|(actor, _)| Did::from(*actor) == did)
+            })
+            && self.base.map_or(true, |base| *latest.base() == base)
+            && self.target.as_ref().map_or(true, |t| t == target)
+            && self
+                .updated_since
+                .map_or(true, |since| patch.updated_at().as_secs() >= since)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum VerdictQuery {
+    Accepted,
+    Rejected,
+    None,
+}
+
+/// Order in which collaborative objects are listed.
+#[derive(Default, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum CobsSort {
+    /// By creation time.
+    #[default]
+    Created,
+    /// By the time of the latest comment.
+    Updated,
+    /// By number of comments.
+    Comments,
+}
+
+#[derive(Default, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum SortDirection {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl SortDirection {
+    /// Apply the direction to an ascending ordering.
+    pub fn apply(self, ordering: Ordering) -> Ordering {
+        match self {
+            Self::Asc => ordering,
+            Self::Desc => ordering.reverse(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchQuery {
+    pub q: String,
+    /// Only return results of this type.
+    #[serde(rename = "type")]
+    pub kind: Option<search_index::Kind>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CodeSearchQuery {
+    pub q: String,
+    /// Commit to search at. Defaults to the canonical head.
+    pub sha: Option<radicle::git::Oid>,
+    /// Only search files matching this glob, eg. `src/**/*.rs`.
+    pub path: Option<String>,
+    /// Whether the query is a regular expression, rather than a literal string.
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(default)]
+    pub case: code_search::Case,
+    /// Lines of context around matches.
+    pub context: Option<usize>,
+    /// Number of matching lines to return.
+    pub limit: Option<usize>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -225,40 +444,68 @@ impl PatchState {
 }
 
 mod search {
-    use std::cmp::Ordering;
+    use std::cmp::Reverse;
 
     use nonempty::NonEmpty;
     use serde::{Deserialize, Serialize};
     use serde_json::json;
 
     use radicle::crypto::Verified;
-    use radicle::identity::{Project, RepoId};
+    use radicle::identity::{Did, Project, RepoId};
     use radicle::node::routing::Store;
     use radicle::node::AliasStore;
     use radicle::node::Database;
     use radicle::profile::Aliases;
     use radicle::storage::RepositoryInfo;
 
+    /// Weight of a match on the project name.
+    const NAME_WEIGHT: f64 = 1.0;
+    /// Weight of a match on a delegate alias.
+    const ALIAS_WEIGHT: f64 = 0.8;
+    /// Weight of a match on the project description.
+    const DESCRIPTION_WEIGHT: f64 = 0.6;
+    /// Lowest weighted score of a repository that is still returned.
+    const MIN_SCORE: f64 = 0.2;
+    /// Longest span, as a multiple of the query length, that the query's characters may be
+    /// spread over when matched in order.
+    const MAX_SPAN_FACTOR: usize = 3;
+
     #[derive(Serialize, Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct SearchQueryString {
         pub q: Option<String>,
-        pub page: Option<usize>,
-        pub per_page: Option<usize>,
+        /// Only return repositories delegated to this DID.
+        pub delegate: Option<Did>,
+        /// Only return repositories with this visibility.
+        /// Private repositories are never returned, so `private` is rejected.
+        pub visibility: Option<VisibilityQuery>,
+        /// Only return repositories with, or without, open patches.
+        pub has_open_patches: Option<bool>,
+        /// Only return repositories whose head was committed at or after this unix timestamp.
+        pub updated_since: Option<i64>,
     }
 
-    #[derive(Serialize, Deserialize, Eq, Debug)]
+    #[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+    #[serde(rename_all = "camelCase")]
+    pub enum VisibilityQuery {
+        Public,
+        Private,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
     pub struct SearchResult {
         pub rid: RepoId,
         #[serde(flatten)]
         pub payload: Project,
         pub delegates: NonEmpty<serde_json::Value>,
         pub seeds: usize,
-        #[serde(skip)]
-        pub index: usize,
+        /// Relevance of the repository to the query, between `0` and `1`.
+        pub score: f64,
     }
 
     impl SearchResult {
+        /// Match a repository against the query, returning `None` if it doesn't match.
+        /// An empty query matches every public repository, with a score of zero.
         pub fn new(
             q: &str,
             info: RepositoryInfo<Verified>,
@@ -269,49 +516,179 @@ mod search {
                 return None;
             }
             let payload = info.doc.project().ok()?;
-            let index = payload.name().find(q)?;
+            let q = q.trim().to_lowercase();
+            let aliases = info
+                .doc
+                .delegates
+                .iter()
+                .map(|did| (*did, aliases.alias(did)))
+                .collect::<Vec<_>>();
+            let score = if q.is_empty() {
+                0.
+            } else {
+                let name = fuzzy(&q, payload.name()) * NAME_WEIGHT;
+                let description = fuzzy(&q, payload.description()) * DESCRIPTION_WEIGHT;
+                let alias = aliases
+                    .iter()
+                    .filter_map(|(_, alias)| alias.as_ref())
+                    .map(|alias| fuzzy(&q, alias.as_ref()) * ALIAS_WEIGHT)
+                    .fold(0., f64::max);
+                let score = name.max(description).max(alias);
+                if score < MIN_SCORE {
+                    return None;
+                }
+                (score * 1000.).round() / 1000.
+            };
             let seeds = db.count(&info.rid).unwrap_or_default();
-            let delegates = info.doc.delegates.map(|did| match aliases.alias(&did) {
-                Some(alias) => json!({
-                    "id": did,
-                    "alias": alias,
-                }),
-                None => json!({
-                    "id": did,
-                }),
-            });
+            let delegates = NonEmpty::from_vec(
+                aliases
+                    .into_iter()
+                    .map(|(did, alias)| match alias {
+                        Some(alias) => json!({
+                            "id": did,
+                            "alias": alias,
+                        }),
+                        None => json!({
+                            "id": did,
+                        }),
+                    })
+                    .collect(),
+            )?;
 
             Some(SearchResult {
                 rid: info.rid,
                 payload,
                 delegates,
                 seeds,
-                index,
+                score,
             })
         }
+
+        /// Sort key, ordering by descending score, then descending seed count, then name.
+        /// Scores are rounded to thousandths, so they are compared as such.
+        pub fn key(&self) -> (Reverse<u64>, Reverse<usize>, String, RepoId) {
+            (
+                Reverse((self.score * 1000.).round() as u64),
+                Reverse(self.seeds),
+                self.payload.name().to_owned(),
+                self.rid,
+            )
+        }
     }
 
-    impl Ord for SearchResult {
-        fn cmp(&self, other: &Self) -> Ordering {
-            match (self.index, other.index) {
-                (0, 0) => self.seeds.cmp(&other.seeds),
-                (0, _) => std::cmp::Ordering::Less,
-                (_, 0) => std::cmp::Ordering::Greater,
-                (ai, bi) if ai == bi => self.seeds.cmp(&other.seeds),
-                (_, _) => self.seeds.cmp(&other.seeds),
+    /// Score how well `text` matches the lowercased query `q`, between `0` and `1`.
+    ///
+    /// Exact matches rank above prefixes, which rank above matches at the start of a word,
+    /// then anywhere. Failing that, words within a small edit distance of the query are
+    /// accepted, and finally the query's characters appearing in order, scored by how close
+    /// together they are, provided they span at most [`MAX_SPAN_FACTOR`] times the query length.
+    pub fn fuzzy(q: &str, text: &str) -> f64 {
+        let text = text.to_lowercase();
+        if q.is_empty() || text.is_empty() {
+            return 0.;
+        }
+        if text == q {
+            return 1.;
+        }
+        if text.starts_with(q) {
+            return 0.9;
+        }
+        if let Some(ix) = text.find(q) {
+            let at_word = text[..ix]
+                .chars()
+                .next_back()
+                .map_or(true, |c| !c.is_alphanumeric());
+            return if at_word { 0.8 } else { 0.7 };
+        }
+        let len = q.chars().count();
+        let max_distance = match len {
+            0..=3 => 0,
+            4..=7 => 1,
+            _ => 2,
+        };
+        if max_distance > 0 {
+            let distance = std::iter::once(text.as_str())
+                .chain(text.split(|c: char| !c.is_alphanumeric()))
+                .map(|word| levenshtein(q, word))
+                .min()
+                .unwrap_or(usize::MAX);
+            if distance <= max_distance {
+                return 0.6 - 0.1 * (distance - 1) as f64;
             }
         }
+        subsequence(q, &text)
+            .filter(|span| *span <= len * MAX_SPAN_FACTOR)
+            .map_or(0., |span| 0.2 + 0.3 * len as f64 / span as f64)
     }
 
-    impl PartialOrd for SearchResult {
-        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-            Some(self.cmp(other))
+    /// Length, in characters, of the shortest span of `text` containing the characters of
+    /// `q` in order, if any.
+    fn subsequence(q: &str, text: &str) -> Option<usize> {
+        let q = q.chars().collect::<Vec<_>>();
+        let text = text.chars().collect::<Vec<_>>();
+        let first = *q.first()?;
+
+        text.iter()
+            .enumerate()
+            .filter(|(_, c)| **c == first)
+            .filter_map(|(start, _)| {
+                let mut rest = q[1..].iter().peekable();
+                if rest.peek().is_none() {
+                    return Some(1);
+                }
+                for (i, c) in text[start + 1..].iter().enumerate() {
+                    if rest.peek() == Some(&c) {
+                        rest.next();
+                        if rest.peek().is_none() {
+                            return Some(i + 2);
+                        }
+                    }
+                }
+                None
+            })
+            .min()
+    }
+
+    fn levenshtein(a: &str, b: &str) -> usize {
+        let b = b.chars().collect::<Vec<_>>();
+        let mut row = (0..=b.len()).collect::<Vec<_>>();
+
+        for (i, ca) in a.chars().enumerate() {
+            let mut prev = row[0];
+            row[0] = i + 1;
+            for (j, cb) in b.iter().enumerate() {
+                let cost = usize::from(ca != *cb);
+                let next = (row[j + 1] + 1).min(row[j] + 1).min(prev + cost);
+                prev = row[j + 1];
+                row[j + 1] = next;
+            }
         }
+        row[b.len()]
     }
 
-    impl PartialEq for SearchResult {
-        fn eq(&self, other: &Self) -> bool {
-            self.rid == other.rid
+    #[cfg(test)]
+    mod test {
+        use super::fuzzy;
+
+        #[test]
+        fn test_fuzzy() {
+            assert_eq!(fuzzy("hello-world", "hello-world"), 1.);
+            assert_eq!(fuzzy("hello", "Hello-World"), 0.9);
+            assert_eq!(fuzzy("world", "hello-world"), 0.8);
+            assert_eq!(fuzzy("orld", "hello-world"), 0.7);
+            assert_eq!(fuzzy("wrld", "hello-world"), 0.6);
+            assert_eq!(fuzzy("helo-wrld", "hello-world"), 0.5);
+            assert_eq!(fuzzy("hlo", "hello-world"), 0.2 + 0.3 * 3. / 5.);
+            assert_eq!(fuzzy("hw", "hello-world"), 0.);
+            assert_eq!(
+                fuzzy(
+                    "hw",
+                    "A shell that is fast, portable and easy to configure for your workflow"
+                ),
+                0.
+            );
+            assert_eq!(fuzzy("xyz", "hello-world"), 0.);
+            assert_eq!(fuzzy("", "hello-world"), 0.);
         }
     }
 }
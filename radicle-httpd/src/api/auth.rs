@@ -1,15 +1,28 @@
+pub mod store;
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fmt::Write as _;
+use std::str::FromStr;
+
 use serde::{Deserialize, Serialize};
 use time::serde::timestamp;
 use time::{Duration, OffsetDateTime};
 
-use radicle::crypto::PublicKey;
-use radicle::node::Alias;
+use radicle::crypto::{PublicKey, Signer};
+use radicle::identity::{Did, RepoId};
+use radicle::node::{Alias, AliasStore as _};
+use radicle::Profile;
 
+use crate::api::auth::store::SessionStore;
 use crate::api::error::Error;
 use crate::api::Context;
 
 pub const UNAUTHORIZED_SESSIONS_EXPIRATION: Duration = Duration::seconds(60);
 pub const AUTHORIZED_SESSIONS_EXPIRATION: Duration = Duration::weeks(1);
+pub const ACCESS_TOKENS_EXPIRATION: Duration = Duration::days(90);
+/// Access token secrets start with this, so they can't be mistaken for session ids.
+pub const ACCESS_TOKEN_PREFIX: &str = "radtok_";
 
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
@@ -18,7 +31,98 @@ pub enum AuthState {
     Unauthorized,
 }
 
-#[derive(Clone, Deserialize)]
+/// An action that a session may be allowed to take.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+pub enum Capability {
+    /// Open, edit, comment on and close issues.
+    #[serde(rename = "issues:write")]
+    IssuesWrite,
+    /// Open, revise, review and comment on patches.
+    #[serde(rename = "patches:write")]
+    PatchesWrite,
+    /// Merge patches.
+    #[serde(rename = "patches:merge")]
+    PatchesMerge,
+    /// Change the seeding policies of the node.
+    #[serde(rename = "policies:write")]
+    PoliciesWrite,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 4] = [
+        Capability::IssuesWrite,
+        Capability::PatchesWrite,
+        Capability::PatchesMerge,
+        Capability::PoliciesWrite,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::IssuesWrite => "issues:write",
+            Self::PatchesWrite => "patches:write",
+            Self::PatchesMerge => "patches:merge",
+            Self::PoliciesWrite => "policies:write",
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Capability {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|c| c.as_str() == s)
+            .ok_or_else(|| format!("unknown capability '{s}'"))
+    }
+}
+
+/// What a session is allowed to do.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Scope {
+    /// Allowed actions. An empty set makes for a read-only session.
+    pub capabilities: BTreeSet<Capability>,
+    /// Repositories the capabilities apply to, or `None` for all of them.
+    pub repos: Option<BTreeSet<RepoId>>,
+}
+
+impl Default for Scope {
+    /// Everything is allowed, on every repository.
+    fn default() -> Self {
+        Self {
+            capabilities: Capability::ALL.into_iter().collect(),
+            repos: None,
+        }
+    }
+}
+
+impl Scope {
+    /// Check whether everything allowed by `other` is also allowed by `self`.
+    pub fn contains(&self, other: &Scope) -> bool {
+        other.capabilities.is_subset(&self.capabilities)
+            && match (&self.repos, &other.repos) {
+                (None, _) => true,
+                (Some(_), None) => false,
+                (Some(ours), Some(theirs)) => theirs.is_subset(ours),
+            }
+    }
+
+    /// Check whether the repository `rid` is within the scope, whatever the capability.
+    pub fn covers(&self, rid: &RepoId) -> bool {
+        self.repos
+            .as_ref()
+            .map_or(true, |repos| repos.contains(rid))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Session {
     pub status: AuthState,
@@ -28,17 +132,143 @@ pub struct Session {
     pub issued_at: OffsetDateTime,
     #[serde(with = "timestamp")]
     pub expires_at: OffsetDateTime,
+    #[serde(default)]
+    pub scope: Scope,
+}
+
+impl Session {
+    /// Make sure the session's scope allows `capability` on the repository `rid`.
+    pub fn authorize(&self, capability: Capability, rid: &RepoId) -> Result<(), Error> {
+        if !self.scope.capabilities.contains(&capability) {
+            return Err(Error::Forbidden(format!(
+                "Session lacks the `{capability}` capability"
+            )));
+        }
+        if !self.scope.covers(rid) {
+            return Err(Error::Forbidden(format!(
+                "Session is not allowed to access {rid}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Make sure the session belongs to the operator of the node, ie. the identity whose keys
+    /// the httpd holds. Only the operator can write: the httpd can't sign for other identities,
+    /// so their sessions are read-only.
+    pub fn require_operator(&self, profile: &Profile) -> Result<(), Error> {
+        if self.public_key != *profile.id() {
+            return Err(Error::Forbidden(format!(
+                "Only the node operator can write, not {}",
+                Did::from(self.public_key)
+            )));
+        }
+        Ok(())
+    }
+
+    /// The signer to use for writes made through this session, which must be the operator's.
+    pub fn signer(&self, profile: &Profile) -> Result<Box<dyn Signer>, Error> {
+        self.require_operator(profile)?;
+        profile.signer().map_err(|_| Error::Auth("Unauthorized"))
+    }
+}
+
+/// The alias to show for `public_key`: the one from the node config for the operator,
+/// otherwise the one known to the node, falling back to an abbreviated key.
+pub fn alias(profile: &Profile, public_key: &PublicKey) -> Alias {
+    if public_key == profile.id() {
+        return profile.config.node.alias.clone();
+    }
+    profile.aliases().alias(public_key).unwrap_or_else(|| {
+        let key = public_key.to_human();
+        let short = format!("{}…{}", &key[..6], &key[key.len() - 6..]);
+        // SAFETY: The abbreviated key is short and has no whitespace.
+        #[allow(clippy::unwrap_used)]
+        Alias::from_str(&short).unwrap()
+    })
+}
+
+/// A named, long-lived access token, for use by CI and bots.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+    /// Public identifier of the token, used to revoke it.
+    pub id: String,
+    /// Human readable name, eg. what the token is used for.
+    pub name: String,
+    pub public_key: PublicKey,
+    pub alias: Alias,
+    pub issued_at: OffsetDateTime,
+    pub expires_at: OffsetDateTime,
+    pub scope: Scope,
+}
+
+impl Token {
+    /// The authorized session that requests made with this token act as.
+    pub fn session(&self) -> Session {
+        Session {
+            status: AuthState::Authorized,
+            public_key: self.public_key,
+            alias: self.alias.clone(),
+            issued_at: self.issued_at,
+            expires_at: self.expires_at,
+            scope: self.scope.clone(),
+        }
+    }
+}
+
+/// Generate the secret of a new access token, from the operating system's random number
+/// generator.
+pub fn token_secret() -> Result<String, getrandom::Error> {
+    let mut bytes = [0; 20];
+    getrandom::getrandom(&mut bytes)?;
+
+    Ok(format!("{ACCESS_TOKEN_PREFIX}{}", hex(&bytes)))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut s, b| {
+        // Writing to a string can't fail.
+        let _ = write!(s, "{b:02x}");
+        s
+    })
+}
+
+/// Check that `token` belongs to an authorized, unexpired session or to an unexpired access
+/// token, and return the session it stands for.
+/// The session store is read off the async runtime, as it may block.
+pub async fn validate(ctx: &Context, token: &str) -> Result<Session, Error> {
+    let sessions = ctx.sessions.clone();
+    let token = token.to_owned();
+
+    tokio::task::spawn_blocking(move || authenticate(sessions.as_ref(), &token)).await?
+}
+
+/// Like [`validate`], but only accepts sessions, not access tokens.
+pub async fn validate_session(ctx: &Context, token: &str) -> Result<Session, Error> {
+    let sessions = ctx.sessions.clone();
+    let token = token.to_owned();
+
+    tokio::task::spawn_blocking(move || authenticate_session(sessions.as_ref(), &token)).await?
+}
+
+/// Like [`validate`], given the session store only.
+pub fn authenticate(sessions: &dyn SessionStore, token: &str) -> Result<Session, Error> {
+    if token.starts_with(ACCESS_TOKEN_PREFIX) {
+        let token = sessions.token(token)?.ok_or(Error::Auth("Unauthorized"))?;
+
+        if token.expires_at <= OffsetDateTime::now_utc() {
+            return Err(Error::Auth("Unauthorized"));
+        }
+        return Ok(token.session());
+    }
+    authenticate_session(sessions, token)
 }
 
-pub async fn validate(ctx: &Context, token: &str) -> Result<(), Error> {
-    let sessions_store = ctx.sessions.read().await;
-    let session = sessions_store
-        .get(token)
-        .ok_or(Error::Auth("Unauthorized"))?;
+fn authenticate_session(sessions: &dyn SessionStore, token: &str) -> Result<Session, Error> {
+    let session = sessions.get(token)?.ok_or(Error::Auth("Unauthorized"))?;
 
     if session.status != AuthState::Authorized || session.expires_at <= OffsetDateTime::now_utc() {
         return Err(Error::Auth("Unauthorized"));
     }
 
-    Ok(())
+    Ok(session)
 }
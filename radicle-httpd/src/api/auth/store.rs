@@ -0,0 +1,635 @@
+//! Session storage backends.
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::{fmt, io};
+
+use sha2::{Digest as _, Sha256};
+use sqlite as sql;
+use time::OffsetDateTime;
+
+use super::{AuthState, Scope, Session, Token};
+use crate::api::db;
+
+/// Name of the sessions database file, in the node directory of the radicle home.
+pub const SESSIONS_DB_FILE: &str = "httpd.db";
+/// How often expired sessions and access tokens are purged.
+pub const PURGE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// I/O error.
+    #[error("i/o error: {0}")]
+    Io(#[from] io::Error),
+    /// An Internal error.
+    #[error("internal error: {0}")]
+    Internal(#[from] sql::Error),
+    /// A stored value could not be decoded.
+    #[error("invalid value for column `{0}`")]
+    InvalidValue(&'static str),
+    /// JSON encoding error.
+    #[error("json: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Where sessions are kept.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// In memory. Sessions are lost when the daemon restarts.
+    Memory,
+    /// In an SQLite database under the radicle home.
+    #[default]
+    Sqlite,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "memory" => Ok(Self::Memory),
+            "sqlite" => Ok(Self::Sqlite),
+            _ => Err(format!("unknown session store '{s}'")),
+        }
+    }
+}
+
+/// Storage for sessions, keyed by session id, and access tokens, keyed by their secret.
+///
+/// Only the SHA-256 digests of session ids and token secrets are kept, so that they can't be
+/// read back from the store to take over a session or token.
+pub trait SessionStore: Send + Sync {
+    /// Get a session.
+    fn get(&self, id: &str) -> Result<Option<Session>, Error>;
+    /// Insert a session, replacing any existing session with the same id.
+    fn insert(&self, id: &str, session: &Session) -> Result<(), Error>;
+    /// Remove a session. Returns whether it existed.
+    fn remove(&self, id: &str) -> Result<bool, Error>;
+    /// Remove all sessions and access tokens that expired before `now`. Returns how many were
+    /// removed.
+    fn purge(&self, now: OffsetDateTime) -> Result<usize, Error>;
+    /// Count the authorized sessions that are still valid at `now`.
+    fn active(&self, now: OffsetDateTime) -> Result<usize, Error>;
+    /// Get an access token by its secret.
+    fn token(&self, secret: &str) -> Result<Option<Token>, Error>;
+    /// Get all access tokens, oldest first.
+    fn tokens(&self) -> Result<Vec<Token>, Error>;
+    /// Insert a new access token.
+    fn insert_token(&self, secret: &str, token: &Token) -> Result<(), Error>;
+    /// Remove an access token by its id. Returns whether it existed.
+    fn remove_token(&self, id: &str) -> Result<bool, Error>;
+}
+
+/// Sessions kept in memory.
+#[derive(Default)]
+pub struct MemoryStore {
+    /// Sessions, by digest of their id.
+    sessions: RwLock<HashMap<String, Session>>,
+    /// Access tokens, by digest of their secret.
+    tokens: RwLock<HashMap<String, Token>>,
+}
+
+impl SessionStore for MemoryStore {
+    fn get(&self, id: &str) -> Result<Option<Session>, Error> {
+        #[allow(clippy::unwrap_used)]
+        Ok(self.sessions.read().unwrap().get(&digest(id)).cloned())
+    }
+
+    fn insert(&self, id: &str, session: &Session) -> Result<(), Error> {
+        #[allow(clippy::unwrap_used)]
+        self.sessions
+            .write()
+            .unwrap()
+            .insert(digest(id), session.clone());
+
+        Ok(())
+    }
+
+    fn remove(&self, id: &str) -> Result<bool, Error> {
+        #[allow(clippy::unwrap_used)]
+        Ok(self.sessions.write().unwrap().remove(&digest(id)).is_some())
+    }
+
+    fn purge(&self, now: OffsetDateTime) -> Result<usize, Error> {
+        #[allow(clippy::unwrap_used)]
+        let mut sessions = self.sessions.write().unwrap();
+        #[allow(clippy::unwrap_used)]
+        let mut tokens = self.tokens.write().unwrap();
+        let before = sessions.len() + tokens.len();
+        sessions.retain(|_, s| s.expires_at > now);
+        tokens.retain(|_, t| t.expires_at > now);
+
+        Ok(before - sessions.len() - tokens.len())
+    }
+
+    fn active(&self, now: OffsetDateTime) -> Result<usize, Error> {
+        #[allow(clippy::unwrap_used)]
+        Ok(self
+            .sessions
+            .read()
+            .unwrap()
+            .values()
+            .filter(|s| s.status == AuthState::Authorized && s.expires_at > now)
+            .count())
+    }
+
+    fn token(&self, secret: &str) -> Result<Option<Token>, Error> {
+        #[allow(clippy::unwrap_used)]
+        Ok(self.tokens.read().unwrap().get(&digest(secret)).cloned())
+    }
+
+    fn tokens(&self) -> Result<Vec<Token>, Error> {
+        #[allow(clippy::unwrap_used)]
+        let mut tokens = self
+            .tokens
+            .read()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        tokens.sort_by(|a, b| a.issued_at.cmp(&b.issued_at).then(a.id.cmp(&b.id)));
+
+        Ok(tokens)
+    }
+
+    fn insert_token(&self, secret: &str, token: &Token) -> Result<(), Error> {
+        #[allow(clippy::unwrap_used)]
+        self.tokens
+            .write()
+            .unwrap()
+            .insert(digest(secret), token.clone());
+
+        Ok(())
+    }
+
+    fn remove_token(&self, id: &str) -> Result<bool, Error> {
+        #[allow(clippy::unwrap_used)]
+        let mut tokens = self.tokens.write().unwrap();
+        let before = tokens.len();
+        tokens.retain(|_, t| t.id != id);
+
+        Ok(tokens.len() < before)
+    }
+}
+
+/// Sessions kept in an SQLite database.
+pub struct SqliteStore {
+    db: sql::ConnectionThreadSafe,
+}
+
+impl fmt::Debug for SqliteStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SqliteStore(..)")
+    }
+}
+
+impl SqliteStore {
+    /// Schema migrations of the session store, see [`db::migrate`].
+    const MIGRATIONS: &'static [&'static str] = &[
+        "CREATE TABLE IF NOT EXISTS `sessions` (
+          `id`          text    primary key not null,
+          `status`      text    not null,
+          `public_key`  text    not null,
+          `alias`       text    not null,
+          `issued_at`   integer not null,
+          `expires_at`  integer not null
+        ) STRICT;",
+        // Sessions created before scopes existed may do everything.
+        r#"ALTER TABLE `sessions` ADD COLUMN `scope` text not null
+          DEFAULT '{"capabilities":["issues:write","patches:write","patches:merge","policies:write"],"repos":null}';"#,
+        "CREATE TABLE IF NOT EXISTS `tokens` (
+          `id`          text    primary key not null,
+          `digest`      text    unique not null,
+          `name`        text    not null,
+          `public_key`  text    not null,
+          `alias`       text    not null,
+          `issued_at`   integer not null,
+          `expires_at`  integer not null,
+          `scope`       text    not null
+        ) STRICT;",
+        // Session ids are replaced by their digest, see `SqliteStore::digest_sessions`.
+        "ALTER TABLE `sessions` RENAME COLUMN `id` TO `digest`;",
+    ];
+    /// Number of migrations after which sessions are keyed by the digest of their id.
+    const SESSION_DIGESTS: usize = 4;
+
+    /// Open a session store at the given path. Creates a new store if it doesn't exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let db = db::open(path)?;
+        Self::migrate(&db)?;
+
+        Ok(Self { db })
+    }
+
+    fn migrate(db: &sql::Connection) -> Result<(), Error> {
+        db::migrate(db, Self::MIGRATIONS, |db, applied| {
+            if applied == Self::SESSION_DIGESTS {
+                Self::digest_sessions(db)?;
+            }
+            Ok::<_, Error>(())
+        })
+    }
+
+    /// Replace the ids of the sessions stored before they were kept as digests.
+    fn digest_sessions(db: &sql::Connection) -> Result<(), sql::Error> {
+        let ids = db
+            .prepare("SELECT digest FROM `sessions`")?
+            .into_iter()
+            .map(|row| row.map(|row| row.read::<&str, _>("digest").to_owned()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut stmt = db.prepare("UPDATE `sessions` SET digest = ?1 WHERE digest = ?2")?;
+
+        for id in ids {
+            stmt.reset()?;
+            stmt.bind((1, digest(&id).as_str()))?;
+            stmt.bind((2, id.as_str()))?;
+            stmt.next()?;
+        }
+        Ok(())
+    }
+}
+
+impl SessionStore for SqliteStore {
+    fn get(&self, id: &str) -> Result<Option<Session>, Error> {
+        let mut stmt = self.db.prepare(
+            "SELECT status, public_key, alias, issued_at, expires_at, scope
+             FROM `sessions` WHERE digest = ?",
+        )?;
+        stmt.bind((1, digest(id).as_str()))?;
+
+        let Some(row) = stmt.into_iter().next() else {
+            return Ok(None);
+        };
+        let row = row?;
+        let status = match row.read::<&str, _>("status") {
+            "authorized" => AuthState::Authorized,
+            "unauthorized" => AuthState::Unauthorized,
+            _ => return Err(Error::InvalidValue("status")),
+        };
+        let public_key = parse(&row, "public_key")?;
+        let alias = parse(&row, "alias")?;
+        let issued_at = timestamp(&row, "issued_at")?;
+        let expires_at = timestamp(&row, "expires_at")?;
+        let scope: Scope = serde_json::from_str(row.read::<&str, _>("scope"))?;
+
+        Ok(Some(Session {
+            status,
+            public_key,
+            alias,
+            issued_at,
+            expires_at,
+            scope,
+        }))
+    }
+
+    fn insert(&self, id: &str, session: &Session) -> Result<(), Error> {
+        let mut stmt = self.db.prepare(
+            "INSERT INTO `sessions` (digest, status, public_key, alias, issued_at, expires_at, scope)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT DO UPDATE
+             SET status = ?2, public_key = ?3, alias = ?4, issued_at = ?5, expires_at = ?6,
+                 scope = ?7",
+        )?;
+        let status = match session.status {
+            AuthState::Authorized => "authorized",
+            AuthState::Unauthorized => "unauthorized",
+        };
+
+        stmt.bind((1, digest(id).as_str()))?;
+        stmt.bind((2, status))?;
+        stmt.bind((3, session.public_key.to_string().as_str()))?;
+        stmt.bind((4, session.alias.as_ref()))?;
+        stmt.bind((5, session.issued_at.unix_timestamp()))?;
+        stmt.bind((6, session.expires_at.unix_timestamp()))?;
+        stmt.bind((7, serde_json::to_string(&session.scope)?.as_str()))?;
+        stmt.next()?;
+
+        Ok(())
+    }
+
+    fn remove(&self, id: &str) -> Result<bool, Error> {
+        let mut stmt = self
+            .db
+            .prepare("DELETE FROM `sessions` WHERE digest = ? RETURNING digest")?;
+        stmt.bind((1, digest(id).as_str()))?;
+
+        Ok(deleted(stmt)? > 0)
+    }
+
+    fn purge(&self, now: OffsetDateTime) -> Result<usize, Error> {
+        let mut stmt = self
+            .db
+            .prepare("DELETE FROM `sessions` WHERE expires_at <= ? RETURNING digest")?;
+        stmt.bind((1, now.unix_timestamp()))?;
+        let sessions = deleted(stmt)?;
+
+        let mut stmt = self
+            .db
+            .prepare("DELETE FROM `tokens` WHERE expires_at <= ? RETURNING id")?;
+        stmt.bind((1, now.unix_timestamp()))?;
+
+        Ok(sessions + deleted(stmt)?)
+    }
+
+    fn active(&self, now: OffsetDateTime) -> Result<usize, Error> {
+        let mut stmt = self.db.prepare(
+            "SELECT COUNT(*) FROM `sessions` WHERE status = 'authorized' AND expires_at > ?",
+        )?;
+        stmt.bind((1, now.unix_timestamp()))?;
+        stmt.next()?;
+
+        Ok(stmt.read::<i64, _>(0)? as usize)
+    }
+
+    fn token(&self, secret: &str) -> Result<Option<Token>, Error> {
+        let mut stmt = self.db.prepare(
+            "SELECT id, name, public_key, alias, issued_at, expires_at, scope
+             FROM `tokens` WHERE digest = ?",
+        )?;
+        stmt.bind((1, digest(secret).as_str()))?;
+
+        match stmt.into_iter().next() {
+            Some(row) => Ok(Some(token(&row?)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn tokens(&self) -> Result<Vec<Token>, Error> {
+        let stmt = self.db.prepare(
+            "SELECT id, name, public_key, alias, issued_at, expires_at, scope
+             FROM `tokens` ORDER BY issued_at, id",
+        )?;
+
+        stmt.into_iter().map(|row| token(&row?)).collect()
+    }
+
+    fn insert_token(&self, secret: &str, token: &Token) -> Result<(), Error> {
+        let mut stmt = self.db.prepare(
+            "INSERT INTO `tokens` (id, digest, name, public_key, alias, issued_at, expires_at, scope)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        )?;
+
+        stmt.bind((1, token.id.as_str()))?;
+        stmt.bind((2, digest(secret).as_str()))?;
+        stmt.bind((3, token.name.as_str()))?;
+        stmt.bind((4, token.public_key.to_string().as_str()))?;
+        stmt.bind((5, token.alias.as_ref()))?;
+        stmt.bind((6, token.issued_at.unix_timestamp()))?;
+        stmt.bind((7, token.expires_at.unix_timestamp()))?;
+        stmt.bind((8, serde_json::to_string(&token.scope)?.as_str()))?;
+        stmt.next()?;
+
+        Ok(())
+    }
+
+    fn remove_token(&self, id: &str) -> Result<bool, Error> {
+        let mut stmt = self
+            .db
+            .prepare("DELETE FROM `tokens` WHERE id = ? RETURNING id")?;
+        stmt.bind((1, id))?;
+
+        Ok(deleted(stmt)? > 0)
+    }
+}
+
+/// Run a `DELETE … RETURNING` statement, and count the rows it deleted. Unlike the change count
+/// of the connection, this isn't affected by statements run concurrently on other threads.
+fn deleted(stmt: sql::Statement) -> Result<usize, Error> {
+    let mut count = 0;
+    for row in stmt.into_iter() {
+        row?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Purge expired sessions and access tokens from the store, every [`PURGE_INTERVAL`].
+pub async fn purge_periodically(store: Arc<dyn SessionStore>) {
+    let mut interval = tokio::time::interval(PURGE_INTERVAL);
+    // The store is purged on startup already.
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+
+        let store = store.clone();
+        match tokio::task::spawn_blocking(move || store.purge(OffsetDateTime::now_utc())).await {
+            Ok(Ok(0)) => {}
+            Ok(Ok(n)) => tracing::debug!("purged {n} expired session(s) and token(s)"),
+            Ok(Err(err)) => tracing::error!("Error purging expired sessions: {err}"),
+            Err(err) => tracing::error!("Error purging expired sessions: {err}"),
+        }
+    }
+}
+
+/// Digest of a session id or access token secret, as stored.
+fn digest(secret: &str) -> String {
+    super::hex(&Sha256::digest(secret.as_bytes()))
+}
+
+/// Decode an access token from a row of the `tokens` table.
+fn token(row: &sql::Row) -> Result<Token, Error> {
+    Ok(Token {
+        id: row.read::<&str, _>("id").to_owned(),
+        name: row.read::<&str, _>("name").to_owned(),
+        public_key: parse(row, "public_key")?,
+        alias: parse(row, "alias")?,
+        issued_at: timestamp(row, "issued_at")?,
+        expires_at: timestamp(row, "expires_at")?,
+        scope: serde_json::from_str(row.read::<&str, _>("scope"))?,
+    })
+}
+
+fn parse<T: FromStr>(row: &sql::Row, column: &'static str) -> Result<T, Error> {
+    T::from_str(row.read::<&str, _>(column)).map_err(|_| Error::InvalidValue(column))
+}
+
+fn timestamp(row: &sql::Row, column: &'static str) -> Result<OffsetDateTime, Error> {
+    OffsetDateTime::from_unix_timestamp(row.read::<i64, _>(column))
+        .map_err(|_| Error::InvalidValue(column))
+}
+
+#[cfg(test)]
+mod test {
+    use time::{Duration, OffsetDateTime};
+
+    use radicle::crypto::PublicKey;
+    use radicle::node::Alias;
+
+    use super::*;
+    use crate::api::auth::Capability;
+
+    fn session(expires_in: Duration) -> Session {
+        let issued_at = OffsetDateTime::from_unix_timestamp(1671125284).unwrap();
+
+        Session {
+            status: AuthState::Authorized,
+            public_key: PublicKey::from_str("z6MknSLrJoTcukLrE435hVNQT4JUhbvWLX4kUzqkEStBU8Vi")
+                .unwrap(),
+            alias: Alias::new("seed"),
+            issued_at,
+            expires_at: issued_at + expires_in,
+            scope: Scope::default(),
+        }
+    }
+
+    fn roundtrip(store: &dyn SessionStore) {
+        let s = session(Duration::weeks(1));
+
+        assert!(store.get("a").unwrap().is_none());
+        store.insert("a", &s).unwrap();
+        assert_eq!(store.get("a").unwrap(), Some(s.clone()));
+
+        let mut u = s.clone();
+        u.status = AuthState::Unauthorized;
+        u.scope = Scope {
+            capabilities: [Capability::IssuesWrite].into_iter().collect(),
+            repos: Some(
+                ["rad:z4FucBZHZMCsxTyQE1dfE2YR59Qbp".parse().unwrap()]
+                    .into_iter()
+                    .collect(),
+            ),
+        };
+        store.insert("a", &u).unwrap();
+        assert_eq!(store.get("a").unwrap(), Some(u));
+
+        assert!(store.remove("a").unwrap());
+        assert!(!store.remove("a").unwrap());
+        assert!(store.get("a").unwrap().is_none());
+    }
+
+    fn tokens(store: &dyn SessionStore) {
+        let s = session(Duration::days(90));
+        let token = Token {
+            id: "t1".to_owned(),
+            name: "ci".to_owned(),
+            public_key: s.public_key,
+            alias: s.alias,
+            issued_at: s.issued_at,
+            expires_at: s.expires_at,
+            scope: s.scope,
+        };
+        let other = Token {
+            id: "t2".to_owned(),
+            name: "bot".to_owned(),
+            issued_at: token.issued_at + Duration::seconds(1),
+            ..token.clone()
+        };
+
+        assert!(store.token("radtok_1").unwrap().is_none());
+        store.insert_token("radtok_1", &token).unwrap();
+        store.insert_token("radtok_2", &other).unwrap();
+        assert_eq!(store.token("radtok_1").unwrap(), Some(token.clone()));
+        assert_eq!(store.tokens().unwrap(), vec![token.clone(), other.clone()]);
+
+        assert_eq!(store.purge(token.expires_at).unwrap(), 2);
+        assert!(store.tokens().unwrap().is_empty());
+
+        store.insert_token("radtok_1", &token).unwrap();
+        assert!(store.remove_token("t1").unwrap());
+        assert!(!store.remove_token("t1").unwrap());
+        assert!(store.token("radtok_1").unwrap().is_none());
+    }
+
+    fn purge(store: &dyn SessionStore) {
+        let expired = session(Duration::seconds(60));
+        let valid = session(Duration::weeks(1));
+        let now = expired.expires_at + Duration::seconds(1);
+
+        store.insert("expired", &expired).unwrap();
+        store.insert("valid", &valid).unwrap();
+        store
+            .insert(
+                "unauthorized",
+                &Session {
+                    status: AuthState::Unauthorized,
+                    ..valid.clone()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(store.active(now).unwrap(), 1);
+        assert_eq!(store.purge(now).unwrap(), 1);
+        assert!(store.get("expired").unwrap().is_none());
+        assert!(store.get("valid").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_memory_store() {
+        roundtrip(&MemoryStore::default());
+        tokens(&MemoryStore::default());
+        purge(&MemoryStore::default());
+    }
+
+    #[test]
+    fn test_sqlite_store() {
+        let tmp = tempfile::tempdir().unwrap();
+
+        roundtrip(&SqliteStore::open(tmp.path().join("roundtrip.db")).unwrap());
+        tokens(&SqliteStore::open(tmp.path().join("tokens.db")).unwrap());
+        purge(&SqliteStore::open(tmp.path().join("purge.db")).unwrap());
+    }
+
+    #[test]
+    fn test_sqlite_store_digests() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open(tmp.path().join(SESSIONS_DB_FILE)).unwrap();
+        let s = session(Duration::days(90));
+        let token = Token {
+            id: "t1".to_owned(),
+            name: "ci".to_owned(),
+            public_key: s.public_key,
+            alias: s.alias,
+            issued_at: s.issued_at,
+            expires_at: s.expires_at,
+            scope: s.scope,
+        };
+        store.insert_token("radtok_1", &token).unwrap();
+        store.insert("a", &session(Duration::days(90))).unwrap();
+
+        for (table, secret) in [("tokens", "radtok_1"), ("sessions", "a")] {
+            let mut stmt = store
+                .db
+                .prepare(format!("SELECT digest FROM `{table}`"))
+                .unwrap();
+            stmt.next().unwrap();
+            assert_eq!(stmt.read::<String, _>(0).unwrap(), digest(secret));
+            assert_ne!(stmt.read::<String, _>(0).unwrap(), secret);
+        }
+    }
+
+    #[test]
+    fn test_sqlite_store_migrates() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join(SESSIONS_DB_FILE);
+        let s = session(Duration::weeks(1));
+        {
+            // A database from before sessions had a scope.
+            let db = sql::Connection::open(&path).unwrap();
+            db.execute(SqliteStore::MIGRATIONS[0]).unwrap();
+            db.execute("PRAGMA user_version = 1").unwrap();
+            db.execute(format!(
+                "INSERT INTO `sessions` VALUES ('a', 'authorized', '{}', 'seed', {}, {})",
+                s.public_key,
+                s.issued_at.unix_timestamp(),
+                s.expires_at.unix_timestamp()
+            ))
+            .unwrap();
+        }
+        let store = SqliteStore::open(&path).unwrap();
+
+        assert_eq!(store.get("a").unwrap(), Some(s));
+    }
+
+    #[test]
+    fn test_sqlite_store_persists() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join(SESSIONS_DB_FILE);
+        let s = session(Duration::weeks(1));
+
+        SqliteStore::open(&path).unwrap().insert("a", &s).unwrap();
+
+        assert_eq!(SqliteStore::open(&path).unwrap().get("a").unwrap(), Some(s));
+    }
+}
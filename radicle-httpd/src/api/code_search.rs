@@ -0,0 +1,433 @@
+//! Searching the files of a repository at a given commit.
+//!
+//! Searches run on public seeds, so the work they do is capped: a search stops when it runs out
+//! of time, has read a given number of bytes, or found enough matches, and reports why it
+//! stopped. Binary and large files are skipped.
+use std::time::{Duration, Instant};
+
+use radicle::git::raw as git2;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+/// How long a search may run for.
+pub const MAX_DURATION: Duration = Duration::from_secs(2);
+/// How many bytes of file content a search may read.
+pub const MAX_BYTES: usize = 64 * 1024 * 1024;
+/// Files larger than this are skipped.
+pub const MAX_FILE_SIZE: usize = 1024 * 1024;
+/// Default number of matching lines returned.
+pub const DEFAULT_LIMIT: usize = 100;
+/// Most matching lines returned.
+pub const MAX_LIMIT: usize = 1000;
+/// Default number of lines of context around matches.
+pub const DEFAULT_CONTEXT: usize = 2;
+/// Most lines of context around matches.
+pub const MAX_CONTEXT: usize = 10;
+/// Longest query accepted, in bytes.
+const MAX_QUERY_LENGTH: usize = 256;
+/// Largest compiled regex accepted, in bytes.
+const MAX_REGEX_SIZE: usize = 1024 * 1024;
+/// Lines longer than this are cut, in bytes.
+const MAX_LINE_LENGTH: usize = 1024;
+/// How much of a file is checked for NUL bytes to tell whether it's binary.
+const BINARY_CHECK_LENGTH: usize = 8000;
+
+/// How letter case is matched.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Case {
+    Sensitive,
+    Insensitive,
+    /// Insensitive, unless the query has upper case letters.
+    #[default]
+    Smart,
+}
+
+/// Why a search stopped before searching all files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Truncated {
+    /// It ran out of time.
+    Time,
+    /// It read too many bytes.
+    Bytes,
+    /// It found as many matches as were asked for.
+    Matches,
+}
+
+/// A character range within a line.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Range {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A line that matched.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Match {
+    pub path: String,
+    /// Line number, starting at 1.
+    pub line: usize,
+    /// Column of the first match on the line, in characters, starting at 1.
+    pub column: usize,
+    pub text: String,
+    /// Where the query matched within the line, in characters.
+    pub ranges: Vec<Range>,
+    /// Lines before the match.
+    pub before: Vec<String>,
+    /// Lines after the match.
+    pub after: Vec<String>,
+}
+
+/// Outcome of a search.
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Results {
+    pub matches: Vec<Match>,
+    /// Files whose content was searched.
+    pub files: usize,
+    /// Bytes of file content searched.
+    pub bytes: usize,
+    pub truncated: Option<Truncated>,
+}
+
+/// What to search for.
+#[derive(Debug)]
+pub struct Search {
+    pattern: Regex,
+    path: Option<glob::Pattern>,
+    context: usize,
+    limit: usize,
+    max_bytes: usize,
+    max_duration: Duration,
+}
+
+impl Search {
+    /// Build a search for `query`, as a regular expression if `regex` is set, and as a literal
+    /// string otherwise. Only files whose path matches the `path` glob are searched, if given.
+    pub fn new(query: &str, regex: bool, case: Case, path: Option<&str>) -> Result<Self, String> {
+        if query.is_empty() {
+            return Err("the query must not be empty".to_owned());
+        }
+        if query.len() > MAX_QUERY_LENGTH {
+            return Err(format!(
+                "the query must not be longer than {MAX_QUERY_LENGTH} bytes"
+            ));
+        }
+        let insensitive = match case {
+            Case::Sensitive => false,
+            Case::Insensitive => true,
+            Case::Smart => !query.chars().any(char::is_uppercase),
+        };
+        let source = if regex {
+            query.to_owned()
+        } else {
+            regex::escape(query)
+        };
+        let pattern = RegexBuilder::new(&source)
+            .case_insensitive(insensitive)
+            .size_limit(MAX_REGEX_SIZE)
+            .build()
+            .map_err(|e| format!("invalid regular expression: {e}"))?;
+        let path = path
+            .filter(|p| !p.is_empty())
+            .map(glob::Pattern::new)
+            .transpose()
+            .map_err(|e| format!("invalid path glob: {e}"))?;
+
+        Ok(Self {
+            pattern,
+            path,
+            context: DEFAULT_CONTEXT,
+            limit: DEFAULT_LIMIT,
+            max_bytes: MAX_BYTES,
+            max_duration: MAX_DURATION,
+        })
+    }
+
+    /// Lines of context to return around matches.
+    pub fn context(self, context: usize) -> Self {
+        Self {
+            context: context.min(MAX_CONTEXT),
+            ..self
+        }
+    }
+
+    /// Number of matching lines to return.
+    pub fn limit(self, limit: usize) -> Self {
+        Self {
+            limit: limit.min(MAX_LIMIT),
+            ..self
+        }
+    }
+
+    /// Search the files of the given commit, in tree order.
+    pub fn run(&self, repo: &git2::Repository, commit: git2::Oid) -> Result<Results, git2::Error> {
+        let started = Instant::now();
+        let tree = repo.find_commit(commit)?.tree()?;
+        let odb = repo.odb()?;
+        let mut results = Results::default();
+        let mut error = None;
+
+        tree.walk(git2::TreeWalkMode::PreOrder, |dir, entry| {
+            if entry.kind() != Some(git2::ObjectType::Blob)
+                || entry.filemode() == i32::from(git2::FileMode::Link)
+            {
+                return git2::TreeWalkResult::Ok;
+            }
+            let Some(name) = entry.name() else {
+                return git2::TreeWalkResult::Ok;
+            };
+            let path = format!("{dir}{name}");
+            // Checked for every file, so that a glob that matches few files in a large tree
+            // doesn't keep the walk going past the deadline.
+            if started.elapsed() > self.max_duration {
+                results.truncated = Some(Truncated::Time);
+                return git2::TreeWalkResult::Abort;
+            }
+            if let Some(glob) = &self.path {
+                let options = glob::MatchOptions {
+                    require_literal_separator: true,
+                    ..glob::MatchOptions::new()
+                };
+                if !glob.matches_with(&path, options) {
+                    return git2::TreeWalkResult::Ok;
+                }
+            }
+            let size = match odb.read_header(entry.id()) {
+                Ok((size, _)) => size,
+                Err(e) => {
+                    error = Some(e);
+                    return git2::TreeWalkResult::Abort;
+                }
+            };
+            if size > MAX_FILE_SIZE {
+                return git2::TreeWalkResult::Ok;
+            }
+            if results.bytes + size > self.max_bytes {
+                results.truncated = Some(Truncated::Bytes);
+                return git2::TreeWalkResult::Abort;
+            }
+            let blob = match repo.find_blob(entry.id()) {
+                Ok(blob) => blob,
+                Err(e) => {
+                    error = Some(e);
+                    return git2::TreeWalkResult::Abort;
+                }
+            };
+            let content = blob.content();
+            results.bytes += content.len();
+
+            if content[..content.len().min(BINARY_CHECK_LENGTH)].contains(&0) {
+                return git2::TreeWalkResult::Ok;
+            }
+            results.files += 1;
+
+            let text = String::from_utf8_lossy(content);
+            if !self.pattern.is_match(&text) {
+                return git2::TreeWalkResult::Ok;
+            }
+            if self.search_file(&path, &text, &mut results.matches) {
+                results.truncated = Some(Truncated::Matches);
+                return git2::TreeWalkResult::Abort;
+            }
+            git2::TreeWalkResult::Ok
+        })
+        .or_else(|e| {
+            // Aborting the walk is reported as an error by libgit2.
+            if e.code() == git2::ErrorCode::User {
+                Ok(())
+            } else {
+                Err(e)
+            }
+        })?;
+
+        match error {
+            Some(e) => Err(e),
+            None => Ok(results),
+        }
+    }
+
+    /// Add the matching lines of a file to `matches`. Returns whether the limit was reached.
+    fn search_file(&self, path: &str, text: &str, matches: &mut Vec<Match>) -> bool {
+        let lines = text
+            .split('\n')
+            .map(|l| l.strip_suffix('\r').unwrap_or(l))
+            .collect::<Vec<_>>();
+        // A trailing newline doesn't start another line.
+        let count = if text.ends_with('\n') {
+            lines.len() - 1
+        } else {
+            lines.len()
+        };
+
+        for (i, line) in lines.iter().take(count).enumerate() {
+            let line = truncate(line);
+            let ranges = self
+                .pattern
+                .find_iter(line)
+                .filter(|m| !m.is_empty())
+                .map(|m| Range {
+                    start: line[..m.start()].chars().count(),
+                    end: line[..m.end()].chars().count(),
+                })
+                .collect::<Vec<_>>();
+            let Some(first) = ranges.first() else {
+                continue;
+            };
+            if matches.len() == self.limit {
+                return true;
+            }
+            let context = |range: std::ops::Range<usize>| {
+                lines[range]
+                    .iter()
+                    .map(|l| truncate(l).to_owned())
+                    .collect::<Vec<_>>()
+            };
+
+            matches.push(Match {
+                path: path.to_owned(),
+                line: i + 1,
+                column: first.start + 1,
+                text: line.to_owned(),
+                before: context(i.saturating_sub(self.context)..i),
+                after: context(i + 1..(i + 1 + self.context).min(count)),
+                ranges,
+            });
+        }
+        false
+    }
+}
+
+/// Cut a line to the maximum line length.
+fn truncate(line: &str) -> &str {
+    if line.len() <= MAX_LINE_LENGTH {
+        return line;
+    }
+    let mut end = MAX_LINE_LENGTH;
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    &line[..end]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A repository with a single commit holding the given files.
+    fn repo(files: &[(&str, &[u8])]) -> (tempfile::TempDir, git2::Repository, git2::Oid) {
+        let tmp = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init(tmp.path()).unwrap();
+        let mut index = repo.index().unwrap();
+        for (path, content) in files {
+            let path = tmp.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        index
+            .add_all(["."], git2::IndexAddOption::DEFAULT, None)
+            .unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let sig = git2::Signature::now("Alice", "alice@radicle.xyz").unwrap();
+        let commit = repo
+            .commit(None, &sig, &sig, "Initial commit", &tree, &[])
+            .unwrap();
+        drop(tree);
+
+        (tmp, repo, commit)
+    }
+
+    #[test]
+    fn test_search() {
+        let (_tmp, repo, commit) = repo(&[
+            ("a.rs", b"fn main() {\n    println!(\"Hello\");\n}\n"),
+            ("src/lib.rs", b"// hello, hello\npub fn hello() {}\n"),
+            ("image.png", b"hello\0binary"),
+        ]);
+
+        let results = Search::new("hello", false, Case::Smart, None)
+            .unwrap()
+            .context(1)
+            .run(&repo, commit)
+            .unwrap();
+        let found = results
+            .matches
+            .iter()
+            .map(|m| (m.path.as_str(), m.line, m.column))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            found,
+            [("a.rs", 2, 15), ("src/lib.rs", 1, 4), ("src/lib.rs", 2, 8)]
+        );
+        assert_eq!(results.files, 2);
+        assert_eq!(results.truncated, None);
+        assert_eq!(
+            results.matches[1].ranges,
+            [Range { start: 3, end: 8 }, Range { start: 10, end: 15 }]
+        );
+        assert_eq!(results.matches[0].before, ["fn main() {"]);
+        assert_eq!(results.matches[0].after, ["}"]);
+
+        // Smart case is sensitive with upper case letters.
+        let results = Search::new("Hello", false, Case::Smart, None)
+            .unwrap()
+            .run(&repo, commit)
+            .unwrap();
+        assert_eq!(results.matches.len(), 1);
+
+        let results = Search::new(r"fn \w+\(", true, Case::Sensitive, Some("src/*.rs"))
+            .unwrap()
+            .run(&repo, commit)
+            .unwrap();
+        assert_eq!(results.matches.len(), 1);
+        assert_eq!(results.matches[0].text, "pub fn hello() {}");
+
+        // Literal queries aren't regular expressions.
+        let results = Search::new("fn \\w+", false, Case::Sensitive, None)
+            .unwrap()
+            .run(&repo, commit)
+            .unwrap();
+        assert!(results.matches.is_empty());
+    }
+
+    #[test]
+    fn test_search_caps() {
+        let (_tmp, repo, commit) = repo(&[("a", b"x\nx\nx\n"), ("b", b"x\n")]);
+
+        let results = Search::new("x", false, Case::Smart, None)
+            .unwrap()
+            .limit(2)
+            .run(&repo, commit)
+            .unwrap();
+        assert_eq!(results.matches.len(), 2);
+        assert_eq!(results.truncated, Some(Truncated::Matches));
+
+        let search = Search {
+            max_bytes: 7,
+            ..Search::new("x", false, Case::Smart, None).unwrap()
+        };
+        let results = search.run(&repo, commit).unwrap();
+        assert_eq!(results.files, 1);
+        assert_eq!(results.matches.len(), 3);
+        assert_eq!(results.truncated, Some(Truncated::Bytes));
+
+        let search = Search {
+            max_duration: Duration::ZERO,
+            ..Search::new("x", false, Case::Smart, None).unwrap()
+        };
+        let results = search.run(&repo, commit).unwrap();
+        assert_eq!(results.files, 0);
+        assert_eq!(results.truncated, Some(Truncated::Time));
+    }
+
+    #[test]
+    fn test_search_invalid() {
+        assert!(Search::new("", false, Case::Smart, None).is_err());
+        assert!(Search::new("(", true, Case::Smart, None).is_err());
+        assert!(Search::new("(", false, Case::Smart, None).is_ok());
+        assert!(Search::new("x", false, Case::Smart, Some("[")).is_err());
+    }
+}
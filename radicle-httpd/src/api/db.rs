@@ -0,0 +1,45 @@
+//! SQLite databases kept by the httpd, eg. the session store and the search index.
+use std::path::Path;
+use std::time::Duration;
+
+use sqlite as sql;
+
+/// How long to wait for the database lock to be released before failing.
+pub const DB_TIMEOUT: Duration = Duration::from_secs(6);
+
+/// Open the database at the given path. Creates a new database if it doesn't exist.
+pub fn open<P: AsRef<Path>>(path: P) -> Result<sql::ConnectionThreadSafe, sql::Error> {
+    let mut db = sql::Connection::open_thread_safe(path)?;
+    db.set_busy_timeout(DB_TIMEOUT.as_millis() as usize)?;
+
+    Ok(db)
+}
+
+/// Apply the schema `migrations` that weren't applied yet, in order. The `user_version` of the
+/// database is the number of migrations that were applied to it.
+///
+/// After each migration, `hook` is called with the number of migrations applied so far, in
+/// the same transaction, to migrate data along with the schema.
+pub fn migrate<E: From<sql::Error>>(
+    db: &sql::Connection,
+    migrations: &[&str],
+    mut hook: impl FnMut(&sql::Connection, usize) -> Result<(), E>,
+) -> Result<(), E> {
+    let mut stmt = db.prepare("PRAGMA user_version")?;
+    let version = match stmt.next()? {
+        sql::State::Row => stmt.read::<i64, _>(0)?,
+        sql::State::Done => 0,
+    };
+    drop(stmt);
+
+    for (i, migration) in migrations.iter().enumerate().skip(version as usize) {
+        radicle::sql::transaction(db, |db| {
+            db.execute(migration)?;
+            hook(db, i + 1)?;
+            db.execute(format!("PRAGMA user_version = {}", i + 1))?;
+
+            Ok::<_, E>(())
+        })?;
+    }
+    Ok(())
+}
@@ -14,6 +14,10 @@ pub enum Error {
     #[error("could not authenticate: {0}")]
     Auth(&'static str),
 
+    /// The session is authorized, but not allowed to take this action.
+    #[error("forbidden: {0}")]
+    Forbidden(String),
+
     /// An error occurred with env variables.
     #[error(transparent)]
     Env(#[from] std::env::VarError),
@@ -98,9 +102,29 @@ pub enum Error {
     #[error(transparent)]
     Node(#[from] radicle::node::Error),
 
+    /// Session store error.
+    #[error(transparent)]
+    SessionStore(#[from] crate::api::auth::store::Error),
+
+    /// Blocking task error.
+    #[error(transparent)]
+    Task(#[from] tokio::task::JoinError),
+
+    /// Random number generator error.
+    #[error("random number generator: {0}")]
+    Random(#[from] getrandom::Error),
+
+    /// Search index error.
+    #[error("search index: {0}")]
+    SearchIndex(#[from] sqlite::Error),
+
     /// Invalid update to issue or patch.
     #[error("{0}")]
     BadRequest(String),
+
+    /// Request conflicts with the state of the repository.
+    #[error("{0}")]
+    Conflict(String),
 }
 
 impl IntoResponse for Error {
@@ -112,6 +136,7 @@ impl IntoResponse for Error {
                 (StatusCode::NOT_FOUND, Some(e.to_string()))
             }
             Error::Auth(msg) => (StatusCode::UNAUTHORIZED, Some(msg.to_string())),
+            Error::Forbidden(msg) => (StatusCode::FORBIDDEN, Some(msg)),
             Error::Crypto(msg) => (StatusCode::BAD_REQUEST, Some(msg.to_string())),
             Error::Surf(radicle_surf::Error::Git(e)) if radicle::git::is_not_found_err(&e) => {
                 (StatusCode::NOT_FOUND, Some(e.message().to_owned()))
@@ -136,6 +161,7 @@ impl IntoResponse for Error {
                 (StatusCode::NOT_FOUND, Some(err.to_string()))
             }
             Error::BadRequest(msg) => (StatusCode::BAD_REQUEST, Some(msg)),
+            Error::Conflict(msg) => (StatusCode::CONFLICT, Some(msg)),
             other => {
                 tracing::error!("Error: {message}");
                 tracing::debug!("Error Debug: {:?}", other);
@@ -0,0 +1,506 @@
+//! Repository change notifications.
+//!
+//! Changes are detected by diffing a snapshot of a repository's git references against the
+//! previous one. Every repository that has at least one subscriber gets a poller task, which can
+//! be woken up early with [`Events::notify`] after a write went through the API.
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::response::sse;
+use futures_util::stream::{self, Stream, StreamExt as _};
+use serde::Serialize;
+use tokio::sync::{broadcast, watch, Notify};
+
+use radicle::cob::{issue, patch, ObjectId, TypeName};
+use radicle::git::Oid;
+use radicle::identity::RepoId;
+use radicle::prelude::NodeId;
+use radicle::storage::{ReadRepository, ReadStorage};
+use radicle::Profile;
+
+/// Number of past events kept per repository, for clients resuming with `Last-Event-ID`.
+pub const HISTORY_SIZE: usize = 256;
+/// How often storage is checked for changes while a repository has subscribers.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/// A change to a repository.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Change {
+    /// An issue was created.
+    #[serde(rename = "issue.created")]
+    IssueCreated { id: Oid, remote: NodeId },
+    /// An issue was updated by a remote.
+    #[serde(rename = "issue.updated")]
+    IssueUpdated { id: Oid, remote: NodeId },
+    /// A patch was created.
+    #[serde(rename = "patch.created")]
+    PatchCreated { id: Oid, remote: NodeId },
+    /// A patch was updated by a remote.
+    #[serde(rename = "patch.updated")]
+    PatchUpdated { id: Oid, remote: NodeId },
+    /// A remote signed a new set of refs.
+    #[serde(rename = "refs.announced")]
+    RefsAnnounced { remote: NodeId, sigrefs: Oid },
+    /// The canonical head of the repository moved.
+    #[serde(rename = "head.moved")]
+    HeadMoved { head: Oid, previous: Option<Oid> },
+    /// Events were missed and the client should refetch its state.
+    #[serde(rename = "resync")]
+    Resync,
+}
+
+impl Change {
+    /// The SSE event name of this change.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::IssueCreated { .. } => "issue.created",
+            Self::IssueUpdated { .. } => "issue.updated",
+            Self::PatchCreated { .. } => "patch.created",
+            Self::PatchUpdated { .. } => "patch.updated",
+            Self::RefsAnnounced { .. } => "refs.announced",
+            Self::HeadMoved { .. } => "head.moved",
+            Self::Resync => "resync",
+        }
+    }
+}
+
+/// A change, numbered for resumption.
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub id: u64,
+    pub change: Change,
+}
+
+/// State of a repository, as far as change detection is concerned.
+#[derive(Debug, Default, Clone)]
+struct Snapshot {
+    head: Option<Oid>,
+    refs: BTreeMap<String, Oid>,
+}
+
+impl Snapshot {
+    fn load(profile: &Profile, rid: RepoId) -> Result<Self, radicle::storage::RepositoryError> {
+        let repo = profile.storage.repository(rid)?;
+        let head = repo.head().ok().map(|(_, oid)| oid);
+        let mut refs = BTreeMap::new();
+
+        for r in repo.backend.references()? {
+            let r = r?;
+            if let (Some(name), Some(oid)) = (r.name(), r.target()) {
+                refs.insert(name.to_owned(), oid.into());
+            }
+        }
+        Ok(Self { head, refs })
+    }
+
+    /// Returns whether any remote has a ref for the given COB.
+    fn has_cob(&self, typename: &TypeName, id: &ObjectId) -> bool {
+        self.refs
+            .keys()
+            .filter_map(|name| Ref::parse(name))
+            .any(|r| matches!(r, Ref::Cob { typename: t, id: i, .. } if &t == typename && &i == id))
+    }
+
+    /// Compute the changes that lead from `self` to `other`.
+    fn diff(&self, other: &Snapshot) -> Vec<Change> {
+        let mut changes = Vec::new();
+
+        for (name, oid) in &other.refs {
+            if self.refs.get(name) == Some(oid) {
+                continue;
+            }
+            match Ref::parse(name) {
+                Some(Ref::Cob {
+                    remote,
+                    typename,
+                    id,
+                }) => {
+                    let created = !self.has_cob(&typename, &id);
+                    let id = *id;
+                    let change = if typename == *issue::TYPENAME {
+                        if created {
+                            Change::IssueCreated { id, remote }
+                        } else {
+                            Change::IssueUpdated { id, remote }
+                        }
+                    } else if typename == *patch::TYPENAME {
+                        if created {
+                            Change::PatchCreated { id, remote }
+                        } else {
+                            Change::PatchUpdated { id, remote }
+                        }
+                    } else {
+                        continue;
+                    };
+                    if !changes.contains(&change) {
+                        changes.push(change);
+                    }
+                }
+                Some(Ref::Sigrefs { remote }) => changes.push(Change::RefsAnnounced {
+                    remote,
+                    sigrefs: *oid,
+                }),
+                None => {}
+            }
+        }
+        if let Some(head) = other.head {
+            if self.head != Some(head) {
+                changes.push(Change::HeadMoved {
+                    head,
+                    previous: self.head,
+                });
+            }
+        }
+        changes
+    }
+}
+
+/// A namespaced reference we care about.
+enum Ref {
+    Cob {
+        remote: NodeId,
+        typename: TypeName,
+        id: ObjectId,
+    },
+    Sigrefs {
+        remote: NodeId,
+    },
+}
+
+impl Ref {
+    fn parse(name: &str) -> Option<Self> {
+        let rest = name.strip_prefix("refs/namespaces/")?;
+        let (remote, rest) = rest.split_once('/')?;
+        let remote = remote.parse().ok()?;
+
+        if rest == "refs/rad/sigrefs" {
+            return Some(Self::Sigrefs { remote });
+        }
+        let (typename, id) = rest.strip_prefix("refs/cobs/")?.split_once('/')?;
+
+        Some(Self::Cob {
+            remote,
+            typename: typename.parse().ok()?,
+            id: id.parse().ok()?,
+        })
+    }
+}
+
+/// Change tracking for a single repository.
+struct Hub {
+    sender: broadcast::Sender<Event>,
+    notify: Arc<Notify>,
+    history: VecDeque<Event>,
+    snapshot: Option<Snapshot>,
+    next_id: u64,
+    polling: bool,
+}
+
+impl Hub {
+    fn new() -> Self {
+        let (sender, _) = broadcast::channel(HISTORY_SIZE);
+
+        Self {
+            sender,
+            notify: Arc::new(Notify::new()),
+            history: VecDeque::with_capacity(HISTORY_SIZE),
+            snapshot: None,
+            next_id: 1,
+            polling: false,
+        }
+    }
+
+    fn publish(&mut self, change: Change) {
+        let event = Event {
+            id: self.next_id,
+            change,
+        };
+        self.next_id += 1;
+
+        if self.history.len() == HISTORY_SIZE {
+            self.history.pop_front();
+        }
+        self.history.push_back(event.clone());
+        // An error only means there is nobody listening right now.
+        self.sender.send(event).ok();
+    }
+
+    /// Events that a client who last saw `last` has missed. Returns `None` if some of them are
+    /// no longer in the history.
+    fn since(&self, last: u64) -> Option<Vec<Event>> {
+        let oldest = self.history.front().map_or(self.next_id, |e| e.id);
+        // Either events were dropped from the history, or the id was handed out before a restart.
+        if last.checked_add(1).map_or(true, |next| oldest > next) || last >= self.next_id {
+            return None;
+        }
+        Some(
+            self.history
+                .iter()
+                .filter(|e| e.id > last)
+                .cloned()
+                .collect(),
+        )
+    }
+}
+
+/// A subscription to the changes of a repository.
+pub struct Subscription {
+    /// Events to replay before listening on the receiver.
+    pub replay: Vec<Event>,
+    pub receiver: broadcast::Receiver<Event>,
+    /// Set once the server shuts down, which ends the subscription.
+    pub closed: watch::Receiver<bool>,
+}
+
+/// Change notifications for all repositories.
+#[derive(Clone)]
+pub struct Events {
+    hubs: Arc<Mutex<HashMap<RepoId, Arc<Mutex<Hub>>>>>,
+    /// How many times each repository was written to through the API.
+    versions: Arc<Mutex<HashMap<RepoId, u64>>>,
+    /// Notified every time a repository is written to through the API.
+    written: Arc<Notify>,
+    closed: Arc<watch::Sender<bool>>,
+}
+
+impl Default for Events {
+    fn default() -> Self {
+        let (closed, _) = watch::channel(false);
+
+        Self {
+            hubs: Arc::default(),
+            versions: Arc::default(),
+            written: Arc::default(),
+            closed: Arc::new(closed),
+        }
+    }
+}
+
+impl Events {
+    fn hub(&self, rid: RepoId) -> Arc<Mutex<Hub>> {
+        #[allow(clippy::unwrap_used)]
+        let mut hubs = self.hubs.lock().unwrap();
+        hubs.entry(rid)
+            .or_insert_with(|| Arc::new(Mutex::new(Hub::new())))
+            .clone()
+    }
+
+    /// Subscribe to the changes of a repository, optionally resuming after the event `last`.
+    pub async fn subscribe(
+        &self,
+        profile: Arc<Profile>,
+        rid: RepoId,
+        last: Option<u64>,
+    ) -> Subscription {
+        let hub = self.hub(rid);
+        #[allow(clippy::unwrap_used)]
+        let loaded = hub.lock().unwrap().snapshot.is_some();
+
+        // Loading a snapshot reads storage, so it's done off the async runtime, and without
+        // holding the lock.
+        let snapshot = if loaded {
+            None
+        } else {
+            load(profile.clone(), rid).await.ok()
+        };
+        #[allow(clippy::unwrap_used)]
+        let mut h = hub.lock().unwrap();
+
+        if h.snapshot.is_none() {
+            h.snapshot = snapshot;
+        }
+        let replay = match last {
+            Some(last) => h.since(last).unwrap_or_else(|| {
+                vec![Event {
+                    id: h.next_id - 1,
+                    change: Change::Resync,
+                }]
+            }),
+            None => Vec::new(),
+        };
+        let receiver = h.sender.subscribe();
+
+        if !h.polling {
+            h.polling = true;
+            tokio::spawn(poll(hub.clone(), h.notify.clone(), profile, rid));
+        }
+        Subscription {
+            replay,
+            receiver,
+            closed: self.closed.subscribe(),
+        }
+    }
+
+    /// End all subscriptions, current and future, eg. so that the server can shut down without
+    /// waiting for event streams that would otherwise never end.
+    pub fn close(&self) {
+        self.closed.send_replace(true);
+    }
+
+    /// Version of a repository, which changes every time it's written to through the API.
+    pub fn version(&self, rid: RepoId) -> u64 {
+        #[allow(clippy::unwrap_used)]
+        self.versions
+            .lock()
+            .unwrap()
+            .get(&rid)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Wait until a repository is written to through the API. Writes made since the last call
+    /// return right away, see [`Events::version`] to tell which repositories changed.
+    pub async fn written(&self) {
+        self.written.notified().await;
+    }
+
+    /// Check a repository for changes right away, eg. after it was written to.
+    pub fn notify(&self, rid: RepoId) {
+        #[allow(clippy::unwrap_used)]
+        {
+            *self.versions.lock().unwrap().entry(rid).or_default() += 1;
+        }
+        self.written.notify_one();
+
+        #[allow(clippy::unwrap_used)]
+        let hubs = self.hubs.lock().unwrap();
+        if let Some(hub) = hubs.get(&rid) {
+            #[allow(clippy::unwrap_used)]
+            hub.lock().unwrap().notify.notify_one();
+        }
+    }
+}
+
+/// Publish the changes of a repository until it has no subscribers left.
+async fn poll(hub: Arc<Mutex<Hub>>, notify: Arc<Notify>, profile: Arc<Profile>, rid: RepoId) {
+    loop {
+        tokio::select! {
+            _ = notify.notified() => {}
+            _ = tokio::time::sleep(POLL_INTERVAL) => {}
+        }
+        {
+            #[allow(clippy::unwrap_used)]
+            let mut h = hub.lock().unwrap();
+
+            if h.sender.receiver_count() == 0 {
+                // The snapshot is kept, so that changes made while nobody was listening are
+                // published once the next client subscribes.
+                h.polling = false;
+                break;
+            }
+        }
+        let snapshot = match load(profile.clone(), rid).await {
+            Ok(snapshot) => snapshot,
+            Err(err) => {
+                tracing::error!("Error loading refs of {rid}: {err}");
+                continue;
+            }
+        };
+        #[allow(clippy::unwrap_used)]
+        let mut h = hub.lock().unwrap();
+        let changes = h
+            .snapshot
+            .as_ref()
+            .map(|s| s.diff(&snapshot))
+            .unwrap_or_default();
+
+        for change in changes {
+            h.publish(change);
+        }
+        h.snapshot = Some(snapshot);
+    }
+}
+
+/// Load a snapshot of a repository on the blocking thread pool.
+async fn load(profile: Arc<Profile>, rid: RepoId) -> Result<Snapshot, String> {
+    tokio::task::spawn_blocking(move || Snapshot::load(&profile, rid))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+impl Subscription {
+    /// Turn the subscription into a stream of server-sent events.
+    pub fn into_stream(self) -> impl Stream<Item = Result<sse::Event, Infallible>> {
+        let live = stream::unfold(self.receiver, |mut receiver| async move {
+            let event = match receiver.recv().await {
+                Ok(event) => sse_event(Some(event.id), &event.change),
+                // The client can't catch up anymore; it keeps the last id it has seen.
+                Err(broadcast::error::RecvError::Lagged(_)) => sse_event(None, &Change::Resync),
+                Err(broadcast::error::RecvError::Closed) => return None,
+            };
+            Some((Ok(event), receiver))
+        });
+
+        stream::iter(
+            self.replay
+                .into_iter()
+                .map(|e| Ok(sse_event(Some(e.id), &e.change))),
+        )
+        .chain(live)
+        .take_until(closed(self.closed))
+    }
+}
+
+/// Wait until `closed` is set.
+async fn closed(mut closed: watch::Receiver<bool>) {
+    while !*closed.borrow_and_update() {
+        if closed.changed().await.is_err() {
+            // The sender is gone, so nothing is left to stream from either.
+            return;
+        }
+    }
+}
+
+fn sse_event(id: Option<u64>, change: &Change) -> sse::Event {
+    let event = sse::Event::default()
+        .event(change.name())
+        .json_data(change)
+        .unwrap_or_default();
+
+    match id {
+        Some(id) => event.id(id.to_string()),
+        None => event,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_since() {
+        let mut hub = Hub::new();
+        hub.publish(Change::Resync);
+        hub.publish(Change::Resync);
+
+        assert_eq!(hub.since(1).map(|events| events.len()), Some(1));
+        assert_eq!(hub.since(2).map(|events| events.len()), Some(0));
+        assert!(hub.since(3).is_none());
+        assert!(hub.since(u64::MAX).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_close() {
+        let events = Events::default();
+        let (_sender, receiver) = broadcast::channel(HISTORY_SIZE);
+        let subscription = Subscription {
+            replay: vec![Event {
+                id: 1,
+                change: Change::Resync,
+            }],
+            receiver,
+            closed: events.closed.subscribe(),
+        };
+        let mut stream = Box::pin(subscription.into_stream());
+
+        assert!(stream.next().await.is_some());
+
+        events.close();
+
+        assert!(stream.next().await.is_none());
+    }
+}
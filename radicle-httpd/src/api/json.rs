@@ -12,8 +12,9 @@ use serde_json::{json, Value};
 use radicle::cob::issue::{Issue, IssueId};
 use radicle::cob::patch::{Merge, Patch, PatchId, Review};
 use radicle::cob::thread::{Comment, CommentId, Edit};
-use radicle::cob::{ActorId, Author};
+use radicle::cob::{ActorId, Author, Label};
 use radicle::git::RefString;
+use radicle::identity::Did;
 use radicle::node::{Alias, AliasStore};
 use radicle::prelude::NodeId;
 use radicle::storage::{git, refs, RemoteRepository};
@@ -21,7 +22,12 @@ use radicle_surf::blob::Blob;
 use radicle_surf::tree::{EntryKind, Tree};
 use radicle_surf::{Commit, Oid};
 
-use crate::api::auth::Session;
+use crate::api::auth::{Session, Token};
+use crate::api::search_index::Hit;
+use crate::api::timeline::TimelineEvent;
+use crate::api::Usage;
+use crate::cob::board::{Board, BoardId};
+use crate::cob::labels::LabelMeta;
 
 /// Returns JSON of a commit.
 pub(crate) fn commit(commit: &Commit) -> Value {
@@ -50,7 +56,21 @@ pub(crate) fn session(session_id: String, session: &Session) -> Value {
       "publicKey": session.public_key,
       "alias": session.alias,
       "issuedAt": session.issued_at.unix_timestamp(),
-      "expiresAt": session.expires_at.unix_timestamp()
+      "expiresAt": session.expires_at.unix_timestamp(),
+      "scope": session.scope,
+    })
+}
+
+/// Returns JSON of an access token, without its secret.
+pub(crate) fn token(token: &Token) -> Value {
+    json!({
+      "id": token.id,
+      "name": token.name,
+      "publicKey": token.public_key,
+      "alias": token.alias,
+      "issuedAt": token.issued_at.unix_timestamp(),
+      "expiresAt": token.expires_at.unix_timestamp(),
+      "scope": token.scope,
     })
 }
 
@@ -116,6 +136,18 @@ pub(crate) fn issue(id: IssueId, issue: Issue, aliases: &impl AliasStore) -> Val
     })
 }
 
+/// Returns JSON for a planning `board`.
+pub(crate) fn board(id: BoardId, board: Board, aliases: &impl AliasStore) -> Value {
+    json!({
+        "id": id.to_string(),
+        "name": board.name(),
+        "description": board.description(),
+        "author": author(&Author::from(*board.author()), aliases.alias(board.author())),
+        "timestamp": board.timestamp().as_secs(),
+        "columns": board.columns(),
+    })
+}
+
 /// Returns JSON for a `patch`.
 pub(crate) fn patch(
     id: PatchId,
@@ -183,6 +215,24 @@ fn reactions(
         .collect::<Vec<_>>()
 }
 
+/// Returns JSON of a search result.
+pub(crate) fn search_hit(hit: &Hit, aliases: &impl AliasStore) -> Value {
+    json!({
+        "type": hit.kind,
+        "rid": hit.rid,
+        "id": hit.id,
+        "title": hit.title,
+        "state": hit.state,
+        "author": hit.author.map(|did| author(&Author::new(did), aliases.alias(did.as_key()))),
+        "timestamp": hit.timestamp,
+        "score": hit.score,
+        "highlights": {
+            "title": hit.title_highlight,
+            "body": hit.snippet,
+        },
+    })
+}
+
 /// Returns JSON for an `author` and fills in `alias` when present.
 pub(crate) fn author(author: &Author, alias: Option<Alias>) -> Value {
     match alias {
@@ -194,6 +244,42 @@ pub(crate) fn author(author: &Author, alias: Option<Alias>) -> Value {
     }
 }
 
+/// Returns JSON for a project `label`, with its usage and description.
+pub(crate) fn label(label: &Label, usage: Usage, meta: Option<&LabelMeta>) -> Value {
+    json!({
+        "name": label,
+        "issues": usage.issues,
+        "patches": usage.patches,
+        "color": meta.and_then(|m| m.color.as_ref()),
+        "description": meta.map(|m| m.description.as_str()),
+    })
+}
+
+/// Returns JSON for a project assignee, with its usage, and fills in `alias` when present.
+pub(crate) fn assignee(did: &Did, usage: Usage, aliases: &impl AliasStore) -> Value {
+    let mut value = author(&Author::new(*did), aliases.alias(did));
+    value["issues"] = usage.issues.into();
+    value["patches"] = usage.patches.into();
+
+    value
+}
+
+/// Returns JSON for an issue or patch timeline `event`, and fills in `alias` when present.
+pub(crate) fn timeline_event(event: TimelineEvent, aliases: &impl AliasStore) -> Value {
+    let mut value = json!({
+        "entry": event.entry,
+        "author": author(&Author::from(event.author), aliases.alias(&event.author)),
+        "timestamp": event.timestamp.as_secs(),
+    });
+    if let (Some(value), Ok(Value::Object(fields))) =
+        (value.as_object_mut(), serde_json::to_value(event.event))
+    {
+        value.extend(fields);
+    }
+
+    value
+}
+
 /// Returns JSON for a patch `Merge` and fills in `alias` when present.
 fn merge(nid: &NodeId, merge: &Merge, aliases: &impl AliasStore) -> Value {
     json!({
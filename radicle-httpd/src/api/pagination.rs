@@ -0,0 +1,620 @@
+//! Pagination of list endpoints.
+//!
+//! Lists are paged with an opaque `cursor` and a `perPage` size, capped at [`MAX_PER_PAGE`].
+//! A cursor holds the sort key of the item a page starts after, or ends before, so that pages
+//! don't shift when items are added to or removed from the list in between requests. Cursors
+//! are only valid for the path and query they were handed out for; changing the sort order or
+//! filters of a list requires starting again from its first page.
+//!
+//! Responses carry links to the next and previous pages in the `Link` header, and, for lists
+//! that are known in full, the number of items across all pages in the `X-Total-Count` header.
+//! The `page` number of earlier versions of the API is still accepted, when no cursor is given.
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+
+use axum::async_trait;
+use axum::extract::{FromRequestParts, OriginalUri};
+use axum::http::request::Parts;
+use axum::http::{header, HeaderMap, HeaderValue, Uri};
+use axum::response::{IntoResponse, IntoResponseParts, Response, ResponseParts};
+use axum::Json;
+use base64::prelude::{Engine, BASE64_URL_SAFE_NO_PAD};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest as _, Sha256};
+
+use crate::api::error::Error;
+use crate::api::{SortDirection, TOTAL_COUNT_HEADER};
+use crate::axum_extra::Query;
+
+/// Page size used when the request doesn't specify one.
+pub const DEFAULT_PER_PAGE: usize = 10;
+/// Largest page size a request can ask for.
+pub const MAX_PER_PAGE: usize = 100;
+
+/// Query parameters used for pagination, as opposed to those selecting and sorting the list.
+const PAGE_PARAMS: [&str; 3] = ["cursor", "page", "perPage"];
+
+/// Position in a list, handed out to clients as an opaque token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cursor {
+    /// Sort key of the item next to the page.
+    key: Value,
+    /// Whether the page ends before the item, rather than starting after it.
+    before: bool,
+    /// Fingerprint of the request the cursor was handed out for, see [`fingerprint`].
+    query: String,
+}
+
+impl Cursor {
+    /// Decode the sort key of the cursor.
+    fn key<K: DeserializeOwned>(&self) -> Result<K, Error> {
+        serde_json::from_value(self.key.clone())
+            .map_err(|_| Error::BadRequest("invalid cursor".to_owned()))
+    }
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let raw =
+            serde_json::to_vec(&(&self.key, self.before, &self.query)).map_err(|_| fmt::Error)?;
+        f.write_str(&BASE64_URL_SAFE_NO_PAD.encode(raw))
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const INVALID: &str = "invalid cursor";
+
+        let raw = BASE64_URL_SAFE_NO_PAD.decode(s).map_err(|_| INVALID)?;
+        let (key, before, query) = serde_json::from_slice(&raw).map_err(|_| INVALID)?;
+
+        Ok(Self { key, before, query })
+    }
+}
+
+impl Serialize for Cursor {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Cursor {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Fingerprint of the path and the list parameters of a request, ie. its query parameters
+/// other than those used for pagination.
+fn fingerprint(uri: &Uri) -> String {
+    let mut pairs = url::form_urlencoded::parse(uri.query().unwrap_or_default().as_bytes())
+        .filter(|(k, _)| !PAGE_PARAMS.contains(&k.as_ref()))
+        .collect::<Vec<_>>();
+    pairs.sort();
+
+    let mut hasher = Sha256::new();
+    hasher.update(uri.path());
+    for (k, v) in pairs {
+        hasher.update([0]);
+        hasher.update(k.as_bytes());
+        hasher.update([b'=']);
+        hasher.update(v.as_bytes());
+    }
+    let digest = hasher.finalize();
+    let mut prefix = [0; 8];
+    prefix.copy_from_slice(&digest[..8]);
+
+    format!("{:016x}", u64::from_be_bytes(prefix))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PageQuery {
+    cursor: Option<Cursor>,
+    page: Option<usize>,
+    per_page: Option<usize>,
+}
+
+/// Pagination parameters of a list request.
+#[derive(Debug, Clone)]
+pub struct Pagination {
+    cursor: Option<Cursor>,
+    page: Option<usize>,
+    per_page: Option<usize>,
+    default_per_page: usize,
+    query: String,
+    uri: Uri,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Pagination
+where
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(PageQuery {
+            cursor,
+            page,
+            per_page,
+        }) = Query::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let uri = match parts.extensions.get::<OriginalUri>() {
+            Some(OriginalUri(uri)) => uri.clone(),
+            None => parts.uri.clone(),
+        };
+        let query = fingerprint(&uri);
+
+        if cursor.as_ref().is_some_and(|c| c.query != query) {
+            return Err(Error::BadRequest(
+                "cursor was handed out for a different sort order or filters".to_owned(),
+            )
+            .into_response());
+        }
+
+        Ok(Self {
+            cursor,
+            page,
+            per_page,
+            default_per_page: DEFAULT_PER_PAGE,
+            query,
+            uri,
+        })
+    }
+}
+
+impl Pagination {
+    /// Use a different page size when the request doesn't specify one.
+    pub fn default_per_page(mut self, per_page: usize) -> Self {
+        self.default_per_page = per_page;
+        self
+    }
+
+    /// Whether the request specified a page size.
+    pub fn has_per_page(&self) -> bool {
+        self.per_page.is_some()
+    }
+
+    /// Number of items on a page.
+    pub fn limit(&self) -> usize {
+        self.per_page
+            .unwrap_or(self.default_per_page)
+            .clamp(1, MAX_PER_PAGE)
+    }
+
+    /// Number of items before the page, when it is requested by number.
+    fn offset(&self) -> usize {
+        self.page.unwrap_or_default().saturating_mul(self.limit())
+    }
+
+    /// Cursor to the page after the item with the given sort key.
+    fn after(&self, key: impl Serialize) -> Option<Cursor> {
+        self.cursor_at(key, false)
+    }
+
+    /// Cursor to the page before the item with the given sort key.
+    fn before(&self, key: impl Serialize) -> Option<Cursor> {
+        self.cursor_at(key, true)
+    }
+
+    fn cursor_at(&self, key: impl Serialize, before: bool) -> Option<Cursor> {
+        Some(Cursor {
+            key: serde_json::to_value(key).ok()?,
+            before,
+            query: self.query.clone(),
+        })
+    }
+
+    fn links(&self, total: Option<usize>, next: Option<Cursor>, prev: Option<Cursor>) -> Links {
+        Links {
+            total,
+            next,
+            prev,
+            limit: self.limit(),
+            uri: self.uri.clone(),
+        }
+    }
+
+    /// Take the requested page out of all the items of a list, sorted by `key` in the given
+    /// direction. Keys must be unique, eg. by ending with the id of the item.
+    pub fn paginate<T, K>(
+        &self,
+        mut items: Vec<T>,
+        key: impl Fn(&T) -> K,
+        direction: SortDirection,
+    ) -> Result<Page<T>, Error>
+    where
+        K: Ord + Serialize + DeserializeOwned,
+    {
+        items.sort_by(|a, b| direction.apply(key(a).cmp(&key(b))));
+
+        let limit = self.limit();
+        let total = items.len();
+        // Number of items that come before the given key, or up to and including it.
+        let preceding = |k: &K, inclusive: bool| {
+            items.partition_point(|item| match direction.apply(key(item).cmp(k)) {
+                std::cmp::Ordering::Less => true,
+                std::cmp::Ordering::Equal => inclusive,
+                std::cmp::Ordering::Greater => false,
+            })
+        };
+        let (start, end) = match &self.cursor {
+            Some(cursor) if cursor.before => {
+                let end = preceding(&cursor.key()?, false);
+                (end.saturating_sub(limit), end)
+            }
+            Some(cursor) => {
+                let start = preceding(&cursor.key()?, true);
+                (start, start.saturating_add(limit).min(total))
+            }
+            None => {
+                let start = self.offset().min(total);
+                (start, start.saturating_add(limit).min(total))
+            }
+        };
+        let next = (end < total)
+            .then(|| items[..end].last())
+            .flatten()
+            .and_then(|item| self.after(key(item)));
+        let prev = (start > 0)
+            .then(|| items.get(start))
+            .flatten()
+            .and_then(|item| self.before(key(item)));
+        let links = self.links(Some(total), next, prev);
+
+        Ok(Page {
+            items: items.drain(start..end).collect(),
+            links,
+        })
+    }
+
+    /// Take the requested page out of a list that can only be walked in order, eg. the history
+    /// of a repository. The list is walked up to the item after the page, and the number of
+    /// items across all pages is left out. Keys must be unique, eg. by being the item's id.
+    pub fn walk<T, K>(
+        &self,
+        items: impl IntoIterator<Item = T>,
+        key: impl Fn(&T) -> K,
+    ) -> Result<Page<T>, Error>
+    where
+        K: PartialEq + Serialize + DeserializeOwned,
+    {
+        const STALE: &str = "cursor points to an item that is no longer in the list";
+
+        let limit = self.limit();
+        let mut items = items.into_iter();
+        let (page, next, prev) = match &self.cursor {
+            Some(cursor) if cursor.before => {
+                let k = cursor.key::<K>()?;
+                let mut page = VecDeque::with_capacity(limit);
+                let mut more = false;
+                let mut found = false;
+
+                for item in items {
+                    if key(&item) == k {
+                        found = true;
+                        break;
+                    }
+                    if page.len() == limit {
+                        page.pop_front();
+                        more = true;
+                    }
+                    page.push_back(item);
+                }
+                if !found {
+                    return Err(Error::BadRequest(STALE.to_owned()));
+                }
+                let next = page.back().and_then(|item| self.after(key(item)));
+                let prev = more
+                    .then(|| page.front())
+                    .flatten()
+                    .and_then(|item| self.before(key(item)));
+
+                (Vec::from(page), next, prev)
+            }
+            Some(cursor) => {
+                let k = cursor.key::<K>()?;
+                if !items.by_ref().any(|item| key(&item) == k) {
+                    return Err(Error::BadRequest(STALE.to_owned()));
+                }
+                let (page, next) = self.take(items, &key);
+                let prev = page.first().and_then(|item| self.before(key(item)));
+
+                (page, next, prev)
+            }
+            None => {
+                let skipped = items.by_ref().take(self.offset()).count();
+                let (page, next) = self.take(items, &key);
+                let prev = (skipped > 0)
+                    .then(|| page.first())
+                    .flatten()
+                    .and_then(|item| self.before(key(item)));
+
+                (page, next, prev)
+            }
+        };
+
+        Ok(Page {
+            items: page,
+            links: self.links(None, next, prev),
+        })
+    }
+
+    /// Take a page from the start of `items`, and the cursor to the next page if there are
+    /// items left after it.
+    fn take<T, K: Serialize>(
+        &self,
+        items: impl Iterator<Item = T>,
+        key: impl Fn(&T) -> K,
+    ) -> (Vec<T>, Option<Cursor>) {
+        let limit = self.limit();
+        let mut page = items.take(limit.saturating_add(1)).collect::<Vec<_>>();
+        let next = if page.len() > limit {
+            page.truncate(limit);
+            page.last().and_then(|item| self.after(key(item)))
+        } else {
+            None
+        };
+
+        (page, next)
+    }
+}
+
+/// A page of a list.
+#[derive(Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    links: Links,
+}
+
+impl<T> Page<T> {
+    /// Map the items of the page.
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            links: self.links,
+        }
+    }
+
+    /// Map the items of the page, leaving out those mapped to `None`. The links and total are
+    /// those of the original page.
+    pub fn filter_map<U>(self, f: impl FnMut(T) -> Option<U>) -> Page<U> {
+        Page {
+            items: self.items.into_iter().filter_map(f).collect(),
+            links: self.links,
+        }
+    }
+
+    /// Like [`Page::filter_map`], with a function that can fail.
+    pub fn try_filter_map<U, E>(
+        self,
+        f: impl FnMut(T) -> Result<Option<U>, E>,
+    ) -> Result<Page<U>, E> {
+        let items = self
+            .items
+            .into_iter()
+            .map(f)
+            .filter_map(Result::transpose)
+            .collect::<Result<_, _>>()?;
+
+        Ok(Page {
+            items,
+            links: self.links,
+        })
+    }
+
+    /// Set the number of items across all pages, for lists walked with [`Pagination::walk`]
+    /// whose length is known.
+    pub fn with_total(mut self, total: usize) -> Self {
+        self.links.total = Some(total);
+        self
+    }
+
+    /// Split the page into its pagination headers and items.
+    pub fn into_parts(self) -> (Links, Vec<T>) {
+        (self.links, self.items)
+    }
+}
+
+impl<T: Serialize> IntoResponse for Page<T> {
+    fn into_response(self) -> Response {
+        let (links, items) = self.into_parts();
+        (links, Json(items)).into_response()
+    }
+}
+
+/// Pagination headers of a page.
+#[derive(Debug)]
+pub struct Links {
+    total: Option<usize>,
+    next: Option<Cursor>,
+    prev: Option<Cursor>,
+    limit: usize,
+    uri: Uri,
+}
+
+impl Links {
+    /// Link to the page at `cursor`, keeping the other query parameters of the request.
+    fn link(&self, cursor: &Cursor, rel: &str) -> String {
+        let mut query = url::form_urlencoded::Serializer::new(String::new());
+        let pairs = url::form_urlencoded::parse(self.uri.query().unwrap_or_default().as_bytes());
+        for (k, v) in pairs {
+            if !PAGE_PARAMS.contains(&k.as_ref()) {
+                query.append_pair(&k, &v);
+            }
+        }
+        query
+            .append_pair("cursor", &cursor.to_string())
+            .append_pair("perPage", &self.limit.to_string());
+
+        format!("<{}?{}>; rel=\"{rel}\"", self.uri.path(), query.finish())
+    }
+}
+
+impl IntoResponseParts for Links {
+    type Error = std::convert::Infallible;
+
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        let mut headers = HeaderMap::new();
+        if let Some(total) = self.total {
+            headers.insert(TOTAL_COUNT_HEADER, HeaderValue::from(total));
+        }
+
+        let links = [(&self.next, "next"), (&self.prev, "prev")]
+            .into_iter()
+            .filter_map(|(cursor, rel)| cursor.as_ref().map(|c| self.link(c, rel)))
+            .collect::<Vec<_>>();
+        if !links.is_empty() {
+            if let Ok(value) = HeaderValue::from_str(&links.join(", ")) {
+                headers.insert(header::LINK, value);
+            }
+        }
+        res.headers_mut().extend(headers);
+
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pagination(uri: &'static str, page: Option<usize>, per_page: Option<usize>) -> Pagination {
+        let uri = Uri::from_static(uri);
+        Pagination {
+            cursor: None,
+            page,
+            per_page,
+            default_per_page: DEFAULT_PER_PAGE,
+            query: fingerprint(&uri),
+            uri,
+        }
+    }
+
+    fn at(p: &Pagination, cursor: Option<Cursor>) -> Pagination {
+        Pagination {
+            cursor,
+            ..p.clone()
+        }
+    }
+
+    #[test]
+    fn test_cursor_roundtrip() {
+        let p = pagination("/", None, None);
+        let cursor = p.after((42, "id")).unwrap();
+        let encoded = cursor.to_string();
+
+        assert_eq!(encoded.parse::<Cursor>(), Ok(cursor));
+        assert!("42".parse::<Cursor>().is_err());
+        assert!("not base64!".parse::<Cursor>().is_err());
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let query = |uri| fingerprint(&Uri::from_static(uri));
+
+        assert_eq!(
+            query("/issues?sort=created&state=open&perPage=2"),
+            query("/issues?state=open&cursor=abc&sort=created")
+        );
+        assert_ne!(query("/issues?sort=created"), query("/issues?sort=updated"));
+        assert_ne!(query("/issues"), query("/patches"));
+    }
+
+    #[test]
+    fn test_paginate() {
+        let p = pagination(
+            "/api/v1/projects?show=all&page=1&perPage=3",
+            Some(1),
+            Some(3),
+        );
+        let page = p
+            .paginate((0..10).collect(), |n| *n, SortDirection::Asc)
+            .unwrap();
+
+        assert_eq!(page.items, vec![3, 4, 5]);
+        assert_eq!(page.links.total, Some(10));
+        assert_eq!(page.links.next, p.after(5));
+        assert_eq!(page.links.prev, p.before(3));
+        assert_eq!(
+            page.links.link(&p.after(5).unwrap(), "next"),
+            format!(
+                "</api/v1/projects?show=all&cursor={}&perPage=3>; rel=\"next\"",
+                p.after(5).unwrap()
+            )
+        );
+
+        // Pages don't shift when items before them are removed.
+        let next = at(&p, page.links.next);
+        let page = next
+            .paginate(vec![0, 6, 7, 8, 9], |n| *n, SortDirection::Asc)
+            .unwrap();
+        assert_eq!(page.items, vec![6, 7, 8]);
+
+        let prev = at(&p, p.before(6));
+        let page = prev
+            .paginate((0..10).collect(), |n| *n, SortDirection::Asc)
+            .unwrap();
+        assert_eq!(page.items, vec![3, 4, 5]);
+        assert_eq!(page.links.prev, p.before(3));
+
+        let last = at(&pagination("/", None, None), p.after(7));
+        let page = last
+            .paginate((0..10).collect(), |n| *n, SortDirection::Desc)
+            .unwrap();
+        assert_eq!(page.items, vec![6, 5, 4, 3, 2, 1, 0]);
+        assert_eq!(page.links.next, None);
+        assert_eq!(page.links.prev, last.before(6));
+    }
+
+    #[test]
+    fn test_walk() {
+        let p = pagination("/commits", None, Some(3));
+        let page = p.walk(0.., |n| *n).unwrap();
+
+        assert_eq!(page.items, vec![0, 1, 2]);
+        assert_eq!(page.links.total, None);
+        assert_eq!(page.links.next, p.after(2));
+        assert_eq!(page.links.prev, None);
+
+        let page = at(&p, p.after(2)).walk(0..7, |n| *n).unwrap();
+        assert_eq!(page.items, vec![3, 4, 5]);
+        assert_eq!(page.links.next, p.after(5));
+        assert_eq!(page.links.prev, p.before(3));
+
+        let page = at(&p, p.before(5)).walk(0..7, |n| *n).unwrap();
+        assert_eq!(page.items, vec![2, 3, 4]);
+        assert_eq!(page.links.next, p.after(4));
+        assert_eq!(page.links.prev, p.before(2));
+
+        let page = pagination("/commits", Some(2), Some(3))
+            .walk(0..7, |n| *n)
+            .unwrap();
+        assert_eq!(page.items, vec![6]);
+        assert_eq!(page.links.next, None);
+
+        assert!(at(&p, p.after(42)).walk(0..7, |n| *n).is_err());
+        assert!(at(&p, p.after("a")).walk(0..7, |n: &u32| *n).is_err());
+    }
+
+    #[test]
+    fn test_max_per_page() {
+        let p = pagination("/", None, Some(MAX_PER_PAGE * 10));
+        assert_eq!(p.limit(), MAX_PER_PAGE);
+        assert_eq!(
+            p.paginate((0..1000).collect(), |n| *n, SortDirection::Asc)
+                .unwrap()
+                .items
+                .len(),
+            MAX_PER_PAGE
+        );
+    }
+}
@@ -0,0 +1,807 @@
+//! Full-text search over issues, patches and projects.
+//!
+//! Documents are kept in an inverted index, in an SQLite database under the radicle home: for
+//! every term, the documents it appears in and how often. The index is kept up to date by a
+//! background task, see [`index_periodically`], so that searching only ever reads it. A
+//! repository is refreshed by comparing the COB references in storage with the ones each
+//! document was indexed at, so that only the COBs that changed are read again. This happens as
+//! soon as the repository is written to through the API, and at least every
+//! [`REFRESH_INTERVAL`], to pick up changes fetched by the node.
+//!
+//! Results are ranked with BM25, counting terms in titles more than terms in bodies.
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use std::{fmt, iter};
+
+use serde::{Deserialize, Serialize};
+use sqlite as sql;
+
+use radicle::cob::{issue, patch};
+use radicle::git::raw as git2;
+use radicle::identity::{DocAt, RepoId};
+use radicle::issue::cache::Issues as _;
+use radicle::patch::cache::Patches as _;
+use radicle::prelude::Did;
+use radicle::storage::git::Repository;
+use radicle::storage::{ReadRepository, ReadStorage};
+use radicle::Profile;
+
+use crate::api::{db, Context, Error};
+
+/// Name of the search index database file, in the node directory of the radicle home.
+pub const SEARCH_DB_FILE: &str = "httpd-search.db";
+/// How many times a term in a title counts for, compared to one in a body.
+const TITLE_WEIGHT: i64 = 3;
+/// BM25 term frequency saturation.
+const K1: f64 = 1.2;
+/// BM25 document length normalization.
+const B: f64 = 0.75;
+/// Longest term that is indexed, in characters.
+const MAX_TERM_LENGTH: usize = 64;
+/// Bytes of body text shown before the first match in a snippet.
+const SNIPPET_CONTEXT: usize = 60;
+/// Length of a snippet, in bytes.
+const SNIPPET_LENGTH: usize = 200;
+/// Longest time a repository's index goes without being refreshed.
+pub const REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// What a document is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Kind {
+    Issue,
+    Patch,
+    Project,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 3] = [Kind::Issue, Kind::Patch, Kind::Project];
+    pub const COBS: [Kind; 2] = [Kind::Issue, Kind::Patch];
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Issue => "issue",
+            Self::Patch => "patch",
+            Self::Project => "project",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|k| k.as_str() == s)
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A piece of highlighted text.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Segment {
+    pub text: String,
+    /// Whether the text matches the query.
+    #[serde(rename = "match")]
+    pub matched: bool,
+}
+
+/// A search result.
+#[derive(Debug, Clone)]
+pub struct Hit {
+    pub kind: Kind,
+    pub rid: RepoId,
+    /// COB id, or the repository id for projects.
+    pub id: String,
+    pub title: String,
+    pub state: Option<String>,
+    pub author: Option<Did>,
+    pub timestamp: Option<u64>,
+    pub score: f64,
+    /// The title, with matching terms highlighted.
+    pub title_highlight: Vec<Segment>,
+    /// An excerpt of the body around the first match, if the body matches.
+    pub snippet: Option<Vec<Segment>>,
+}
+
+/// A document matching a search, before its contents are loaded.
+#[derive(Debug, Clone, Copy)]
+pub struct Match {
+    doc: i64,
+    score: f64,
+}
+
+impl Match {
+    /// Sort key, putting the best matches first. Scores are positive, so their bits order the
+    /// same way the scores do.
+    pub fn key(&self) -> (Reverse<u64>, i64) {
+        (Reverse(self.score.to_bits()), self.doc)
+    }
+}
+
+/// A document, as indexed.
+struct Document {
+    kind: Kind,
+    id: String,
+    /// What the document was indexed at, to tell whether it changed.
+    version: String,
+    title: String,
+    body: String,
+    state: Option<&'static str>,
+    author: Option<Did>,
+    timestamp: Option<u64>,
+}
+
+impl Document {
+    fn issue(id: &issue::IssueId, issue: &issue::Issue, version: String) -> Self {
+        let body = issue
+            .comments()
+            .map(|(_, c)| c.body())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let state = match issue.state() {
+            issue::State::Open => "open",
+            issue::State::Closed { .. } => "closed",
+        };
+
+        Self {
+            kind: Kind::Issue,
+            id: id.to_string(),
+            version,
+            title: issue.title().to_owned(),
+            body,
+            state: Some(state),
+            author: Some(issue.author().id),
+            timestamp: Some(issue.timestamp().as_secs()),
+        }
+    }
+
+    fn patch(id: &patch::PatchId, patch: &patch::Patch, version: String) -> Self {
+        let mut body = Vec::new();
+        for (_, revision) in patch.revisions() {
+            if !body.contains(&revision.description()) {
+                body.push(revision.description());
+            }
+            body.extend(revision.discussion().comments().map(|(_, c)| c.body()));
+            for (_, review) in revision.reviews() {
+                body.extend(review.summary());
+                body.extend(review.comments().map(|(_, c)| c.body()));
+            }
+        }
+        let state = match patch.state() {
+            patch::State::Draft => "draft",
+            patch::State::Open { .. } => "open",
+            patch::State::Archived => "archived",
+            patch::State::Merged { .. } => "merged",
+        };
+
+        Self {
+            kind: Kind::Patch,
+            id: id.to_string(),
+            version,
+            title: patch.title().to_owned(),
+            body: body.join("\n\n"),
+            state: Some(state),
+            author: Some(patch.author().id),
+            timestamp: Some(patch.timestamp().as_secs()),
+        }
+    }
+
+    fn project(rid: RepoId, doc: &DocAt) -> Result<Self, Error> {
+        let project = doc.project()?;
+
+        Ok(Self {
+            kind: Kind::Project,
+            id: rid.to_string(),
+            version: doc.blob.to_string(),
+            title: project.name().to_owned(),
+            body: project.description().to_owned(),
+            state: None,
+            author: None,
+            timestamp: None,
+        })
+    }
+}
+
+/// The search index.
+pub struct SearchIndex {
+    db: sql::ConnectionThreadSafe,
+    /// When each repository was last refreshed, and at what version.
+    refreshed: Mutex<HashMap<RepoId, (u64, Instant)>>,
+}
+
+impl fmt::Debug for SearchIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SearchIndex(..)")
+    }
+}
+
+impl SearchIndex {
+    /// Schema migrations of the index, see [`db::migrate`].
+    const MIGRATIONS: &'static [&'static str] = &["CREATE TABLE IF NOT EXISTS `documents` (
+          `id`         integer primary key,
+          `rid`        text    not null,
+          `kind`       text    not null,
+          `cob`        text    not null,
+          `version`    text    not null,
+          `title`      text    not null,
+          `body`       text    not null,
+          `state`      text,
+          `author`     text,
+          `timestamp`  integer,
+          `length`     integer not null,
+          UNIQUE (`rid`, `kind`, `cob`)
+        ) STRICT;
+        CREATE TABLE IF NOT EXISTS `postings` (
+          `term`      text    not null,
+          `doc`       integer not null references `documents` (`id`) on delete cascade,
+          `title_tf`  integer not null,
+          `body_tf`   integer not null,
+          PRIMARY KEY (`term`, `doc`)
+        ) STRICT, WITHOUT ROWID;
+        CREATE INDEX IF NOT EXISTS `postings_doc` ON `postings` (`doc`);"];
+
+    /// Open the index at the given path. Creates a new index if it doesn't exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, sql::Error> {
+        let db = db::open(path)?;
+        db.execute("PRAGMA foreign_keys = ON")?;
+        db::migrate(&db, Self::MIGRATIONS, |_, _| Ok::<_, sql::Error>(()))?;
+
+        Ok(Self {
+            db,
+            refreshed: Mutex::default(),
+        })
+    }
+
+    /// Like [`SearchIndex::refresh`], but only if the repository's `version` changed since it
+    /// was last refreshed, or that was more than [`REFRESH_INTERVAL`] ago.
+    pub fn refresh_stale(
+        &self,
+        profile: &Profile,
+        repo: &Repository,
+        doc: &DocAt,
+        version: u64,
+    ) -> Result<(), Error> {
+        let rid = repo.id();
+        let now = Instant::now();
+        {
+            #[allow(clippy::unwrap_used)]
+            let refreshed = self.refreshed.lock().unwrap();
+            if let Some((v, at)) = refreshed.get(&rid) {
+                if *v == version && now.duration_since(*at) < REFRESH_INTERVAL {
+                    return Ok(());
+                }
+            }
+        }
+        self.refresh(profile, repo, doc)?;
+
+        #[allow(clippy::unwrap_used)]
+        self.refreshed.lock().unwrap().insert(rid, (version, now));
+
+        Ok(())
+    }
+
+    /// Bring the index of a repository up to date, re-indexing the issues, patches and project
+    /// metadata that changed since they were last indexed.
+    pub fn refresh(&self, profile: &Profile, repo: &Repository, doc: &DocAt) -> Result<(), Error> {
+        let rid = repo.id();
+        let indexed = self.versions(rid)?;
+        let mut current = cob_versions(repo, &issue::TYPENAME, Kind::Issue)?;
+        current.extend(cob_versions(repo, &patch::TYPENAME, Kind::Patch)?);
+        current.insert((Kind::Project, rid.to_string()), doc.blob.to_string());
+
+        let mut changed = Vec::new();
+        for ((kind, id), version) in &current {
+            if indexed.get(&(*kind, id.clone())) == Some(version) {
+                continue;
+            }
+            let document = match kind {
+                Kind::Issue => {
+                    let Ok(oid) = id.parse() else { continue };
+                    profile
+                        .issues(repo)?
+                        .get(&oid)?
+                        .map(|i| Document::issue(&oid, &i, version.clone()))
+                }
+                Kind::Patch => {
+                    let Ok(oid) = id.parse() else { continue };
+                    profile
+                        .patches(repo)?
+                        .get(&oid)?
+                        .map(|p| Document::patch(&oid, &p, version.clone()))
+                }
+                Kind::Project => Some(Document::project(rid, doc)?),
+            };
+            // COBs missing from the cache are indexed once they make it there.
+            changed.extend(document);
+        }
+        let removed = indexed
+            .into_keys()
+            .filter(|key| !current.contains_key(key))
+            .collect::<Vec<_>>();
+
+        if changed.is_empty() && removed.is_empty() {
+            return Ok(());
+        }
+        radicle::sql::transaction(&self.db, |db| {
+            for (kind, id) in &removed {
+                let mut stmt =
+                    db.prepare("DELETE FROM `documents` WHERE rid = ? AND kind = ? AND cob = ?")?;
+                stmt.bind((1, rid.to_string().as_str()))?;
+                stmt.bind((2, kind.as_str()))?;
+                stmt.bind((3, id.as_str()))?;
+                stmt.next()?;
+            }
+            for document in &changed {
+                insert(db, rid, document)?;
+            }
+            Ok::<_, sql::Error>(())
+        })?;
+        tracing::debug!(
+            "search index of {rid}: {} document(s) updated, {} removed",
+            changed.len(),
+            removed.len()
+        );
+
+        Ok(())
+    }
+
+    /// Versions of the documents of a repository, as indexed.
+    fn versions(&self, rid: RepoId) -> Result<BTreeMap<(Kind, String), String>, sql::Error> {
+        let mut stmt = self
+            .db
+            .prepare("SELECT kind, cob, version FROM `documents` WHERE rid = ?")?;
+        stmt.bind((1, rid.to_string().as_str()))?;
+
+        let mut versions = BTreeMap::new();
+        for row in stmt.into_iter() {
+            let row = row?;
+            let Some(kind) = Kind::parse(row.read::<&str, _>("kind")) else {
+                continue;
+            };
+            versions.insert(
+                (kind, row.read::<&str, _>("cob").to_owned()),
+                row.read::<&str, _>("version").to_owned(),
+            );
+        }
+        Ok(versions)
+    }
+
+    /// Search the documents of the given kinds in the given repositories, returning those that
+    /// match, in no particular order. All terms of the query must match. The results are loaded
+    /// with [`SearchIndex::hit`].
+    pub fn search(
+        &self,
+        query: &str,
+        kinds: &[Kind],
+        rids: &[RepoId],
+    ) -> Result<Vec<Match>, sql::Error> {
+        let terms = terms(query);
+        if terms.is_empty() || kinds.is_empty() || rids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let scope = format!(
+            "d.rid IN ({}) AND d.kind IN ({})",
+            placeholders(rids.len()),
+            placeholders(kinds.len())
+        );
+        let scope_values = rids
+            .iter()
+            .map(|rid| sql::Value::String(rid.to_string()))
+            .chain(kinds.iter().map(|k| sql::Value::String(k.to_string())))
+            .collect::<Vec<_>>();
+
+        // Collection statistics, for the scope of the search.
+        let mut stmt = self.db.prepare(format!(
+            "SELECT COUNT(*), AVG(d.length) FROM `documents` d WHERE {scope}"
+        ))?;
+        bind(&mut stmt, &scope_values, 1)?;
+        stmt.next()?;
+        let total = stmt.read::<i64, _>(0)? as f64;
+        let avg_length = stmt.read::<Option<f64>, _>(1)?.unwrap_or_default().max(1.);
+        drop(stmt);
+
+        let mut stmt = self.db.prepare(format!(
+            "SELECT p.term, p.doc, p.title_tf, p.body_tf, d.length
+             FROM `postings` p JOIN `documents` d ON d.id = p.doc
+             WHERE p.term IN ({}) AND {scope}",
+            placeholders(terms.len())
+        ))?;
+        for (i, term) in terms.iter().enumerate() {
+            stmt.bind((i + 1, term.as_str()))?;
+        }
+        bind(&mut stmt, &scope_values, terms.len() + 1)?;
+
+        let mut frequencies = HashMap::<&str, usize>::new();
+        let mut docs = HashMap::<i64, (i64, Vec<(&str, i64)>)>::new();
+        for row in stmt.into_iter() {
+            let row = row?;
+            let Some(term) = terms.iter().find(|t| *t == row.read::<&str, _>("term")) else {
+                continue;
+            };
+            let tf = TITLE_WEIGHT * row.read::<i64, _>("title_tf") + row.read::<i64, _>("body_tf");
+
+            *frequencies.entry(term).or_default() += 1;
+            docs.entry(row.read::<i64, _>("doc"))
+                .or_insert_with(|| (row.read::<i64, _>("length"), Vec::new()))
+                .1
+                .push((term, tf));
+        }
+
+        Ok(docs
+            .into_iter()
+            .filter(|(_, (_, matches))| matches.len() == terms.len())
+            .map(|(doc, (length, matches))| {
+                let score = matches
+                    .iter()
+                    .map(|(term, tf)| {
+                        let df = frequencies.get(term).copied().unwrap_or_default() as f64;
+                        let idf = (1. + (total - df + 0.5) / (df + 0.5)).ln();
+                        let tf = *tf as f64;
+                        let norm = 1. - B + B * length as f64 / avg_length;
+
+                        idf * tf * (K1 + 1.) / (tf + K1 * norm)
+                    })
+                    .sum::<f64>();
+                Match { doc, score }
+            })
+            .collect())
+    }
+
+    /// Load a search result, highlighting the terms of `query`. Returns `None` if the document
+    /// was removed from the index in the meantime.
+    pub fn hit(&self, query: &str, m: Match) -> Result<Option<Hit>, sql::Error> {
+        let Match { doc, score } = m;
+        let terms = terms(query);
+
+        let mut stmt = self.db.prepare(
+            "SELECT rid, kind, cob, title, body, state, author, timestamp
+             FROM `documents` WHERE id = ?",
+        )?;
+        stmt.bind((1, doc))?;
+
+        let Some(row) = stmt.into_iter().next() else {
+            return Ok(None);
+        };
+        let row = row?;
+        let (Ok(rid), Some(kind)) = (
+            row.read::<&str, _>("rid").parse(),
+            Kind::parse(row.read::<&str, _>("kind")),
+        ) else {
+            return Ok(None);
+        };
+        let title = row.read::<&str, _>("title");
+
+        Ok(Some(Hit {
+            kind,
+            rid,
+            id: row.read::<&str, _>("cob").to_owned(),
+            title: title.to_owned(),
+            state: row.read::<Option<&str>, _>("state").map(ToOwned::to_owned),
+            author: row
+                .read::<Option<&str>, _>("author")
+                .and_then(|a| a.parse().ok()),
+            timestamp: row.read::<Option<i64>, _>("timestamp").map(|t| t as u64),
+            score,
+            title_highlight: highlight(title, 0..title.len(), &terms),
+            snippet: snippet(row.read::<&str, _>("body"), &terms),
+        }))
+    }
+}
+
+/// Bring the index of every public repository up to date, see [`SearchIndex::refresh_stale`].
+/// Errors are logged per repository, so that one broken repository doesn't keep the others
+/// from being indexed.
+pub fn index(ctx: &Context) -> Result<(), Error> {
+    for info in ctx.profile.storage.repositories()? {
+        if !info.doc.visibility.is_public() {
+            continue;
+        }
+        let rid = info.rid;
+        let version = ctx.events.version(rid);
+        let result = ctx
+            .repo(rid)
+            .and_then(|(repo, doc)| ctx.search.refresh_stale(&ctx.profile, &repo, &doc, version));
+
+        if let Err(err) = result {
+            tracing::error!("Error indexing {rid}: {err}");
+        }
+    }
+    Ok(())
+}
+
+/// Keep the index up to date, refreshing repositories as soon as they're written to through the
+/// API, and every [`REFRESH_INTERVAL`] otherwise.
+pub async fn index_periodically(ctx: Context) {
+    loop {
+        let c = ctx.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let _span = tracing::info_span!("search.index").entered();
+            index(&c)
+        })
+        .await;
+
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => tracing::error!("Error indexing repositories: {err}"),
+            Err(err) => tracing::error!("Error indexing repositories: {err}"),
+        }
+        tokio::select! {
+            () = ctx.events.written() => {}
+            () = tokio::time::sleep(REFRESH_INTERVAL) => {}
+        }
+    }
+}
+
+/// Insert or replace a document, along with its postings.
+fn insert(db: &sql::Connection, rid: RepoId, document: &Document) -> Result<(), sql::Error> {
+    let mut counts = BTreeMap::<String, (i64, i64)>::new();
+    let mut title_length = 0;
+    let mut body_length = 0;
+    for (_, term) in tokens(&document.title) {
+        counts.entry(term).or_default().0 += 1;
+        title_length += 1;
+    }
+    for (_, term) in tokens(&document.body) {
+        counts.entry(term).or_default().1 += 1;
+        body_length += 1;
+    }
+
+    let mut stmt = db.prepare("DELETE FROM `documents` WHERE rid = ? AND kind = ? AND cob = ?")?;
+    stmt.bind((1, rid.to_string().as_str()))?;
+    stmt.bind((2, document.kind.as_str()))?;
+    stmt.bind((3, document.id.as_str()))?;
+    stmt.next()?;
+
+    let mut stmt = db.prepare(
+        "INSERT INTO `documents` (rid, kind, cob, version, title, body, state, author, timestamp, length)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+         RETURNING id",
+    )?;
+    stmt.bind((1, rid.to_string().as_str()))?;
+    stmt.bind((2, document.kind.as_str()))?;
+    stmt.bind((3, document.id.as_str()))?;
+    stmt.bind((4, document.version.as_str()))?;
+    stmt.bind((5, document.title.as_str()))?;
+    stmt.bind((6, document.body.as_str()))?;
+    stmt.bind((7, document.state))?;
+    stmt.bind((8, document.author.map(|a| a.to_string()).as_deref()))?;
+    stmt.bind((9, document.timestamp.map(|t| t as i64)))?;
+    stmt.bind((10, TITLE_WEIGHT * title_length + body_length))?;
+    stmt.next()?;
+    let doc = stmt.read::<i64, _>(0)?;
+    drop(stmt);
+
+    let mut stmt = db
+        .prepare("INSERT INTO `postings` (term, doc, title_tf, body_tf) VALUES (?1, ?2, ?3, ?4)")?;
+    for (term, (title_tf, body_tf)) in counts {
+        stmt.reset()?;
+        stmt.bind((1, term.as_str()))?;
+        stmt.bind((2, doc))?;
+        stmt.bind((3, title_tf))?;
+        stmt.bind((4, body_tf))?;
+        stmt.next()?;
+    }
+    Ok(())
+}
+
+/// Versions of the COBs of the given type in a repository. A COB's version changes whenever
+/// any remote's reference to it moves.
+fn cob_versions(
+    repo: &Repository,
+    typename: &radicle::cob::TypeName,
+    kind: Kind,
+) -> Result<BTreeMap<(Kind, String), String>, git2::Error> {
+    let mut tips = BTreeMap::<String, BTreeSet<String>>::new();
+
+    for r in repo
+        .backend
+        .references_glob(&format!("refs/namespaces/*/refs/cobs/{typename}/*"))?
+    {
+        let r = r?;
+        let (Some(name), Some(oid)) = (r.name(), r.target()) else {
+            continue;
+        };
+        let Some((namespace, id)) = name
+            .strip_prefix("refs/namespaces/")
+            .and_then(|rest| rest.split_once('/'))
+            .and_then(|(remote, rest)| Some((remote, rest.rsplit_once('/')?.1)))
+        else {
+            continue;
+        };
+        tips.entry(id.to_owned())
+            .or_default()
+            .insert(format!("{namespace} {oid}"));
+    }
+
+    tips.into_iter()
+        .map(|(id, tips)| {
+            let tips = tips.into_iter().collect::<Vec<_>>().join("\n");
+            let version = git2::Oid::hash_object(git2::ObjectType::Blob, tips.as_bytes())?;
+
+            Ok(((kind, id), version.to_string()))
+        })
+        .collect()
+}
+
+/// Split text into lowercase terms, along with their byte ranges.
+fn tokens(text: &str) -> impl Iterator<Item = (Range<usize>, String)> + '_ {
+    let mut chars = text.char_indices().peekable();
+
+    iter::from_fn(move || loop {
+        let (start, c) = chars.find(|(_, c)| c.is_alphanumeric())?;
+        let mut end = start + c.len_utf8();
+        while let Some((i, c)) = chars.next_if(|(_, c)| c.is_alphanumeric()) {
+            end = i + c.len_utf8();
+        }
+        let term = &text[start..end];
+        if term.chars().count() <= MAX_TERM_LENGTH {
+            return Some((start..end, term.to_lowercase()));
+        }
+    })
+}
+
+/// The distinct terms of a query.
+pub fn terms(query: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    for (_, term) in tokens(query) {
+        if !terms.contains(&term) {
+            terms.push(term);
+        }
+    }
+    terms
+}
+
+/// Split the given range of `text` into segments, highlighting the terms that match.
+fn highlight(text: &str, range: Range<usize>, terms: &[String]) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut push = |text: &str, matched: bool| {
+        if !text.is_empty() {
+            segments.push(Segment {
+                text: text.replace(['\n', '\r', '\t'], " "),
+                matched,
+            });
+        }
+    };
+    let mut offset = range.start;
+
+    for (token, term) in tokens(&text[range.clone()]) {
+        let token = token.start + range.start..token.end + range.start;
+        if terms.contains(&term) {
+            push(&text[offset..token.start], false);
+            push(&text[token.clone()], true);
+            offset = token.end;
+        }
+    }
+    push(&text[offset..range.end], false);
+
+    segments
+}
+
+/// An excerpt of `body` around the first term that matches, highlighted.
+fn snippet(body: &str, terms: &[String]) -> Option<Vec<Segment>> {
+    let (first, _) = tokens(body).find(|(_, term)| terms.contains(term))?;
+
+    let mut start = first.start.saturating_sub(SNIPPET_CONTEXT);
+    while !body.is_char_boundary(start) {
+        start -= 1;
+    }
+    // Don't start in the middle of a word.
+    if start > 0 {
+        if let Some((i, c)) = body[start..first.start]
+            .char_indices()
+            .find(|(_, c)| c.is_whitespace())
+        {
+            start += i + c.len_utf8();
+        }
+    }
+    let mut end = (start + SNIPPET_LENGTH).max(first.end).min(body.len());
+    while !body.is_char_boundary(end) {
+        end += 1;
+    }
+    if end < body.len() {
+        if let Some(i) = body[first.end..end].rfind(char::is_whitespace) {
+            end = first.end + i;
+        }
+    }
+
+    let mut segments = highlight(body, start..end, terms);
+    if start > 0 {
+        segments.insert(
+            0,
+            Segment {
+                text: "…".to_owned(),
+                matched: false,
+            },
+        );
+    }
+    if end < body.len() {
+        segments.push(Segment {
+            text: "…".to_owned(),
+            matched: false,
+        });
+    }
+    Some(segments)
+}
+
+fn placeholders(n: usize) -> String {
+    vec!["?"; n].join(", ")
+}
+
+fn bind(stmt: &mut sql::Statement, values: &[sql::Value], first: usize) -> Result<(), sql::Error> {
+    for (i, value) in values.iter().enumerate() {
+        stmt.bind((first + i, value))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn text(segments: &[Segment]) -> String {
+        segments
+            .iter()
+            .map(|s| {
+                if s.matched {
+                    format!("[{}]", s.text)
+                } else {
+                    s.text.clone()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_terms() {
+        assert_eq!(
+            terms("Hello, hello_World! Ünïcode 42"),
+            vec!["hello", "world", "ünïcode", "42"]
+        );
+        assert!(terms(" -- ").is_empty());
+    }
+
+    #[test]
+    fn test_highlight() {
+        let terms = terms("hello world");
+        let title = "Say `Hello` to the world";
+
+        assert_eq!(
+            text(&highlight(title, 0..title.len(), &terms)),
+            "Say `[Hello]` to the [world]"
+        );
+    }
+
+    #[test]
+    fn test_snippet() {
+        let terms = terms("needle");
+        let body = format!("{} needle {}", "hay ".repeat(40), "stack ".repeat(60));
+        let snippet = text(&snippet(&body, &terms).unwrap());
+
+        assert!(snippet.starts_with("…hay"), "{snippet}");
+        assert!(snippet.contains(" [needle] stack"), "{snippet}");
+        assert!(snippet.ends_with("stack…"), "{snippet}");
+        assert!(
+            snippet.len() <= SNIPPET_LENGTH + 2 * "…".len() + 2,
+            "{snippet}"
+        );
+        assert_eq!(super::snippet("no match here", &terms), None);
+        assert_eq!(
+            text(&super::snippet("a needle", &terms).unwrap()),
+            "a [needle]"
+        );
+
+        // Whitespace that is more than one byte long.
+        for space in ['\u{a0}', '\u{3000}'] {
+            let body = format!("{}needle", format!("hay{space}").repeat(40));
+            let snippet = text(&super::snippet(&body, &terms).unwrap());
+
+            assert!(snippet.starts_with("…hay"), "{snippet}");
+            assert!(snippet.ends_with("[needle]"), "{snippet}");
+        }
+    }
+}
@@ -0,0 +1,451 @@
+//! Chronological history of issues and patches.
+//!
+//! The operations of a collaborative object are replayed in the order they are evaluated in,
+//! and each is turned into the events it causes, by comparing the state of the object before
+//! and after it. Operations that don't apply, eg. because their author wasn't allowed to carry
+//! them out, cause no events, and neither do the operations that build on them, as they aren't
+//! part of the object either. Reactions aren't part of the timeline.
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Serialize;
+
+use radicle::cob;
+use radicle::cob::issue::{self, Issue};
+use radicle::cob::patch::{self, Patch, ReviewId, RevisionId, Verdict};
+use radicle::cob::store::{self, Cob};
+use radicle::cob::thread::CommentId;
+use radicle::cob::{ActorId, EntryId, Label, ObjectId, Timestamp};
+use radicle::git;
+use radicle::identity::Did;
+use radicle::storage::git::Repository;
+
+use crate::api::error::Error;
+
+/// An event of a timeline, caused by an operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimelineEvent {
+    /// Operation that caused the event.
+    pub entry: EntryId,
+    /// Position of the event among those caused by the operation.
+    pub index: usize,
+    /// Author of the operation.
+    pub author: ActorId,
+    /// Time of the operation.
+    pub timestamp: Timestamp,
+    /// What happened.
+    pub event: Event,
+}
+
+/// State of an issue or patch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum State {
+    Issue(issue::State),
+    Patch(patch::State),
+}
+
+/// Something that happened to an issue or patch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type")]
+pub enum Event {
+    #[serde(rename = "created")]
+    Created { title: String },
+    #[serde(rename = "title.changed")]
+    TitleChanged { from: String, to: String },
+    #[serde(rename = "label.added")]
+    LabelAdded { label: Label },
+    #[serde(rename = "label.removed")]
+    LabelRemoved { label: Label },
+    #[serde(rename = "assignee.added")]
+    AssigneeAdded { assignee: Did },
+    #[serde(rename = "assignee.removed")]
+    AssigneeRemoved { assignee: Did },
+    #[serde(rename = "state.changed")]
+    StateChanged { state: State },
+    #[serde(rename = "comment.added")]
+    #[serde(rename_all = "camelCase")]
+    CommentAdded {
+        id: CommentId,
+        /// Revision commented on, for patches.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        revision: Option<RevisionId>,
+        reply_to: Option<CommentId>,
+    },
+    #[serde(rename = "comment.edited")]
+    CommentEdited {
+        id: CommentId,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        revision: Option<RevisionId>,
+    },
+    #[serde(rename = "comment.redacted")]
+    CommentRedacted {
+        id: CommentId,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        revision: Option<RevisionId>,
+    },
+    #[serde(rename = "revision.pushed")]
+    RevisionPushed {
+        revision: RevisionId,
+        base: git::Oid,
+        oid: git::Oid,
+    },
+    #[serde(rename = "revision.edited")]
+    RevisionEdited { revision: RevisionId },
+    #[serde(rename = "revision.redacted")]
+    RevisionRedacted { revision: RevisionId },
+    #[serde(rename = "review.submitted")]
+    ReviewSubmitted {
+        revision: RevisionId,
+        review: ReviewId,
+        verdict: Option<Verdict>,
+    },
+    #[serde(rename = "review.edited")]
+    ReviewEdited {
+        revision: RevisionId,
+        review: ReviewId,
+        verdict: Option<Verdict>,
+    },
+    #[serde(rename = "review.redacted")]
+    ReviewRedacted {
+        revision: RevisionId,
+        review: ReviewId,
+    },
+    #[serde(rename = "merged")]
+    Merged {
+        revision: RevisionId,
+        commit: git::Oid,
+    },
+}
+
+/// Timeline of an issue, or `None` if the issue doesn't exist.
+pub fn issue(repo: &Repository, id: ObjectId) -> Result<Option<Vec<TimelineEvent>>, Error> {
+    Ok(replay::<Issue>(repo, id)?.map(|replay| replay.timeline))
+}
+
+/// Timeline of a patch, or `None` if the patch doesn't exist.
+pub fn patch(repo: &Repository, id: ObjectId) -> Result<Option<Vec<TimelineEvent>>, Error> {
+    Ok(replay::<Patch>(repo, id)?.map(|replay| replay.timeline))
+}
+
+/// Time of the latest operation an issue is made of, or `None` if the issue doesn't exist.
+pub fn issue_updated(repo: &Repository, id: ObjectId) -> Result<Option<Timestamp>, Error> {
+    Ok(replay::<Issue>(repo, id)?.map(|replay| replay.updated))
+}
+
+/// Objects with a timeline.
+trait Timeline: Cob + cob::Evaluate<Repository> + Clone {
+    /// Events caused by going from one state of the object to the next.
+    fn events(before: Option<&Self>, after: &Self) -> Vec<Event>;
+}
+
+impl Timeline for Issue {
+    fn events(before: Option<&Self>, after: &Self) -> Vec<Event> {
+        issue_events(before, after)
+    }
+}
+
+impl Timeline for Patch {
+    fn events(before: Option<&Self>, after: &Self) -> Vec<Event> {
+        patch_events(before, after)
+    }
+}
+
+/// An object evaluated along with its timeline.
+///
+/// Evaluating it goes through the same steps as evaluating the object itself: entries are
+/// applied with their concurrent entries, and an entry that fails to apply is pruned along
+/// with the entries that depend on it, so that the timeline only has events caused by the
+/// entries the object's state is made of.
+#[derive(Debug)]
+struct Replay<T> {
+    state: T,
+    timeline: Vec<TimelineEvent>,
+    /// Time of the latest entry, whether or not it caused events.
+    updated: Timestamp,
+}
+
+impl<T: Timeline> Replay<T> {
+    fn push(&mut self, entry: &cob::Entry, events: Vec<Event>) {
+        self.updated = self.updated.max(Timestamp::from_secs(entry.timestamp));
+        self.timeline.extend(
+            events
+                .into_iter()
+                .enumerate()
+                .map(|(index, event)| TimelineEvent {
+                    entry: *entry.id(),
+                    index,
+                    author: *entry.author(),
+                    timestamp: Timestamp::from_secs(entry.timestamp),
+                    event,
+                }),
+        )
+    }
+}
+
+impl<T: Timeline> cob::Evaluate<Repository> for Replay<T> {
+    type Error = <T as cob::Evaluate<Repository>>::Error;
+
+    fn init(entry: &cob::Entry, repo: &Repository) -> Result<Self, Self::Error> {
+        let state = T::init(entry, repo)?;
+        let mut replay = Self {
+            timeline: Vec::new(),
+            state,
+            updated: Timestamp::from_secs(entry.timestamp),
+        };
+        replay.push(entry, T::events(None, &replay.state));
+
+        Ok(replay)
+    }
+
+    fn apply<'a, I: Iterator<Item = (&'a EntryId, &'a cob::Entry)>>(
+        &mut self,
+        entry: &cob::Entry,
+        concurrent: I,
+        repo: &Repository,
+    ) -> Result<(), Self::Error> {
+        let mut next = self.state.clone();
+        next.apply(entry, concurrent, repo)?;
+        self.push(entry, T::events(Some(&self.state), &next));
+        self.state = next;
+
+        Ok(())
+    }
+}
+
+/// Replay the operations of an object, collecting the events caused by each.
+fn replay<T: Timeline>(repo: &Repository, id: ObjectId) -> Result<Option<Replay<T>>, Error> {
+    let object = cob::get::<Replay<T>, _>(repo, T::type_name(), &id).map_err(store::Error::from)?;
+
+    Ok(object.map(|object| object.object))
+}
+
+/// Events caused by going from one state of an issue to the next. The first state of an issue
+/// is compared against an issue without labels or assignees.
+fn issue_events(before: Option<&Issue>, after: &Issue) -> Vec<Event> {
+    let mut events = Vec::new();
+
+    match before {
+        None => events.push(Event::Created {
+            title: after.title().to_owned(),
+        }),
+        Some(before) if before.title() != after.title() => events.push(Event::TitleChanged {
+            from: before.title().to_owned(),
+            to: after.title().to_owned(),
+        }),
+        Some(_) => {}
+    }
+    labels(
+        before.into_iter().flat_map(|b| b.labels()),
+        after.labels(),
+        &mut events,
+    );
+    assignees(
+        before.into_iter().flat_map(|b| b.assignees()).copied(),
+        after.assignees().copied(),
+        &mut events,
+    );
+
+    // The description of an issue is its first comment, and is part of its creation.
+    let Some(before) = before else {
+        return events;
+    };
+    if before.state() != after.state() {
+        events.push(Event::StateChanged {
+            state: State::Issue(*after.state()),
+        });
+    }
+    comments(
+        before
+            .comments()
+            .map(|(id, c)| (*id, c.edits().count(), c.reply_to())),
+        after
+            .comments()
+            .map(|(id, c)| (*id, c.edits().count(), c.reply_to())),
+        None,
+        &mut events,
+    );
+
+    events
+}
+
+/// Events caused by going from one state of a patch to the next. The first state of a patch
+/// is compared against a patch without labels or assignees.
+fn patch_events(before: Option<&Patch>, after: &Patch) -> Vec<Event> {
+    let mut events = Vec::new();
+
+    match before {
+        None => events.push(Event::Created {
+            title: after.title().to_owned(),
+        }),
+        Some(before) if before.title() != after.title() => events.push(Event::TitleChanged {
+            from: before.title().to_owned(),
+            to: after.title().to_owned(),
+        }),
+        Some(_) => {}
+    }
+    labels(
+        before.into_iter().flat_map(|b| b.labels()),
+        after.labels(),
+        &mut events,
+    );
+    assignees(
+        before.into_iter().flat_map(|b| b.assignees()),
+        after.assignees(),
+        &mut events,
+    );
+
+    // The first revision of a patch is part of its creation.
+    let Some(before) = before else {
+        return events;
+    };
+    for (id, revision) in after.revisions() {
+        let Some(previous) = before.revision(&id) else {
+            events.push(Event::RevisionPushed {
+                revision: id,
+                base: *revision.base(),
+                oid: revision.head(),
+            });
+            continue;
+        };
+        if previous.edits().count() != revision.edits().count() {
+            events.push(Event::RevisionEdited { revision: id });
+        }
+
+        let reviews = before.reviews_of(id).collect::<BTreeMap<_, _>>();
+        for (review, r) in after.reviews_of(id) {
+            match reviews.get(review) {
+                None => events.push(Event::ReviewSubmitted {
+                    revision: id,
+                    review: *review,
+                    verdict: r.verdict(),
+                }),
+                Some(previous) if previous != &r => events.push(Event::ReviewEdited {
+                    revision: id,
+                    review: *review,
+                    verdict: r.verdict(),
+                }),
+                Some(_) => {}
+            }
+        }
+        let current = after
+            .reviews_of(id)
+            .map(|(review, _)| review)
+            .collect::<BTreeSet<_>>();
+        for review in reviews.keys().filter(|r| !current.contains(*r)) {
+            events.push(Event::ReviewRedacted {
+                revision: id,
+                review: **review,
+            });
+        }
+
+        comments(
+            previous
+                .discussion()
+                .comments()
+                .map(|(id, c)| (*id, c.edits().count(), c.reply_to())),
+            revision
+                .discussion()
+                .comments()
+                .map(|(id, c)| (*id, c.edits().count(), c.reply_to())),
+            Some(id),
+            &mut events,
+        );
+    }
+    for (id, _) in before.revisions() {
+        if after.revision(&id).is_none() {
+            events.push(Event::RevisionRedacted { revision: id });
+        }
+    }
+
+    let merges = before.merges().collect::<BTreeMap<_, _>>();
+    for (author, merge) in after.merges() {
+        if merges.get(author) != Some(&merge) {
+            events.push(Event::Merged {
+                revision: merge.revision,
+                commit: merge.commit,
+            });
+        }
+    }
+    if before.state() != after.state() {
+        events.push(Event::StateChanged {
+            state: State::Patch(after.state().clone()),
+        });
+    }
+
+    events
+}
+
+/// Label events, from the labels before and after an operation.
+fn labels<'a>(
+    before: impl Iterator<Item = &'a Label>,
+    after: impl Iterator<Item = &'a Label>,
+    events: &mut Vec<Event>,
+) {
+    let before = before.collect::<BTreeSet<_>>();
+    let after = after.collect::<BTreeSet<_>>();
+
+    events.extend(after.difference(&before).map(|label| Event::LabelAdded {
+        label: (*label).clone(),
+    }));
+    events.extend(before.difference(&after).map(|label| Event::LabelRemoved {
+        label: (*label).clone(),
+    }));
+}
+
+/// Assignee events, from the assignees before and after an operation.
+fn assignees(
+    before: impl Iterator<Item = Did>,
+    after: impl Iterator<Item = Did>,
+    events: &mut Vec<Event>,
+) {
+    let before = before.collect::<BTreeSet<_>>();
+    let after = after.collect::<BTreeSet<_>>();
+
+    events.extend(
+        after
+            .difference(&before)
+            .map(|assignee| Event::AssigneeAdded {
+                assignee: *assignee,
+            }),
+    );
+    events.extend(
+        before
+            .difference(&after)
+            .map(|assignee| Event::AssigneeRemoved {
+                assignee: *assignee,
+            }),
+    );
+}
+
+/// Comment events, from the comments of a thread before and after an operation, given as
+/// their id, number of edits and the comment they reply to.
+fn comments(
+    before: impl Iterator<Item = (CommentId, usize, Option<CommentId>)>,
+    after: impl Iterator<Item = (CommentId, usize, Option<CommentId>)>,
+    revision: Option<RevisionId>,
+    events: &mut Vec<Event>,
+) {
+    let mut before = before
+        .map(|(id, edits, _)| (id, edits))
+        .collect::<BTreeMap<_, _>>();
+
+    for (id, edits, reply_to) in after {
+        match before.remove(&id) {
+            None => events.push(Event::CommentAdded {
+                id,
+                revision,
+                reply_to,
+            }),
+            Some(previous) if previous != edits => {
+                events.push(Event::CommentEdited { id, revision })
+            }
+            Some(_) => {}
+        }
+    }
+    // Comments that are gone were redacted.
+    events.extend(
+        before
+            .into_keys()
+            .map(|id| Event::CommentRedacted { id, revision }),
+    );
+}
@@ -1,9 +1,13 @@
+mod boards;
 mod delegates;
+mod labels;
 mod node;
 mod profile;
 mod projects;
+mod search;
 mod sessions;
 mod stats;
+mod tokens;
 
 use axum::extract::State;
 use axum::response::{IntoResponse, Json};
@@ -23,8 +27,12 @@ pub fn router(ctx: Context) -> Router {
         .merge(node::router(ctx.clone()))
         .merge(profile::router(ctx.clone()))
         .merge(sessions::router(ctx.clone()))
+        .merge(tokens::router(ctx.clone()))
         .merge(delegates::router(ctx.clone()))
         .merge(projects::router(ctx.clone()))
+        .merge(boards::router(ctx.clone()))
+        .merge(labels::router(ctx.clone()))
+        .merge(search::router(ctx.clone()))
         .merge(stats::router(ctx));
 
     Router::new().nest("/v1", routes)
@@ -59,6 +67,11 @@ async fn root_handler(State(ctx): State<Context>) -> impl IntoResponse {
                 "rel": "profile",
                 "type": "GET"
             },
+            {
+                "href": "/search",
+                "rel": "search",
+                "type": "GET"
+            },
             {
                 "href": "/stats",
                 "rel": "stats",
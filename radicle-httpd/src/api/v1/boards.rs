@@ -0,0 +1,386 @@
+use axum::extract::State;
+use axum::response::IntoResponse;
+use axum::routing::{get, patch};
+use axum::{Json, Router};
+use axum_auth::AuthBearer;
+use hyper::StatusCode;
+use nonempty::NonEmpty;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use radicle::cob::ObjectId;
+use radicle::git::Oid;
+use radicle::identity::RepoId;
+use radicle::node::Node;
+use radicle::storage::ReadRepository;
+
+use crate::api::auth::{Capability, Session};
+use crate::api::error::Error;
+use crate::api::pagination::Pagination;
+use crate::api::{self, announce_refs, Context, SortDirection};
+use crate::axum_extra::Path;
+use crate::cob::board::{self, Board, Boards, ColumnId};
+
+pub fn router(ctx: Context) -> Router {
+    Router::new()
+        .route(
+            "/projects/:project/boards",
+            get(boards_handler).post(board_create_handler),
+        )
+        .route(
+            "/projects/:project/boards/:id",
+            get(board_handler)
+                .patch(board_update_handler)
+                .delete(board_delete_handler),
+        )
+        .route("/projects/:project/boards/:id/cards", patch(cards_handler))
+        .with_state(ctx)
+}
+
+/// List project boards, most recently created first.
+/// `GET /projects/:project/boards`
+async fn boards_handler(
+    State(ctx): State<Context>,
+    Path(project): Path<RepoId>,
+    pagination: Pagination,
+) -> impl IntoResponse {
+    let (repo, _) = ctx.repo(project)?;
+    let boards = Boards::open(&repo)?.all()?.collect::<Result<Vec<_>, _>>()?;
+    let aliases = ctx.profile.aliases();
+    let boards = pagination
+        .paginate(
+            boards,
+            |(id, board)| (board.timestamp(), *id),
+            SortDirection::Desc,
+        )?
+        .map(|(id, board)| api::json::board(id, board, &aliases));
+
+    Ok::<_, Error>(boards)
+}
+
+/// Get a project board.
+/// `GET /projects/:project/boards/:id`
+async fn board_handler(
+    State(ctx): State<Context>,
+    Path((project, id)): Path<(RepoId, Oid)>,
+) -> impl IntoResponse {
+    let (repo, _) = ctx.repo(project)?;
+    let id = ObjectId::from(id);
+    let board = Boards::open(&repo)?.get(&id)?.ok_or(Error::NotFound)?;
+    let aliases = ctx.profile.aliases();
+
+    Ok::<_, Error>(Json(api::json::board(id, board, &aliases)))
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BoardCreate {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub columns: Vec<ColumnCreate>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ColumnCreate {
+    pub id: ColumnId,
+    pub name: String,
+}
+
+/// Create a project board.
+/// `POST /projects/:project/boards`
+///
+/// Boards are used to plan issues, so writing to them takes the `issues:write` capability.
+async fn board_create_handler(
+    State(ctx): State<Context>,
+    AuthBearer(token): AuthBearer,
+    Path(project): Path<RepoId>,
+    Json(board): Json<BoardCreate>,
+) -> impl IntoResponse {
+    let session = api::auth::validate(&ctx, &token).await?;
+    session.authorize(Capability::IssuesWrite, &project)?;
+
+    let columns = board
+        .columns
+        .into_iter()
+        .map(|c| (c.id, c.name))
+        .collect::<Vec<_>>();
+    board::check(&board.name, &columns).map_err(|e| Error::BadRequest(e.to_string()))?;
+
+    let (repo, _) = ctx.repo(project)?;
+    let node = Node::new(ctx.profile.socket());
+    let signer = session.signer(&ctx.profile)?;
+    let boards = Boards::open(&repo)?;
+    let (id, _) = boards.create(board.name, board.description, columns, &signer)?;
+
+    ctx.events.notify(repo.id());
+    announce_refs(node, repo.id())?;
+
+    Ok::<_, Error>((
+        StatusCode::CREATED,
+        Json(json!({ "success": true, "id": id.to_string() })),
+    ))
+}
+
+/// Edit a project board, or its columns.
+/// `PATCH /projects/:project/boards/:id`
+///
+/// Cards are moved with `PATCH /projects/:project/boards/:id/cards`.
+async fn board_update_handler(
+    State(ctx): State<Context>,
+    AuthBearer(token): AuthBearer,
+    Path((project, id)): Path<(RepoId, Oid)>,
+    Json(action): Json<board::Action>,
+) -> impl IntoResponse {
+    if matches!(
+        action,
+        board::Action::CardMove { .. } | board::Action::CardRemove { .. }
+    ) {
+        return Err(Error::BadRequest(
+            "cards are moved through the board's `cards` endpoint".to_owned(),
+        ));
+    }
+    let session = api::auth::validate(&ctx, &token).await?;
+    session.authorize(Capability::IssuesWrite, &project)?;
+    let entry = update(&ctx, &session, project, id, "Edit board", action)?;
+
+    Ok::<_, Error>(Json(json!({ "success": true, "id": entry })))
+}
+
+/// Move a card onto a board column, to reorder it or take it off the board.
+/// `PATCH /projects/:project/boards/:id/cards`
+async fn cards_handler(
+    State(ctx): State<Context>,
+    AuthBearer(token): AuthBearer,
+    Path((project, id)): Path<(RepoId, Oid)>,
+    Json(action): Json<board::Action>,
+) -> impl IntoResponse {
+    if !matches!(
+        action,
+        board::Action::CardMove { .. } | board::Action::CardRemove { .. }
+    ) {
+        return Err(Error::BadRequest(
+            "only `card.move` and `card.remove` actions are accepted".to_owned(),
+        ));
+    }
+    let session = api::auth::validate(&ctx, &token).await?;
+    session.authorize(Capability::IssuesWrite, &project)?;
+    let entry = update(&ctx, &session, project, id, "Move card", action)?;
+
+    Ok::<_, Error>(Json(json!({ "success": true, "id": entry })))
+}
+
+/// Delete a project board.
+/// `DELETE /projects/:project/boards/:id`
+///
+/// Deletion is per namespace: only the copy of the board held by the session's signer is
+/// deleted, and the board remains visible while other peers, eg. its author, still hold a
+/// copy. Deleting a board the signer holds no copy of is a conflict.
+async fn board_delete_handler(
+    State(ctx): State<Context>,
+    AuthBearer(token): AuthBearer,
+    Path((project, id)): Path<(RepoId, Oid)>,
+) -> impl IntoResponse {
+    let session = api::auth::validate(&ctx, &token).await?;
+    session.authorize(Capability::IssuesWrite, &project)?;
+
+    let (repo, _) = ctx.repo(project)?;
+    let node = Node::new(ctx.profile.socket());
+    let signer = session.signer(&ctx.profile)?;
+    let boards = Boards::open(&repo)?;
+    let id = ObjectId::from(id);
+    boards.get(&id)?.ok_or(Error::NotFound)?;
+    if !boards.remove(&id, &signer)? {
+        return Err(Error::Conflict(
+            "board isn't stored in the signer's namespace".to_owned(),
+        ));
+    }
+
+    ctx.events.notify(repo.id());
+    announce_refs(node, repo.id())?;
+
+    Ok::<_, Error>(Json(json!({ "success": true })))
+}
+
+/// Check an action against the current state of the board, then sign and announce it.
+fn update(
+    ctx: &Context,
+    session: &Session,
+    project: RepoId,
+    id: Oid,
+    message: &str,
+    action: board::Action,
+) -> Result<radicle::cob::EntryId, Error> {
+    let (repo, _) = ctx.repo(project)?;
+    let node = Node::new(ctx.profile.socket());
+    let signer = session.signer(&ctx.profile)?;
+    let boards = Boards::open(&repo)?;
+    let id = ObjectId::from(id);
+    let mut board: Board = boards.get(&id)?.ok_or(Error::NotFound)?;
+    board
+        .apply([action.clone()])
+        .map_err(|e| Error::BadRequest(e.to_string()))?;
+    let entry = boards.update(id, message, NonEmpty::new(action), &signer)?;
+
+    ctx.events.notify(repo.id());
+    announce_refs(node, repo.id())?;
+
+    Ok(entry)
+}
+
+#[cfg(test)]
+mod routes {
+    use std::str::FromStr;
+
+    use axum::http::{Method, StatusCode};
+    use radicle::identity::RepoId;
+    use radicle::storage::ReadStorage;
+    use radicle_crypto::test::signer::MockSigner;
+    use serde_json::json;
+
+    use crate::cob::board::Boards;
+    use crate::test::*;
+
+    #[tokio::test]
+    async fn test_boards() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ctx = contributor(tmp.path());
+        let app = super::router(ctx.to_owned());
+        let boards = format!("/projects/{CONTRIBUTOR_RID}/boards");
+
+        let response = get(&app, &boards).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.json().await, json!([]));
+
+        let body = json!({
+            "name": "Roadmap",
+            "columns": [{ "id": "todo", "name": "To do" }, { "id": "done", "name": "Done" }],
+        });
+        let response = write(&app, Method::POST, &boards, body.clone()).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        create_session(ctx).await;
+
+        let response = write(&app, Method::POST, &boards, body).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let id = response.json().await["id"].as_str().unwrap().to_owned();
+        let board = format!("{boards}/{id}");
+
+        let response = write(
+            &app,
+            Method::PATCH,
+            format!("{board}/cards"),
+            json!({ "type": "card.move", "card": ISSUE_DISCUSSION_ID, "column": "todo" }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        for action in [
+            json!({ "type": "column.add", "id": "doing", "name": "Doing", "position": 1 }),
+            json!({ "type": "edit", "name": "Roadmap 2024", "description": "Next steps" }),
+        ] {
+            let response = write(&app, Method::PATCH, &board, action).await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        let response = write(
+            &app,
+            Method::PATCH,
+            format!("{board}/cards"),
+            json!({ "type": "card.move", "card": ISSUE_DISCUSSION_ID, "column": "doing" }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = get(&app, &board).await;
+        assert_eq!(
+            response.json().await,
+            json!({
+                "id": id,
+                "name": "Roadmap 2024",
+                "description": "Next steps",
+                "author": {
+                    "id": CONTRIBUTOR_DID,
+                    "alias": CONTRIBUTOR_ALIAS,
+                },
+                "timestamp": TIMESTAMP,
+                "columns": [
+                    { "id": "todo", "name": "To do", "cards": [] },
+                    { "id": "doing", "name": "Doing", "cards": [ISSUE_DISCUSSION_ID] },
+                    { "id": "done", "name": "Done", "cards": [] },
+                ],
+            })
+        );
+
+        let response = get(&app, &boards).await;
+        assert_eq!(response.headers()["x-total-count"], "1");
+        assert_eq!(response.json().await[0]["id"], id);
+
+        // Invalid actions are rejected, without being recorded.
+        for (path, action) in [
+            (
+                format!("{board}/cards"),
+                json!({ "type": "card.move", "card": ISSUE_DISCUSSION_ID, "column": "nope" }),
+            ),
+            (
+                board.clone(),
+                json!({ "type": "column.add", "id": "todo", "name": "Again" }),
+            ),
+            (
+                board.clone(),
+                json!({ "type": "card.remove", "card": ISSUE_DISCUSSION_ID }),
+            ),
+            (
+                format!("{board}/cards"),
+                json!({ "type": "column.remove", "id": "todo" }),
+            ),
+        ] {
+            let response = write(&app, Method::PATCH, path, action.clone()).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{action}");
+        }
+
+        let response = delete(&app, &board, Some(SESSION_ID.to_string())).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = get(&app, &board).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_boards_delete_other_author() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ctx = contributor(tmp.path());
+        let app = super::router(ctx.to_owned());
+        let author = MockSigner::from_seed([0xaa; 32]);
+        let rid = RepoId::from_str(CONTRIBUTOR_RID).unwrap();
+        let repo = ctx.profile.storage.repository(rid).unwrap();
+        let (id, _) = Boards::open(&repo)
+            .unwrap()
+            .create("Roadmap".to_owned(), String::new(), [], &author)
+            .unwrap();
+        let board = format!("/projects/{CONTRIBUTOR_RID}/boards/{id}");
+
+        create_session(ctx).await;
+
+        // The signer holds no copy of the board.
+        let response = delete(&app, &board, Some(SESSION_ID.to_string())).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let response = get(&app, &board).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Once it changed the board, the signer holds a copy it can delete, which leaves the
+        // author's copy in place.
+        let response = write(
+            &app,
+            Method::PATCH,
+            &board,
+            json!({ "type": "edit", "name": "Roadmap 2024", "description": "" }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = delete(&app, &board, Some(SESSION_ID.to_string())).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = get(&app, &board).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.json().await["name"], "Roadmap");
+    }
+}
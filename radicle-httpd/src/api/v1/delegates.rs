@@ -1,7 +1,7 @@
 use axum::extract::State;
 use axum::response::IntoResponse;
 use axum::routing::get;
-use axum::{Json, Router};
+use axum::Router;
 
 use radicle::cob::Author;
 use radicle::identity::Did;
@@ -13,9 +13,10 @@ use radicle::storage::{ReadRepository, ReadStorage};
 
 use crate::api::error::Error;
 use crate::api::json;
+use crate::api::pagination::Pagination;
 use crate::api::project::Info;
 use crate::api::Context;
-use crate::api::{PaginationQuery, ProjectQuery};
+use crate::api::{PaginationQuery, ProjectQuery, SortDirection};
 use crate::axum_extra::{Path, Query};
 
 pub fn router(ctx: Context) -> Router {
@@ -32,15 +33,9 @@ pub fn router(ctx: Context) -> Router {
 async fn delegates_projects_handler(
     State(ctx): State<Context>,
     Path(delegate): Path<Did>,
-    Query(qs): Query<PaginationQuery>,
+    Query(PaginationQuery { show }): Query<PaginationQuery>,
+    pagination: Pagination,
 ) -> impl IntoResponse {
-    let PaginationQuery {
-        show,
-        page,
-        per_page,
-    } = qs;
-    let page = page.unwrap_or(0);
-    let per_page = per_page.unwrap_or(10);
     let storage = &ctx.profile.storage;
     let db = &ctx.profile.database()?;
     let pinned = &ctx.profile.config.web.pinned;
@@ -52,14 +47,13 @@ async fn delegates_projects_handler(
             .collect::<Vec<_>>(),
         ProjectQuery::Pinned => storage.repositories_by_id(pinned.repositories.iter())?,
     };
-    projects.sort_by_key(|p| p.rid);
+    // Only the projects on the page are looked into, the others are only checked for the
+    // delegate.
+    projects.retain(|id| id.doc.delegates.iter().any(|d| *d == delegate));
 
-    let infos = projects
-        .into_iter()
+    let infos = pagination
+        .paginate(projects, |id| id.rid, SortDirection::Asc)?
         .filter_map(|id| {
-            if !id.doc.delegates.iter().any(|d| *d == delegate) {
-                return None;
-            }
             let Ok(repo) = storage.repository(id.rid) else {
                 return None;
             };
@@ -102,12 +96,9 @@ async fn delegates_projects_handler(
                 id: id.rid,
                 seeding,
             })
-        })
-        .skip(page * per_page)
-        .take(per_page)
-        .collect::<Vec<_>>();
+        });
 
-    Ok::<_, Error>(Json(infos))
+    Ok::<_, Error>(infos)
 }
 
 #[cfg(test)]
@@ -0,0 +1,285 @@
+use std::collections::BTreeMap;
+
+use axum::extract::State;
+use axum::response::IntoResponse;
+use axum::routing::{get, put};
+use axum::{Json, Router};
+use axum_auth::AuthBearer;
+use nonempty::NonEmpty;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use radicle::cob::{Color, Label};
+use radicle::identity::{Did, RepoId};
+use radicle::issue::cache::Issues as _;
+use radicle::node::Node;
+use radicle::patch::cache::Patches as _;
+use radicle::storage::ReadRepository;
+
+use crate::api::auth::Capability;
+use crate::api::error::Error;
+use crate::api::pagination::{Pagination, MAX_PER_PAGE};
+use crate::api::{self, announce_refs, Context, SortDirection, Usage};
+use crate::axum_extra::Path;
+use crate::cob::labels::{self, Registries};
+
+pub fn router(ctx: Context) -> Router {
+    Router::new()
+        .route("/projects/:project/labels", get(labels_handler))
+        .route(
+            "/projects/:project/labels/:label",
+            put(label_update_handler).delete(label_delete_handler),
+        )
+        .route("/projects/:project/assignees", get(assignees_handler))
+        .with_state(ctx)
+}
+
+/// List the labels of a project, by name.
+/// `GET /projects/:project/labels`
+///
+/// Labels are those in use on issues or patches, and those described in the label registry.
+async fn labels_handler(
+    State(ctx): State<Context>,
+    Path(project): Path<RepoId>,
+    pagination: Pagination,
+) -> impl IntoResponse {
+    let (repo, _) = ctx.repo(project)?;
+    let mut usage = BTreeMap::<Label, Usage>::new();
+
+    for (_, issue) in ctx.profile.issues(&repo)?.list()?.filter_map(Result::ok) {
+        for label in issue.labels() {
+            usage.entry(label.clone()).or_default().issues += 1;
+        }
+    }
+    for (_, patch) in ctx.profile.patches(&repo)?.list()?.filter_map(Result::ok) {
+        for label in patch.labels() {
+            usage.entry(label.clone()).or_default().patches += 1;
+        }
+    }
+    let registry = Registries::open(&repo)?
+        .registry()?
+        .map(|(_, registry)| registry);
+    if let Some(registry) = &registry {
+        for label in registry.labels().keys() {
+            usage.entry(label.clone()).or_default();
+        }
+    }
+    let labels = pagination
+        .default_per_page(MAX_PER_PAGE)
+        .paginate(
+            usage.into_iter().collect(),
+            |(label, _)| label.clone(),
+            SortDirection::Asc,
+        )?
+        .map(|(label, usage)| {
+            let meta = registry.as_ref().and_then(|r| r.get(&label));
+            api::json::label(&label, usage, meta)
+        });
+
+    Ok::<_, Error>(labels)
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LabelUpdate {
+    #[serde(default)]
+    pub color: Option<Color>,
+    #[serde(default)]
+    pub description: String,
+}
+
+/// Describe a project label, with a color and description.
+/// `PUT /projects/:project/labels/:label`
+///
+/// Labels are set on issues and patches, but describing them takes the `issues:write`
+/// capability only.
+async fn label_update_handler(
+    State(ctx): State<Context>,
+    AuthBearer(token): AuthBearer,
+    Path((project, label)): Path<(RepoId, String)>,
+    Json(update): Json<LabelUpdate>,
+) -> impl IntoResponse {
+    let label = Label::new(label).map_err(|e| Error::BadRequest(e.to_string()))?;
+    let session = api::auth::validate(&ctx, &token).await?;
+    session.authorize(Capability::IssuesWrite, &project)?;
+
+    let (repo, _) = ctx.repo(project)?;
+    let node = Node::new(ctx.profile.socket());
+    let signer = session.signer(&ctx.profile)?;
+    let registries = Registries::open(&repo)?;
+    let action = labels::Action::LabelEdit {
+        label,
+        color: update.color,
+        description: update.description,
+    };
+    let mut registry = registries
+        .registry()?
+        .map(|(_, registry)| registry)
+        .unwrap_or_default();
+    registry
+        .apply([action.clone()])
+        .map_err(|e| Error::BadRequest(e.to_string()))?;
+    let entry = registries.update("Edit label", NonEmpty::new(action), &signer)?;
+
+    ctx.events.notify(repo.id());
+    announce_refs(node, repo.id())?;
+
+    Ok::<_, Error>(Json(json!({ "success": true, "id": entry })))
+}
+
+/// Remove the description of a project label.
+/// `DELETE /projects/:project/labels/:label`
+async fn label_delete_handler(
+    State(ctx): State<Context>,
+    AuthBearer(token): AuthBearer,
+    Path((project, label)): Path<(RepoId, String)>,
+) -> impl IntoResponse {
+    let label = Label::new(label).map_err(|e| Error::BadRequest(e.to_string()))?;
+    let session = api::auth::validate(&ctx, &token).await?;
+    session.authorize(Capability::IssuesWrite, &project)?;
+
+    let (repo, _) = ctx.repo(project)?;
+    let node = Node::new(ctx.profile.socket());
+    let signer = session.signer(&ctx.profile)?;
+    let registries = Registries::open(&repo)?;
+    let (_, registry) = registries.registry()?.ok_or(Error::NotFound)?;
+    registry.get(&label).ok_or(Error::NotFound)?;
+    let entry = registries.update(
+        "Remove label",
+        NonEmpty::new(labels::Action::LabelRemove { label }),
+        &signer,
+    )?;
+
+    ctx.events.notify(repo.id());
+    announce_refs(node, repo.id())?;
+
+    Ok::<_, Error>(Json(json!({ "success": true, "id": entry })))
+}
+
+/// List the assignees of a project's issues and patches, by id.
+/// `GET /projects/:project/assignees`
+async fn assignees_handler(
+    State(ctx): State<Context>,
+    Path(project): Path<RepoId>,
+    pagination: Pagination,
+) -> impl IntoResponse {
+    let (repo, _) = ctx.repo(project)?;
+    let mut usage = BTreeMap::<Did, Usage>::new();
+
+    for (_, issue) in ctx.profile.issues(&repo)?.list()?.filter_map(Result::ok) {
+        for assignee in issue.assignees() {
+            usage.entry(*assignee).or_default().issues += 1;
+        }
+    }
+    for (_, patch) in ctx.profile.patches(&repo)?.list()?.filter_map(Result::ok) {
+        for assignee in patch.assignees() {
+            usage.entry(assignee).or_default().patches += 1;
+        }
+    }
+    let aliases = ctx.profile.aliases();
+    let assignees = pagination
+        .default_per_page(MAX_PER_PAGE)
+        .paginate(
+            usage.into_iter().collect(),
+            |(did, _)| *did,
+            SortDirection::Asc,
+        )?
+        .map(|(did, usage)| api::json::assignee(&did, usage, &aliases));
+
+    Ok::<_, Error>(assignees)
+}
+
+#[cfg(test)]
+mod routes {
+    use axum::http::{Method, StatusCode};
+    use serde_json::json;
+
+    use crate::test::*;
+
+    #[tokio::test]
+    async fn test_labels_and_assignees() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ctx = contributor(tmp.path());
+        let app =
+            super::router(ctx.to_owned()).merge(super::super::projects::router(ctx.to_owned()));
+        let labels = format!("/projects/{CONTRIBUTOR_RID}/labels");
+
+        let response = get(&app, &labels).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.json().await, json!([]));
+
+        let body = json!({ "color": "#FF0000", "description": "Something isn't working" });
+        let response = write(&app, Method::PUT, format!("{labels}/bug"), body.clone()).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        create_session(ctx).await;
+
+        for action in [
+            json!({ "type": "label", "labels": ["bug"] }),
+            json!({ "type": "assign", "assignees": [CONTRIBUTOR_DID] }),
+        ] {
+            let response = write(
+                &app,
+                Method::PATCH,
+                format!("/projects/{CONTRIBUTOR_RID}/issues/{ISSUE_DISCUSSION_ID}"),
+                action,
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        for (label, body) in [("bug", body), ("docs", json!({}))] {
+            let response = write(&app, Method::PUT, format!("{labels}/{label}"), body).await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        let response = get(&app, &labels).await;
+        assert_eq!(response.headers()["x-total-count"], "2");
+        assert_eq!(
+            response.json().await,
+            json!([
+                {
+                    "name": "bug",
+                    "issues": 1,
+                    "patches": 0,
+                    "color": "#ff0000",
+                    "description": "Something isn't working",
+                },
+                {
+                    "name": "docs",
+                    "issues": 0,
+                    "patches": 0,
+                    "color": null,
+                    "description": "",
+                },
+            ])
+        );
+
+        let response = write(
+            &app,
+            Method::PUT,
+            format!("{labels}/docs"),
+            json!({ "description": "Two\nlines" }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = delete(&app, format!("{labels}/docs"), Some(SESSION_ID.to_string())).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = delete(&app, format!("{labels}/docs"), Some(SESSION_ID.to_string())).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = get(&app, &labels).await;
+        assert_eq!(response.json().await.as_array().unwrap().len(), 1);
+
+        let response = get(&app, format!("/projects/{CONTRIBUTOR_RID}/assignees")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.json().await,
+            json!([{
+                "id": CONTRIBUTOR_DID,
+                "alias": CONTRIBUTOR_ALIAS,
+                "issues": 1,
+                "patches": 0,
+            }])
+        );
+    }
+}
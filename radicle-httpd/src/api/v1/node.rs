@@ -14,6 +14,7 @@ use radicle::node::{
 };
 use radicle::Node;
 
+use crate::api::auth::Capability;
 use crate::api::error::Error;
 use crate::api::{self, Context, PoliciesQuery, RADICLE_VERSION};
 use crate::axum_extra::{Path, Query};
@@ -106,7 +107,9 @@ async fn node_policies_seed_handler(
     Path(project): Path<RepoId>,
     Query(qs): Query<PoliciesQuery>,
 ) -> impl IntoResponse {
-    api::auth::validate(&ctx, &token).await?;
+    let session = api::auth::validate(&ctx, &token).await?;
+    session.authorize(Capability::PoliciesWrite, &project)?;
+    session.require_operator(&ctx.profile)?;
     let mut node = Node::new(ctx.profile.socket());
     node.seed(project, qs.scope.unwrap_or_default())?;
 
@@ -127,7 +130,9 @@ async fn node_policies_unseed_handler(
     AuthBearer(token): AuthBearer,
     Path(project): Path<RepoId>,
 ) -> impl IntoResponse {
-    api::auth::validate(&ctx, &token).await?;
+    let session = api::auth::validate(&ctx, &token).await?;
+    session.authorize(Capability::PoliciesWrite, &project)?;
+    session.require_operator(&ctx.profile)?;
     let mut node = Node::new(ctx.profile.socket());
     node.unseed(project)?;
 
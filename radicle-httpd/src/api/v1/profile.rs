@@ -21,6 +21,7 @@ async fn profile_handler(
     State(ctx): State<Context>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
 ) -> impl IntoResponse {
+    // Peers connected over a unix socket show up as `listen::UNIX_PEER`, a loopback address.
     if !addr.ip().is_loopback() {
         return Err(Error::Auth("Profile data is only shown for localhost"));
     }
@@ -120,4 +121,14 @@ mod routes {
             })
         );
     }
+
+    #[tokio::test]
+    async fn test_unix_socket_profile() {
+        let tmp = tempfile::tempdir().unwrap();
+        let seed = test::seed(tmp.path());
+        let app = super::router(seed).layer(MockConnectInfo(crate::listen::UNIX_PEER));
+        let response = get(&app, "/profile").await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
 }
@@ -1,7 +1,8 @@
 use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 use axum::extract::{DefaultBodyLimit, State};
-use axum::http::header;
+use axum::http::{header, HeaderMap};
+use axum::response::sse::{KeepAlive, Sse};
 use axum::response::IntoResponse;
 use axum::routing::{get, patch, post};
 use axum::{Json, Router};
@@ -13,18 +14,27 @@ use serde::{Deserialize, Serialize};
 use serde_json::json;
 
 use radicle::cob::{
-    issue, issue::cache::Issues as _, patch, patch::cache::Patches as _, resolve_embed, Author,
-    Embed, Label, Uri,
+    cache, issue, issue::cache::Issues as _, patch, patch::cache::Patches as _, resolve_embed,
+    Author, Embed, EntryId, Label, ObjectId, Uri,
 };
+use radicle::crypto::{PublicKey, Signer};
+use radicle::git;
 use radicle::identity::{Did, RepoId};
 use radicle::node::routing::Store;
 use radicle::node::{AliasStore, Node, NodeId};
-use radicle::storage::{ReadRepository, ReadStorage, RemoteRepository, WriteRepository};
+use radicle::storage::{self, ReadRepository, ReadStorage, RemoteRepository, WriteRepository};
 
+use crate::api::auth::Capability;
+use crate::api::code_search::Search;
 use crate::api::error::Error;
+use crate::api::pagination::{Pagination, MAX_PER_PAGE};
 use crate::api::project::Info;
-use crate::api::search::{SearchQueryString, SearchResult};
-use crate::api::{self, announce_refs, CobsQuery, Context, PaginationQuery, ProjectQuery};
+use crate::api::search::{SearchQueryString, SearchResult, VisibilityQuery};
+use crate::api::search_index::Kind;
+use crate::api::{
+    self, announce_refs, CobsQuery, CobsSort, CodeSearchQuery, Context, PaginationQuery,
+    PatchesQuery, ProjectQuery, SearchQuery, SortDirection,
+};
 use crate::axum_extra::{cached_response, immutable_response, Path, Query};
 
 const MAX_BODY_LIMIT: usize = 4_194_304;
@@ -38,6 +48,9 @@ pub fn router(ctx: Context) -> Router {
         .route("/projects/:project/commits/:sha", get(commit_handler))
         .route("/projects/:project/diff/:base/:oid", get(diff_handler))
         .route("/projects/:project/activity", get(activity_handler))
+        .route("/projects/:project/events", get(events_handler))
+        .route("/projects/:project/search", get(search_handler))
+        .route("/projects/:project/code-search", get(code_search_handler))
         .route("/projects/:project/tree/:sha/", get(tree_handler_root))
         .route("/projects/:project/tree/:sha/*path", get(tree_handler))
         .route(
@@ -52,10 +65,18 @@ pub fn router(ctx: Context) -> Router {
             "/projects/:project/issues",
             post(issue_create_handler).get(issues_handler),
         )
+        .route(
+            "/projects/:project/issues/batch",
+            post(issues_batch_handler),
+        )
         .route(
             "/projects/:project/issues/:id",
             patch(issue_update_handler).get(issue_handler),
         )
+        .route(
+            "/projects/:project/issues/:id/timeline",
+            get(issue_timeline_handler),
+        )
         .route(
             "/projects/:project/patches",
             post(patch_create_handler).get(patches_handler),
@@ -64,6 +85,10 @@ pub fn router(ctx: Context) -> Router {
             "/projects/:project/patches/:id",
             patch(patch_update_handler).get(patch_handler),
         )
+        .route(
+            "/projects/:project/patches/:id/timeline",
+            get(patch_timeline_handler),
+        )
         .with_state(ctx)
         .layer(DefaultBodyLimit::max(MAX_BODY_LIMIT))
 }
@@ -72,15 +97,9 @@ pub fn router(ctx: Context) -> Router {
 /// `GET /projects`
 async fn project_root_handler(
     State(ctx): State<Context>,
-    Query(qs): Query<PaginationQuery>,
+    Query(PaginationQuery { show }): Query<PaginationQuery>,
+    pagination: Pagination,
 ) -> impl IntoResponse {
-    let PaginationQuery {
-        show,
-        page,
-        per_page,
-    } = qs;
-    let page = page.unwrap_or(0);
-    let per_page = per_page.unwrap_or(10);
     let storage = &ctx.profile.storage;
     let db = &ctx.profile.database()?;
     let pinned = &ctx.profile.config.web.pinned;
@@ -98,14 +117,12 @@ async fn project_root_handler(
             .filter(|repo| repo.doc.visibility.is_public())
             .collect::<Vec<_>>(),
     };
-    projects.sort_by_key(|p| p.rid);
+    // Only the projects on the page are looked into, the others are only checked for a policy.
+    projects.retain(|info| policies.is_seeding(&info.rid).unwrap_or_default());
 
-    let infos = projects
-        .into_iter()
+    let infos = pagination
+        .paginate(projects, |info| info.rid, SortDirection::Asc)?
         .filter_map(|info| {
-            if !policies.is_seeding(&info.rid).unwrap_or_default() {
-                return None;
-            }
             let Ok(repo) = storage.repository(info.rid) else {
                 return None;
             };
@@ -147,48 +164,114 @@ async fn project_root_handler(
                 id: info.rid,
                 seeding,
             })
-        })
-        .skip(page * per_page)
-        .take(per_page)
-        .collect::<Vec<_>>();
+        });
 
-    Ok::<_, Error>(Json(infos))
+    Ok::<_, Error>(infos)
 }
 
-/// Search repositories by name.
+/// Search repositories by name, description and delegate aliases.
 /// `GET /projects/search?q=<query>`
 ///
-/// We obtain the byte index of the first character of the query that matches the repo name.
-/// And skip if the query doesn't match the repo name.
+/// Matching is fuzzy and case-insensitive, see `search::fuzzy`. Each result carries a
+/// `score` between `0` and `1`, and results are ordered by score, then by seeding count.
+/// Repositories scoring below `0.2` aren't returned.
 ///
-/// Sorting algorithm:
-/// If both byte indices are 0, compare by seeding count.
-/// A repo name with a byte index of 0 should come before non-zero indices.
-/// If both indices are non-zero and equal, then compare by seeding count.
-/// If none of the above, all non-zero indices are compared by their seeding count primarily.
+/// Results can be filtered with `delegate=<did>`, `visibility=public`,
+/// `hasOpenPatches=<bool>` and `updatedSince=<timestamp>`. Private repositories are never
+/// returned, so `visibility=private` is rejected with `400 Bad Request`.
 async fn project_search_handler(
     State(ctx): State<Context>,
-    Query(SearchQueryString { q, per_page, page }): Query<SearchQueryString>,
+    Query(qs): Query<SearchQueryString>,
+    pagination: Pagination,
 ) -> impl IntoResponse {
+    let SearchQueryString {
+        q,
+        delegate,
+        visibility,
+        has_open_patches,
+        updated_since,
+    } = qs;
+    if visibility == Some(VisibilityQuery::Private) {
+        return Err(Error::BadRequest(
+            "private repositories can't be searched".to_owned(),
+        ));
+    }
     let q = q.unwrap_or_default();
-    let page = page.unwrap_or(0);
-    let per_page = per_page.unwrap_or(10);
     let storage = &ctx.profile.storage;
     let aliases = &ctx.profile.aliases();
     let db = &ctx.profile.database()?;
     let found_repos = storage
         .repositories()?
         .into_iter()
+        .filter(|info| delegate.map_or(true, |did| info.doc.delegates.contains(&did)))
+        .filter(|info| {
+            if has_open_patches.is_none() && updated_since.is_none() {
+                return true;
+            }
+            let Ok(repo) = storage.repository(info.rid) else {
+                return false;
+            };
+            if let Some(has_open_patches) = has_open_patches {
+                let Some(counts) = ctx
+                    .profile
+                    .patches(&repo)
+                    .ok()
+                    .and_then(|p| p.counts().ok())
+                else {
+                    return false;
+                };
+                if (counts.open > 0) != has_open_patches {
+                    return false;
+                }
+            }
+            if let Some(since) = updated_since {
+                let Ok((_, head)) = repo.head() else {
+                    return false;
+                };
+                let Ok(commit) = repo.backend.find_commit(*head) else {
+                    return false;
+                };
+                if commit.time().seconds() < since {
+                    return false;
+                }
+            }
+            true
+        })
         .filter_map(|info| SearchResult::new(&q, info, db, aliases))
-        .collect::<BTreeSet<SearchResult>>();
+        .collect::<Vec<SearchResult>>();
+    let (links, found_repos) = pagination
+        .paginate(found_repos, SearchResult::key, SortDirection::Asc)?
+        .into_parts();
 
-    let found_repos = found_repos
-        .into_iter()
-        .skip(page * per_page)
-        .take(per_page)
-        .collect::<Vec<_>>();
+    Ok::<_, Error>((links, cached_response(found_repos, 600)).into_response())
+}
 
-    Ok::<_, Error>(cached_response(found_repos, 600).into_response())
+/// Search the issues and patches of a project by title, description and comments.
+/// `GET /projects/:project/search?q=<query>&type=<issue|patch>`
+async fn search_handler(
+    State(ctx): State<Context>,
+    Path(project): Path<RepoId>,
+    Query(qs): Query<SearchQuery>,
+    pagination: Pagination,
+) -> impl IntoResponse {
+    let SearchQuery { q, kind } = qs;
+    let kinds = match kind {
+        Some(Kind::Project) => {
+            return Err(Error::BadRequest(
+                "only issues and patches can be searched within a project".to_owned(),
+            ))
+        }
+        Some(kind) => vec![kind],
+        None => Kind::COBS.to_vec(),
+    };
+    ctx.repo(project)?;
+    let aliases = ctx.profile.aliases();
+    let hits = ctx
+        .search(q, kinds, vec![project], pagination)
+        .await?
+        .map(|hit| api::json::search_hit(&hit, &aliases));
+
+    Ok::<_, Error>(hits)
 }
 
 /// Get project metadata.
@@ -206,24 +289,24 @@ pub struct CommitsQueryString {
     pub parent: Option<String>,
     pub since: Option<i64>,
     pub until: Option<i64>,
-    pub page: Option<usize>,
-    pub per_page: Option<usize>,
 }
 
 /// Get project commit range.
 /// `GET /projects/:project/commits?parent=<sha>`
+///
+/// History is only walked up to the end of the requested page, so the number of commits
+/// isn't returned in the `X-Total-Count` header.
 async fn history_handler(
     State(ctx): State<Context>,
     Path(rid): Path<RepoId>,
     Query(qs): Query<CommitsQueryString>,
+    pagination: Pagination,
 ) -> impl IntoResponse {
     let (repo, doc) = ctx.repo(rid)?;
     let CommitsQueryString {
         since,
         until,
         parent,
-        page,
-        per_page,
     } = qs;
 
     // If the parent commit is provided, the response depends only on the query
@@ -237,20 +320,18 @@ async fn history_handler(
     };
     let repo = Repository::open(repo.path())?;
 
-    // If a pagination is defined, we do not want to paginate the commits, and we return all of them on the first page.
-    let page = page.unwrap_or(0);
-    let per_page = if per_page.is_none() && (since.is_some() || until.is_some()) {
-        usize::MAX
+    // If a time range is given without a page size, we return as many commits as we can on the
+    // first page.
+    let pagination = if !pagination.has_per_page() && (since.is_some() || until.is_some()) {
+        pagination.default_per_page(MAX_PER_PAGE)
     } else {
-        per_page.unwrap_or(30)
+        pagination.default_per_page(30)
     };
 
-    let commits = repo
-        .history(&sha)?
-        .filter_map(|commit| {
+    let commits = tracing::info_span!("surf.history", %sha).in_scope(|| {
+        let history = repo.history(&sha)?.filter_map(|commit| {
             let commit = commit.ok()?;
             let time = commit.committer.time.seconds();
-            let commit = api::json::commit(&commit);
             match (since, until) {
                 (Some(since), Some(until)) if time >= since && time < until => Some(commit),
                 (Some(since), None) if time >= since => Some(commit),
@@ -258,15 +339,19 @@ async fn history_handler(
                 (None, None) => Some(commit),
                 _ => None,
             }
-        })
-        .skip(page * per_page)
-        .take(per_page)
-        .collect::<Vec<_>>();
+        });
+        let commits = pagination
+            .walk(history, |commit| commit.id)?
+            .map(|commit| api::json::commit(&commit));
+
+        Ok::<_, Error>(commits)
+    })?;
 
     if is_immutable {
-        Ok::<_, Error>(immutable_response(commits).into_response())
+        let (links, commits) = commits.into_parts();
+        Ok::<_, Error>((links, immutable_response(commits)).into_response())
     } else {
-        Ok::<_, Error>(Json(commits).into_response())
+        Ok::<_, Error>(commits.into_response())
     }
 }
 
@@ -386,17 +471,18 @@ async fn diff_handler(
         }
     });
 
-    let commits = repo
-        .history(commit.id)?
-        .take_while(|c| {
-            if let Ok(c) = c {
-                c.id != base.id
-            } else {
-                false
-            }
-        })
-        .map(|r| r.map(|c| api::json::commit(&c)))
-        .collect::<Result<Vec<_>, _>>()?;
+    let commits = tracing::info_span!("surf.history", sha = %commit.id).in_scope(|| {
+        repo.history(commit.id)?
+            .take_while(|c| {
+                if let Ok(c) = c {
+                    c.id != base.id
+                } else {
+                    false
+                }
+            })
+            .map(|r| r.map(|c| api::json::commit(&c)))
+            .collect::<Result<Vec<_>, _>>()
+    })?;
 
     let response = json!({ "diff": diff, "files": files, "commits": commits });
 
@@ -416,22 +502,49 @@ async fn activity_handler(
     let one_year_ago = chrono::Duration::try_weeks(52).unwrap();
     let repo = Repository::open(repo.path())?;
     let head = repo.head()?;
-    let timestamps = repo
-        .history(head)?
-        .filter_map(|a| {
-            if let Ok(a) = a {
-                let seconds = a.committer.time.seconds();
-                if seconds > current_date - one_year_ago.num_seconds() {
-                    return Some(seconds);
-                }
-            }
-            None
+    let timestamps = tracing::info_span!("surf.history", sha = %head).in_scope(|| {
+        repo.history(head).map(|history| {
+            history
+                .filter_map(|a| {
+                    if let Ok(a) = a {
+                        let seconds = a.committer.time.seconds();
+                        if seconds > current_date - one_year_ago.num_seconds() {
+                            return Some(seconds);
+                        }
+                    }
+                    None
+                })
+                .collect::<Vec<i64>>()
         })
-        .collect::<Vec<i64>>();
+    })?;
 
     Ok::<_, Error>(cached_response(json!({ "activity": timestamps }), 3600))
 }
 
+/// Stream project changes as server-sent events.
+/// `GET /projects/:project/events`
+///
+/// Clients reconnecting with a `Last-Event-ID` header get the events they missed replayed, or a
+/// `resync` event if they can no longer be replayed.
+async fn events_handler(
+    State(ctx): State<Context>,
+    Path(project): Path<RepoId>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    ctx.repo(project)?;
+
+    let last = headers
+        .get("Last-Event-ID")
+        .and_then(|id| id.to_str().ok())
+        .and_then(|id| id.parse().ok());
+    let subscription = ctx
+        .events
+        .subscribe(ctx.profile.clone(), project, last)
+        .await;
+
+    Ok::<_, Error>(Sse::new(subscription.into_stream()).keep_alive(KeepAlive::default()))
+}
+
 /// Get project source tree for '/' path.
 /// `GET /projects/:project/tree/:sha/`
 async fn tree_handler_root(
@@ -451,7 +564,10 @@ async fn tree_handler(
 
     if let Some(ref cache) = ctx.cache {
         let cache = &mut cache.tree.lock().await;
-        if let Some(response) = cache.get(&(project, sha, path.clone())) {
+        let cached = cache.get(&(project, sha, path.clone()));
+
+        ctx.metrics().tree_cache(cached.is_some());
+        if let Some(response) = cached {
             return Ok::<_, Error>(immutable_response(response.clone()));
         }
     }
@@ -468,6 +584,59 @@ async fn tree_handler(
     Ok::<_, Error>(immutable_response(response))
 }
 
+/// Search the files of a project at a commit.
+/// `GET /projects/:project/code-search?q=<query>&sha=<sha>&path=<glob>`
+async fn code_search_handler(
+    State(ctx): State<Context>,
+    Path(project): Path<RepoId>,
+    Query(qs): Query<CodeSearchQuery>,
+) -> impl IntoResponse {
+    let CodeSearchQuery {
+        q,
+        sha,
+        path,
+        regex,
+        case,
+        context,
+        limit,
+    } = qs;
+    let search = Search::new(&q, regex, case, path.as_deref()).map_err(Error::BadRequest)?;
+    let search = match context {
+        Some(context) => search.context(context),
+        None => search,
+    };
+    let search = match limit {
+        Some(limit) => search.limit(limit),
+        None => search,
+    };
+    let (repo, _) = ctx.repo(project)?;
+    let sha = match sha {
+        Some(sha) => sha,
+        None => repo.head()?.1,
+    };
+    let results = tokio::task::spawn_blocking(move || {
+        // An unknown commit is a missing resource, unlike errors while searching a known one.
+        if let Err(e) = repo.backend.find_commit(*sha) {
+            return Err(if radicle::git::is_not_found_err(&e) {
+                Error::NotFound
+            } else {
+                Error::from(e)
+            });
+        }
+        tracing::info_span!("code.search", %sha)
+            .in_scope(|| search.run(&repo.backend, *sha))
+            .map_err(Error::from)
+    })
+    .await??;
+
+    Ok::<_, Error>(Json(json!({
+        "sha": sha,
+        "matches": results.matches,
+        "stats": { "files": results.files, "bytes": results.bytes },
+        "truncated": results.truncated,
+    })))
+}
+
 /// Get project source tree stats.
 /// `GET /projects/:project/stats/tree/:sha`
 async fn stats_tree_handler(
@@ -617,40 +786,68 @@ async fn readme_handler(
 }
 
 /// Get project issues list.
-/// `GET /projects/:project/issues`
+/// `GET /projects/:project/issues?label=<label>&sort=<created|updated|comments>&direction=<asc|desc>`
+///
+/// The number of matching issues, across all pages, is returned in the `X-Total-Count` header.
 async fn issues_handler(
     State(ctx): State<Context>,
     Path(project): Path<RepoId>,
     Query(qs): Query<CobsQuery<api::IssueState>>,
+    pagination: Pagination,
 ) -> impl IntoResponse {
     let (repo, _) = ctx.repo(project)?;
-    let CobsQuery {
-        page,
-        per_page,
-        state,
-    } = qs;
-    let page = page.unwrap_or(0);
-    let per_page = per_page.unwrap_or(10);
-    let state = state.unwrap_or_default();
-    let issues = ctx.profile.issues(&repo)?;
-    let mut issues: Vec<_> = issues
-        .list()?
-        .filter_map(|r| {
-            let (id, issue) = r.ok()?;
-            (state.matches(issue.state())).then_some((id, issue))
-        })
-        .collect::<Vec<_>>();
+    let state = qs.state.clone().unwrap_or_default();
+    let issues = tracing::info_span!("cob.cache", kind = "issues").in_scope(|| {
+        let issues = ctx.profile.issues(&repo)?;
+        let issues = issues
+            .list()?
+            .filter_map(|r| {
+                let (id, issue) = r.ok()?;
+                let matches = state.matches(issue.state())
+                    && qs.matches(
+                        issue.title(),
+                        issue.author().id(),
+                        issue.timestamp(),
+                        issue.labels(),
+                        issue.assignees().copied(),
+                    );
+                matches.then_some((id, issue))
+            })
+            .collect::<Vec<_>>();
+
+        Ok::<_, Error>(issues)
+    })?;
+    // Issues don't record when they last changed, so their operations are replayed to find out.
+    let updated = if let CobsSort::Updated = qs.sort {
+        tracing::info_span!("cob.replay", kind = "issues").in_scope(|| {
+            issues
+                .iter()
+                .map(|(id, issue)| {
+                    let updated = api::timeline::issue_updated(&repo, *id)?
+                        .unwrap_or_else(|| issue.timestamp());
+                    Ok((*id, updated.as_millis()))
+                })
+                .collect::<Result<HashMap<_, _>, Error>>()
+        })?
+    } else {
+        HashMap::new()
+    };
 
-    issues.sort_by(|(_, a), (_, b)| b.timestamp().cmp(&a.timestamp()));
+    // Ties are broken by id, so that pages are stable.
+    let key = |(id, issue): &(_, issue::Issue)| {
+        let sort = match qs.sort {
+            CobsSort::Created => issue.timestamp().as_millis(),
+            CobsSort::Updated => updated.get(id).copied().unwrap_or_default(),
+            CobsSort::Comments => issue.replies().count() as u64,
+        };
+        (sort, *id)
+    };
     let aliases = &ctx.profile.aliases();
-    let issues = issues
-        .into_iter()
-        .map(|(id, issue)| api::json::issue(id, issue, aliases))
-        .skip(page * per_page)
-        .take(per_page)
-        .collect::<Vec<_>>();
+    let issues = pagination
+        .paginate(issues, key, qs.direction)?
+        .map(|(id, issue)| api::json::issue(id, issue, aliases));
 
-    Ok::<_, Error>(Json(issues))
+    Ok::<_, Error>(issues)
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -670,14 +867,12 @@ async fn issue_create_handler(
     Path(project): Path<RepoId>,
     Json(issue): Json<IssueCreate>,
 ) -> impl IntoResponse {
-    api::auth::validate(&ctx, &token).await?;
+    let session = api::auth::validate(&ctx, &token).await?;
+    session.authorize(Capability::IssuesWrite, &project)?;
 
     let (repo, _) = ctx.repo(project)?;
     let node = Node::new(ctx.profile.socket());
-    let signer = ctx
-        .profile
-        .signer()
-        .map_err(|_| Error::Auth("Unauthorized"))?;
+    let signer = session.signer(&ctx.profile)?;
     let embeds: Vec<Embed> = issue
         .embeds
         .into_iter()
@@ -696,6 +891,7 @@ async fn issue_create_handler(
         )
         .map_err(Error::from)?;
 
+    ctx.events.notify(repo.id());
     announce_refs(node, repo.id())?;
 
     Ok::<_, Error>((
@@ -712,19 +908,34 @@ async fn issue_update_handler(
     Path((project, issue_id)): Path<(RepoId, Oid)>,
     Json(action): Json<issue::Action>,
 ) -> impl IntoResponse {
-    api::auth::validate(&ctx, &token).await?;
+    let session = api::auth::validate(&ctx, &token).await?;
+    session.authorize(Capability::IssuesWrite, &project)?;
 
     let (repo, _) = ctx.repo(project)?;
     let node = Node::new(ctx.profile.socket());
-    let signer = ctx.profile.signer()?;
+    let signer = session.signer(&ctx.profile)?;
     let mut issues = ctx.profile.issues_mut(&repo)?;
     let mut issue = issues.get_mut(&issue_id.into())?;
+    let id = issue_action(&mut issue, action, &repo, &signer)?;
+
+    ctx.events.notify(repo.id());
+    announce_refs(node, repo.id())?;
+
+    Ok::<_, Error>(Json(json!({ "success": true, "id": id })))
+}
 
+/// Sign an issue action as a new entry of the issue.
+fn issue_action<G: Signer>(
+    issue: &mut issue::IssueMut<'_, '_, storage::git::Repository, cache::StoreWriter>,
+    action: issue::Action,
+    repo: &storage::git::Repository,
+    signer: &G,
+) -> Result<EntryId, Error> {
     let id = match action {
-        issue::Action::Assign { assignees } => issue.assign(assignees, &signer)?,
-        issue::Action::Lifecycle { state } => issue.lifecycle(state, &signer)?,
-        issue::Action::Label { labels } => issue.label(labels, &signer)?,
-        issue::Action::Edit { title } => issue.edit(title, &signer)?,
+        issue::Action::Assign { assignees } => issue.assign(assignees, signer)?,
+        issue::Action::Lifecycle { state } => issue.lifecycle(state, signer)?,
+        issue::Action::Label { labels } => issue.label(labels, signer)?,
+        issue::Action::Edit { title } => issue.edit(title, signer)?,
         issue::Action::Comment {
             body,
             reply_to,
@@ -732,10 +943,10 @@ async fn issue_update_handler(
         } => {
             let embeds: Vec<Embed> = embeds
                 .into_iter()
-                .filter_map(|embed| resolve_embed(&repo, embed))
+                .filter_map(|embed| resolve_embed(repo, embed))
                 .collect();
             if let Some(to) = reply_to {
-                issue.comment(body, to, embeds, &signer)?
+                issue.comment(body, to, embeds, signer)?
             } else {
                 return Err(Error::BadRequest("`replyTo` missing".to_owned()));
             }
@@ -744,20 +955,177 @@ async fn issue_update_handler(
             id,
             reaction,
             active,
-        } => issue.react(id, reaction, active, &signer)?,
+        } => issue.react(id, reaction, active, signer)?,
         issue::Action::CommentEdit { id, body, embeds } => {
             let embeds: Vec<Embed> = embeds
                 .into_iter()
-                .filter_map(|embed| resolve_embed(&repo, embed))
+                .filter_map(|embed| resolve_embed(repo, embed))
                 .collect();
-            issue.edit_comment(id, body, embeds, &signer)?
+            issue.edit_comment(id, body, embeds, signer)?
         }
-        issue::Action::CommentRedact { id } => issue.redact_comment(id, &signer)?,
+        issue::Action::CommentRedact { id } => issue.redact_comment(id, signer)?,
     };
 
-    announce_refs(node, repo.id())?;
+    Ok(id)
+}
 
-    Ok::<_, Error>(Json(json!({ "success": true, "id": id })))
+/// Largest number of updates accepted in a batch.
+const MAX_BATCH_SIZE: usize = 100;
+
+#[derive(Deserialize, Serialize)]
+pub struct IssuesBatch {
+    pub updates: Vec<IssueBatchUpdate>,
+    /// Apply all of the updates, or none of them.
+    #[serde(default)]
+    pub atomic: bool,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct IssueBatchUpdate {
+    pub id: Oid,
+    pub action: issue::Action,
+}
+
+/// Update several issues, announcing the changes once.
+/// `POST /projects/:project/issues/batch`
+///
+/// Updates are applied in order, and the result of each is returned. In `atomic` mode, the
+/// batch stops at the first update that fails, and the issues it touched are put back the way
+/// they were: the failed update carries the error, all of the others are marked as `skipped`,
+/// and the response is a `400`, as nothing was written.
+async fn issues_batch_handler(
+    State(ctx): State<Context>,
+    AuthBearer(token): AuthBearer,
+    Path(project): Path<RepoId>,
+    Json(batch): Json<IssuesBatch>,
+) -> impl IntoResponse {
+    let session = api::auth::validate(&ctx, &token).await?;
+    session.authorize(Capability::IssuesWrite, &project)?;
+
+    if batch.updates.is_empty() {
+        return Err(Error::BadRequest("no updates given".to_owned()));
+    }
+    if batch.updates.len() > MAX_BATCH_SIZE {
+        return Err(Error::BadRequest(format!(
+            "at most {MAX_BATCH_SIZE} updates can be given"
+        )));
+    }
+
+    let (repo, _) = ctx.repo(project)?;
+    let node = Node::new(ctx.profile.socket());
+    let signer = session.signer(&ctx.profile)?;
+    let mut issues = ctx.profile.issues_mut(&repo)?;
+
+    // The signer's copies of the issues are what updates are written to, so they are what is
+    // put back should an update of an atomic batch fail.
+    let heads = if batch.atomic {
+        batch
+            .updates
+            .iter()
+            .map(|update| ObjectId::from(update.id))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|id| Ok((id, issue_head(&repo, signer.public_key(), &id)?)))
+            .collect::<Result<Vec<_>, Error>>()?
+    } else {
+        Vec::new()
+    };
+
+    let mut results = Vec::with_capacity(batch.updates.len());
+    let mut applied = 0;
+    let mut failed = false;
+    for IssueBatchUpdate { id, action } in batch.updates {
+        if failed && batch.atomic {
+            results.push(json!({ "id": id, "success": false, "skipped": true }));
+            continue;
+        }
+        let entry = issues
+            .get_mut(&id.into())
+            .map_err(Error::from)
+            .and_then(|mut issue| issue_action(&mut issue, action, &repo, &signer));
+
+        match entry {
+            Ok(entry) => {
+                applied += 1;
+                results.push(json!({ "id": id, "success": true, "entry": entry }));
+            }
+            Err(e) => {
+                failed = true;
+                results.push(json!({ "id": id, "success": false, "error": e.to_string() }));
+            }
+        }
+    }
+
+    let status = if failed && batch.atomic {
+        if applied > 0 {
+            issues_restore(&repo, &mut issues, &heads, &signer)?;
+        }
+        for result in &mut results {
+            if result["success"] == true {
+                *result = json!({ "id": result["id"], "success": false, "skipped": true });
+            }
+        }
+        StatusCode::BAD_REQUEST
+    } else {
+        if applied > 0 {
+            ctx.events.notify(repo.id());
+            announce_refs(node, repo.id())?;
+        }
+        StatusCode::OK
+    };
+
+    Ok::<_, Error>((
+        status,
+        Json(json!({ "success": !failed, "results": results })),
+    ))
+}
+
+/// The head of the signer's copy of an issue, if the signer has one.
+fn issue_head(
+    repo: &storage::git::Repository,
+    signer: &PublicKey,
+    id: &ObjectId,
+) -> Result<Option<git::raw::Oid>, Error> {
+    let name = git::refs::storage::cob(signer, &issue::TYPENAME, id);
+
+    match repo.backend.refname_to_id(name.as_str()) {
+        Ok(oid) => Ok(Some(oid)),
+        Err(e) if e.code() == git::raw::ErrorCode::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Put the signer's copies of issues back to the given heads, see [`issue_head`], removing
+/// the copies the signer didn't have. The cache and the signed references follow.
+fn issues_restore<G: Signer>(
+    repo: &storage::git::Repository,
+    issues: &mut issue::Cache<issue::Issues<'_, storage::git::Repository>, cache::StoreWriter>,
+    heads: &[(ObjectId, Option<git::raw::Oid>)],
+    signer: &G,
+) -> Result<(), Error> {
+    for (id, head) in heads {
+        let name = git::refs::storage::cob(signer.public_key(), &issue::TYPENAME, id);
+
+        match head {
+            Some(oid) => {
+                repo.backend.reference(
+                    name.as_str(),
+                    *oid,
+                    true,
+                    "Restore issue after failed batch",
+                )?;
+            }
+            None => match repo.backend.find_reference(name.as_str()) {
+                Ok(mut reference) => reference.delete()?,
+                Err(e) if e.code() == git::raw::ErrorCode::NotFound => {}
+                Err(e) => return Err(e.into()),
+            },
+        }
+        issues.write(id)?;
+    }
+    repo.sign_refs(signer)?;
+
+    Ok(())
 }
 
 /// Get project issue.
@@ -767,16 +1135,34 @@ async fn issue_handler(
     Path((project, issue_id)): Path<(RepoId, Oid)>,
 ) -> impl IntoResponse {
     let (repo, _) = ctx.repo(project)?;
-    let issue = ctx
-        .profile
-        .issues(&repo)?
-        .get(&issue_id.into())?
+    let issue = tracing::info_span!("cob.cache", kind = "issues")
+        .in_scope(|| Ok::<_, Error>(ctx.profile.issues(&repo)?.get(&issue_id.into())?))?
         .ok_or(Error::NotFound)?;
     let aliases = ctx.profile.aliases();
 
     Ok::<_, Error>(Json(api::json::issue(issue_id.into(), issue, &aliases)))
 }
 
+/// Get the timeline of a project issue, oldest event first.
+/// `GET /projects/:project/issues/:id/timeline`
+async fn issue_timeline_handler(
+    State(ctx): State<Context>,
+    Path((project, issue_id)): Path<(RepoId, Oid)>,
+    pagination: Pagination,
+) -> impl IntoResponse {
+    let (repo, _) = ctx.repo(project)?;
+    let timeline = api::timeline::issue(&repo, issue_id.into())?.ok_or(Error::NotFound)?;
+    let aliases = ctx.profile.aliases();
+    let total = timeline.len();
+    let timeline = pagination
+        .default_per_page(MAX_PER_PAGE)
+        .walk(timeline, |event| (event.entry, event.index))?
+        .with_total(total)
+        .map(|event| api::json::timeline_event(event, &aliases));
+
+    Ok::<_, Error>(timeline)
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct PatchCreate {
     pub title: String,
@@ -794,13 +1180,11 @@ async fn patch_create_handler(
     Path(project): Path<RepoId>,
     Json(patch): Json<PatchCreate>,
 ) -> impl IntoResponse {
-    api::auth::validate(&ctx, &token).await?;
+    let session = api::auth::validate(&ctx, &token).await?;
+    session.authorize(Capability::PatchesWrite, &project)?;
 
     let node = Node::new(ctx.profile.socket());
-    let signer = ctx
-        .profile
-        .signer()
-        .map_err(|_| Error::Auth("Unauthorized"))?;
+    let signer = session.signer(&ctx.profile)?;
     let (repo, _) = ctx.repo(project)?;
     let mut patches = ctx.profile.patches_mut(&repo)?;
     let base_oid = repo.raw().merge_base(*patch.target, *patch.oid)?;
@@ -817,6 +1201,7 @@ async fn patch_create_handler(
         )
         .map_err(Error::from)?;
 
+    ctx.events.notify(repo.id());
     announce_refs(node, repo.id())?;
 
     Ok::<_, Error>((
@@ -833,13 +1218,15 @@ async fn patch_update_handler(
     Path((project, patch_id)): Path<(RepoId, Oid)>,
     Json(action): Json<patch::Action>,
 ) -> impl IntoResponse {
-    api::auth::validate(&ctx, &token).await?;
+    let capability = match action {
+        patch::Action::Merge { .. } => Capability::PatchesMerge,
+        _ => Capability::PatchesWrite,
+    };
+    let session = api::auth::validate(&ctx, &token).await?;
+    session.authorize(capability, &project)?;
 
     let node = Node::new(ctx.profile.socket());
-    let signer = ctx
-        .profile
-        .signer()
-        .map_err(|_| Error::Auth("Unauthorized"))?;
+    let signer = session.signer(&ctx.profile)?;
     let (repo, _) = ctx.repo(project)?;
     let mut patches = ctx.profile.patches_mut(&repo)?;
     let mut patch = patches.get_mut(&patch_id.into())?;
@@ -964,45 +1351,68 @@ async fn patch_update_handler(
         }
     };
 
+    ctx.events.notify(repo.id());
     announce_refs(node, repo.id())?;
 
     Ok::<_, Error>(Json(json!({ "success": true, "id": id })))
 }
 
 /// Get project patches list.
-/// `GET /projects/:project/patches`
+/// `GET /projects/:project/patches?reviewer=<did>&verdict=<accepted|rejected|none>`
+///
+/// Takes the same filters and sorting as the issues list, and patch specific filters, see
+/// [`PatchesQuery`]. The number of matching patches is returned in the `X-Total-Count` header.
 async fn patches_handler(
     State(ctx): State<Context>,
     Path(rid): Path<RepoId>,
     Query(qs): Query<CobsQuery<api::PatchState>>,
+    Query(filters): Query<PatchesQuery>,
+    pagination: Pagination,
 ) -> impl IntoResponse {
-    let (repo, _) = ctx.repo(rid)?;
-    let CobsQuery {
-        page,
-        per_page,
-        state,
-    } = qs;
-    let page = page.unwrap_or(0);
-    let per_page = per_page.unwrap_or(10);
-    let state = state.unwrap_or_default();
-    let patches = ctx.profile.patches(&repo)?;
-    let mut patches = patches
-        .list()?
-        .filter_map(|r| {
-            let (id, patch) = r.ok()?;
-            (state.matches(patch.state())).then_some((id, patch))
-        })
-        .collect::<Vec<_>>();
-    patches.sort_by(|(_, a), (_, b)| b.timestamp().cmp(&a.timestamp()));
+    let (repo, doc) = ctx.repo(rid)?;
+    let project = doc.project()?;
+    let default_branch = project.default_branch().as_str();
+    let state = qs.state.clone().unwrap_or_default();
+    let patches = tracing::info_span!("cob.cache", kind = "patches").in_scope(|| {
+        let patches = ctx.profile.patches(&repo)?;
+        let patches = patches
+            .list()?
+            .filter_map(|r| {
+                let (id, patch) = r.ok()?;
+                let matches = state.matches(patch.state())
+                    && qs.matches(
+                        patch.title(),
+                        patch.author().id(),
+                        patch.timestamp(),
+                        patch.labels(),
+                        patch.assignees(),
+                    )
+                    && filters.matches(&patch, default_branch);
+                matches.then_some((id, patch))
+            })
+            .collect::<Vec<_>>();
+
+        Ok::<_, Error>(patches)
+    })?;
+    let key = |(id, patch): &(_, patch::Patch)| {
+        let sort = match qs.sort {
+            CobsSort::Created => patch.timestamp().as_millis(),
+            CobsSort::Updated => patch.updated_at().as_millis(),
+            CobsSort::Comments => patch_comments(patch) as u64,
+        };
+        (sort, *id)
+    };
     let aliases = ctx.profile.aliases();
-    let patches = patches
-        .into_iter()
-        .map(|(id, patch)| api::json::patch(id, patch, &repo, &aliases))
-        .skip(page * per_page)
-        .take(per_page)
-        .collect::<Vec<_>>();
+    let patches = pagination
+        .paginate(patches, key, qs.direction)?
+        .map(|(id, patch)| api::json::patch(id, patch, &repo, &aliases));
 
-    Ok::<_, Error>(Json(patches))
+    Ok::<_, Error>(patches)
+}
+
+/// Number of comments across all revisions of a patch.
+fn patch_comments(patch: &patch::Patch) -> usize {
+    patch.revisions().map(|(_, r)| r.replies().count()).sum()
 }
 
 /// Get project patch.
@@ -1012,8 +1422,9 @@ async fn patch_handler(
     Path((rid, patch_id)): Path<(RepoId, Oid)>,
 ) -> impl IntoResponse {
     let (repo, _) = ctx.repo(rid)?;
-    let patches = ctx.profile.patches(&repo)?;
-    let patch = patches.get(&patch_id.into())?.ok_or(Error::NotFound)?;
+    let patch = tracing::info_span!("cob.cache", kind = "patches")
+        .in_scope(|| Ok::<_, Error>(ctx.profile.patches(&repo)?.get(&patch_id.into())?))?
+        .ok_or(Error::NotFound)?;
     let aliases = ctx.profile.aliases();
 
     Ok::<_, Error>(Json(api::json::patch(
@@ -1024,17 +1435,44 @@ async fn patch_handler(
     )))
 }
 
+/// Get the timeline of a project patch, oldest event first.
+/// `GET /projects/:project/patches/:id/timeline`
+async fn patch_timeline_handler(
+    State(ctx): State<Context>,
+    Path((project, patch_id)): Path<(RepoId, Oid)>,
+    pagination: Pagination,
+) -> impl IntoResponse {
+    let (repo, _) = ctx.repo(project)?;
+    let timeline = api::timeline::patch(&repo, patch_id.into())?.ok_or(Error::NotFound)?;
+    let aliases = ctx.profile.aliases();
+    let total = timeline.len();
+    let timeline = pagination
+        .default_per_page(MAX_PER_PAGE)
+        .walk(timeline, |event| (event.entry, event.index))?
+        .with_total(total)
+        .map(|event| api::json::timeline_event(event, &aliases));
+
+    Ok::<_, Error>(timeline)
+}
+
 #[cfg(test)]
 mod routes {
+    use std::collections::BTreeSet;
     use std::net::SocketAddr;
+    use std::str::FromStr;
 
     use axum::body::Body;
     use axum::extract::connect_info::MockConnectInfo;
     use axum::http::StatusCode;
     use pretty_assertions::assert_eq;
+    use radicle::cob::{Label, ObjectId};
+    use radicle::identity::RepoId;
     use radicle::storage::ReadStorage;
+    use radicle_crypto::test::signer::MockSigner;
     use serde_json::json;
 
+    use crate::api::auth::{Capability, Scope};
+    use crate::api::search_index::index;
     use crate::test::*;
 
     #[tokio::test]
@@ -1219,6 +1657,7 @@ mod routes {
     }
 
     #[tokio::test]
+    #[allow(clippy::useless_format)]
     async fn test_search_projects() {
         let tmp = tempfile::tempdir().unwrap();
         let app = super::router(seed(tmp.path()));
@@ -1240,6 +1679,7 @@ mod routes {
                   }
                 ],
                 "seeds": 0,
+                "score": 0.9,
               },
               {
                 "rid": "rad:z4GypKmh1gkEfmkXtarcYnkvtFUfE",
@@ -1253,12 +1693,14 @@ mod routes {
                   },
                 ],
                 "seeds": 0,
+                "score": 0.8,
               },
             ])
         );
     }
 
     #[tokio::test]
+    #[allow(clippy::useless_format)]
     async fn test_search_projects_pagination() {
         let tmp = tempfile::tempdir().unwrap();
         let app = super::router(seed(tmp.path()));
@@ -1280,9 +1722,245 @@ mod routes {
                   }
                 ],
                 "seeds": 0,
+                "score": 0.9,
+              },
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_projects_cursor() {
+        let tmp = tempfile::tempdir().unwrap();
+        let app = super::router(seed(tmp.path()));
+        let next = |response: &Response| {
+            let links = response.headers()[axum::http::header::LINK]
+                .to_str()
+                .unwrap()
+                .to_owned();
+            links
+                .split(", ")
+                .find(|l| l.ends_with("rel=\"next\""))
+                .and_then(|l| l.strip_prefix('<')?.split_once('>'))
+                .map(|(url, _)| url.to_owned())
+        };
+
+        let response = get(&app, "/projects/search?q=hello&perPage=1").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-total-count"], "2");
+        let url = next(&response).unwrap();
+        assert_eq!(response.json().await[0]["name"], "hello-world");
+
+        let response = get(&app, url).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(next(&response), None);
+        assert_eq!(response.json().await[0]["name"], "again-hello-world");
+
+        // Page numbers far past the end don't overflow.
+        let response = get(
+            &app,
+            format!(
+                "/projects/search?q=hello&page={}&perPage={}",
+                usize::MAX,
+                usize::MAX
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.json().await, json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_search_projects_fuzzy() {
+        let tmp = tempfile::tempdir().unwrap();
+        let app = super::router(seed(tmp.path()));
+        async fn search(app: &axum::Router, query: &str) -> Vec<(String, f64)> {
+            let response = get(app, format!("/projects/search?{query}")).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            response
+                .json()
+                .await
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|r| {
+                    (
+                        r["name"].as_str().unwrap().to_owned(),
+                        r["score"].as_f64().unwrap(),
+                    )
+                })
+                .collect()
+        }
+        let results = |expected: &[(&str, f64)]| {
+            expected
+                .iter()
+                .map(|(name, score)| (name.to_string(), *score))
+                .collect::<Vec<_>>()
+        };
+
+        // Case-insensitive.
+        assert_eq!(
+            search(&app, "q=HELLO-WORLD").await,
+            results(&[("hello-world", 1.), ("again-hello-world", 0.8)])
+        );
+        // Typos, ties broken by name.
+        assert_eq!(
+            search(&app, "q=helo").await,
+            results(&[("again-hello-world", 0.6), ("hello-world", 0.6)])
+        );
+        // Descriptions and delegate aliases.
+        assert_eq!(
+            search(&app, "q=sorting").await,
+            results(&[("again-hello-world", 0.48)])
+        );
+        assert_eq!(
+            search(&app, "q=seed").await,
+            results(&[("again-hello-world", 0.8), ("hello-world", 0.8)])
+        );
+        assert_eq!(search(&app, "q=quokka").await, results(&[]));
+        // Letters spread far apart over a description don't match.
+        assert_eq!(search(&app, "q=dt").await, results(&[]));
+
+        // Filters.
+        assert_eq!(
+            search(&app, "q=hello&hasOpenPatches=true").await,
+            results(&[("hello-world", 0.9)])
+        );
+        assert_eq!(
+            search(&app, "q=hello&hasOpenPatches=false").await,
+            results(&[("again-hello-world", 0.8)])
+        );
+        assert_eq!(
+            search(&app, &format!("q=hello&delegate={DID}")).await.len(),
+            2
+        );
+        assert_eq!(
+            search(&app, &format!("q=hello&delegate={CONTRIBUTOR_DID}")).await,
+            results(&[])
+        );
+        assert_eq!(search(&app, "q=hello&visibility=public").await.len(), 2);
+        let response = get(&app, "/projects/search?q=hello&visibility=private").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(search(&app, "q=hello&updatedSince=0").await.len(), 2);
+        assert_eq!(
+            search(&app, "q=hello&updatedSince=4102444800").await,
+            results(&[])
+        );
+        // Without a query, every repository matching the filters is returned.
+        assert_eq!(
+            search(&app, "hasOpenPatches=true").await,
+            results(&[("hello-world", 0.)])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_projects_search() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ctx = contributor(tmp.path());
+        let app = super::router(ctx.to_owned());
+
+        create_session(ctx.clone()).await;
+        index(&ctx).unwrap();
+
+        let response = get(&app, format!("/projects/{CONTRIBUTOR_RID}/search?q=quokka")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.json().await, json!([]));
+
+        let body = serde_json::to_vec(&json!({
+            "title": "Quokka sighting",
+            "description": "A quokka was seen near the README.",
+            "labels": [],
+            "embeds": [],
+            "assignees": [],
+        }))
+        .unwrap();
+        let response = post(
+            &app,
+            format!("/projects/{CONTRIBUTOR_RID}/issues"),
+            Some(Body::from(body)),
+            Some(SESSION_ID.to_string()),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let id = response.json().await["id"].clone();
+
+        // The index picks up the new issue.
+        index(&ctx).unwrap();
+        let response = get(&app, format!("/projects/{CONTRIBUTOR_RID}/search?q=Quokka")).await;
+        let mut hits = response.json().await;
+        assert!(hits[0]["score"].as_f64().unwrap() > 0.);
+        hits[0]["score"].take();
+        assert_eq!(
+            hits,
+            json!([
+              {
+                "type": "issue",
+                "rid": CONTRIBUTOR_RID,
+                "id": id,
+                "title": "Quokka sighting",
+                "state": "open",
+                "author": {
+                  "id": CONTRIBUTOR_DID,
+                  "alias": CONTRIBUTOR_ALIAS,
+                },
+                "timestamp": TIMESTAMP,
+                "score": null,
+                "highlights": {
+                  "title": [
+                    { "text": "Quokka", "match": true },
+                    { "text": " sighting", "match": false },
+                  ],
+                  "body": [
+                    { "text": "A ", "match": false },
+                    { "text": "quokka", "match": true },
+                    { "text": " was seen near the README.", "match": false },
+                  ],
+                },
               },
             ])
         );
+
+        // Titles rank higher than bodies.
+        let response = get(&app, format!("/projects/{CONTRIBUTOR_RID}/search?q=hello")).await;
+        let total = response.headers()["x-total-count"]
+            .to_str()
+            .unwrap()
+            .parse::<usize>()
+            .unwrap();
+        let hits = response.json().await;
+        let titles = hits
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|h| h["title"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(titles.first(), Some(&"A new `hello world`"), "{hits}");
+        assert_eq!(titles.len(), total);
+
+        let response = get(
+            &app,
+            format!("/projects/{CONTRIBUTOR_RID}/search?q=hello&perPage=1"),
+        )
+        .await;
+        assert_eq!(
+            response.headers()["x-total-count"],
+            total.to_string().as_str()
+        );
+        assert!(response.headers().contains_key(axum::http::header::LINK));
+        assert_eq!(response.json().await[0]["title"], "A new `hello world`");
+
+        let response = get(
+            &app,
+            format!("/projects/{CONTRIBUTOR_RID}/search?q=quokka&type=patch"),
+        )
+        .await;
+        assert_eq!(response.json().await, json!([]));
+
+        let response = get(
+            &app,
+            format!("/projects/{CONTRIBUTOR_RID}/search?q=quokka&type=project"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
     }
 
     #[tokio::test]
@@ -1525,6 +2203,53 @@ mod routes {
         );
     }
 
+    #[tokio::test]
+    async fn test_projects_commits_pagination() {
+        let tmp = tempfile::tempdir().unwrap();
+        let app = super::router(seed(tmp.path()));
+        let link = |response: &Response, rel: &str| {
+            let links = response.headers()[axum::http::header::LINK]
+                .to_str()
+                .unwrap()
+                .to_owned();
+            links
+                .split(", ")
+                .find(|l| l.ends_with(&format!("rel=\"{rel}\"")))
+                .and_then(|l| l.strip_prefix('<')?.split_once('>'))
+                .map(|(url, _)| url.to_owned())
+        };
+
+        let response = get(&app, format!("/projects/{RID}/commits?perPage=1")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get("x-total-count").is_none());
+        assert_eq!(link(&response, "prev"), None);
+        let next = link(&response, "next").unwrap();
+        assert!(next.starts_with(&format!("/projects/{RID}/commits?cursor=")));
+        assert_eq!(response.json().await[0]["id"], HEAD);
+
+        let response = get(&app, &next).await;
+        assert!(link(&response, "prev").is_some());
+        let next = link(&response, "next").unwrap();
+        assert_eq!(response.json().await[0]["id"], PARENT);
+
+        let response = get(&app, &next).await;
+        assert_eq!(link(&response, "next"), None);
+        assert_eq!(response.json().await[0]["id"], INITIAL_COMMIT);
+
+        // Page numbers are still understood.
+        let response = get(&app, format!("/projects/{RID}/commits?perPage=1&page=1")).await;
+        assert_eq!(response.json().await[0]["id"], PARENT);
+
+        let response = get(&app, format!("/projects/{RID}/commits?cursor=bogus")).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // Cursors can't be reused with other filters.
+        let response = get(&app, format!("/projects/{RID}/commits?perPage=1")).await;
+        let next = link(&response, "next").unwrap();
+        let response = get(&app, format!("{next}&since=0")).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
     #[tokio::test]
     async fn test_projects_commits_not_found() {
         let tmp = tempfile::tempdir().unwrap();
@@ -1656,6 +2381,83 @@ mod routes {
         assert_eq!(response.status(), StatusCode::NOT_FOUND);
     }
 
+    #[tokio::test]
+    async fn test_projects_code_search() {
+        let tmp = tempfile::tempdir().unwrap();
+        let app = super::router(seed(tmp.path()));
+        let response = get(&app, format!("/projects/{RID}/code-search?q=world")).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.json().await,
+            json!({
+              "sha": HEAD,
+              "matches": [
+                {
+                  "path": "README",
+                  "line": 1,
+                  "column": 7,
+                  "text": "Hello World!",
+                  "ranges": [{ "start": 6, "end": 11 }],
+                  "before": [],
+                  "after": [],
+                },
+                {
+                  "path": "dir1/README",
+                  "line": 1,
+                  "column": 7,
+                  "text": "Hello World from dir1!",
+                  "ranges": [{ "start": 6, "end": 11 }],
+                  "before": [],
+                  "after": [],
+                },
+              ],
+              "stats": { "files": 2, "bytes": 36 },
+              "truncated": null,
+            })
+        );
+
+        let response = get(
+            &app,
+            format!("/projects/{RID}/code-search?q=world&case=sensitive"),
+        )
+        .await;
+        assert_eq!(response.json().await["matches"], json!([]));
+
+        let response = get(
+            &app,
+            format!("/projects/{RID}/code-search?q=W.r&regex=true&path=dir1/*&sha={PARENT}"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        // The folder doesn't exist yet at the parent commit.
+        assert_eq!(response.json().await["matches"], json!([]));
+
+        let response = get(
+            &app,
+            format!("/projects/{RID}/code-search?q=W.r&regex=true&path=dir1/*"),
+        )
+        .await;
+        let body = response.json().await;
+        assert_eq!(body["matches"][0]["path"], "dir1/README");
+        assert_eq!(body["stats"]["files"], 1);
+
+        let response = get(&app, format!("/projects/{RID}/code-search?q=(&regex=true")).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = get(&app, format!("/projects/{RID}/code-search?q=")).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = get(
+            &app,
+            format!(
+                "/projects/{RID}/code-search?q=world&sha=0000000000000000000000000000000000000001"
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
     #[tokio::test]
     async fn test_projects_remotes_root() {
         let tmp = tempfile::tempdir().unwrap();
@@ -1950,6 +2752,119 @@ mod routes {
         );
     }
 
+    #[tokio::test]
+    async fn test_projects_issues_filters() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ctx = contributor(tmp.path());
+        let app = super::router(ctx.to_owned());
+
+        create_session(ctx).await;
+
+        for (title, labels, assignees) in [
+            ("Issue #2", vec!["bug"], vec![CONTRIBUTOR_DID]),
+            ("Another thing", vec![], vec![]),
+        ] {
+            let body = serde_json::to_vec(&json!({
+                "title": title,
+                "description": "",
+                "labels": labels,
+                "embeds": [],
+                "assignees": assignees,
+            }))
+            .unwrap();
+            let response = post(
+                &app,
+                format!("/projects/{CONTRIBUTOR_RID}/issues"),
+                Some(Body::from(body)),
+                Some(SESSION_ID.to_string()),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::CREATED);
+        }
+        let body = serde_json::to_vec(&json!({
+          "type": "comment",
+          "body": "A reply",
+          "embeds": [],
+          "replyTo": ISSUE_DISCUSSION_ID,
+        }))
+        .unwrap();
+        let response = patch(
+            &app,
+            format!("/projects/{CONTRIBUTOR_RID}/issues/{ISSUE_DISCUSSION_ID}"),
+            Some(Body::from(body)),
+            Some(SESSION_ID.to_string()),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let titles = |query: String| {
+            let app = app.clone();
+            async move {
+                let response =
+                    get(&app, format!("/projects/{CONTRIBUTOR_RID}/issues?{query}")).await;
+                assert_eq!(response.status(), StatusCode::OK);
+                let total = response.headers()["x-total-count"]
+                    .to_str()
+                    .unwrap()
+                    .to_owned();
+                let titles = response
+                    .json()
+                    .await
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|i| i["title"].as_str().unwrap().to_owned())
+                    .collect::<Vec<_>>();
+                (total, titles)
+            }
+        };
+
+        let (total, all) = titles(String::new()).await;
+        assert_eq!(total, "3");
+        assert_eq!(all.len(), 3);
+        assert_eq!(
+            titles("label=bug".to_owned()).await,
+            ("1".to_owned(), vec!["Issue #2".to_owned()])
+        );
+        assert_eq!(
+            titles(format!("assignee={CONTRIBUTOR_DID}")).await,
+            ("1".to_owned(), vec!["Issue #2".to_owned()])
+        );
+        assert_eq!(titles(format!("author={CONTRIBUTOR_DID}")).await.1.len(), 3);
+        assert_eq!(titles(format!("author={DID}")).await.1.len(), 0);
+        let (_, mut found) = titles("q=ISSUE".to_owned()).await;
+        found.sort();
+        assert_eq!(found, ["Issue #1", "Issue #2"]);
+        assert_eq!(titles(format!("since={TIMESTAMP}")).await.1.len(), 3);
+        assert_eq!(titles(format!("since={}", TIMESTAMP + 1)).await.1.len(), 0);
+        assert_eq!(titles(format!("until={}", TIMESTAMP - 1)).await.1.len(), 0);
+
+        // The most commented issue comes first.
+        assert_eq!(titles("sort=comments".to_owned()).await.1[0], "Issue #1");
+        assert_eq!(
+            titles("sort=comments&direction=asc".to_owned()).await.1[2],
+            "Issue #1"
+        );
+        // Issues are sorted by their latest operation, which all happened at the same time.
+        assert_eq!(titles("sort=updated".to_owned()).await.1.len(), 3);
+        // Directions reverse each other.
+        let (_, mut asc) = titles("sort=created&direction=asc".to_owned()).await;
+        asc.reverse();
+        assert_eq!(asc, all);
+
+        // The total doesn't depend on the page.
+        let (total, page) = titles("perPage=1&page=1".to_owned()).await;
+        assert_eq!(total, "3");
+        assert_eq!(page, vec![all[1].clone()]);
+
+        let response = get(
+            &app,
+            format!("/projects/{CONTRIBUTOR_RID}/issues?sort=bogus"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
     #[tokio::test]
     async fn test_projects_issues_create() {
         const CREATED_ISSUE_ID: &str = "fcd0d5940b55df596cf8079fd1845903f1104bcd";
@@ -2048,6 +2963,201 @@ mod routes {
         );
     }
 
+    #[tokio::test]
+    async fn test_projects_events() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ctx = contributor(tmp.path());
+        let app = super::router(ctx.to_owned());
+
+        create_session(ctx).await;
+
+        let response = get(&app, format!("/projects/{CONTRIBUTOR_RID}/events")).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let mut events = response.events();
+        let body = serde_json::to_vec(&json!({
+            "title": "Issue #2",
+            "description": "Streamed to the board",
+            "labels": [],
+            "embeds": [],
+            "assignees": [],
+        }))
+        .unwrap();
+        let response = post(
+            &app,
+            format!("/projects/{CONTRIBUTOR_RID}/issues"),
+            Some(Body::from(body)),
+            Some(SESSION_ID.to_string()),
+        )
+        .await;
+        let issue_id = response.id().await.to_string();
+        let event = events.next_named("issue.created").await;
+
+        assert_eq!(
+            event.data,
+            json!({
+              "type": "issue.created",
+              "id": issue_id,
+              "remote": CONTRIBUTOR_NID,
+            })
+        );
+
+        let body = serde_json::to_vec(&json!({
+          "type": "edit",
+          "title": "Issue #2 on the board",
+        }))
+        .unwrap();
+        patch(
+            &app,
+            format!("/projects/{CONTRIBUTOR_RID}/issues/{issue_id}"),
+            Some(Body::from(body)),
+            Some(SESSION_ID.to_string()),
+        )
+        .await;
+        let event = events.next_named("issue.updated").await;
+
+        assert_eq!(event.data["id"], json!(issue_id));
+
+        // Resuming replays what was missed, starting after the given id.
+        let response = get_with_header(
+            &app,
+            format!("/projects/{CONTRIBUTOR_RID}/events"),
+            ("Last-Event-ID", "0"),
+        )
+        .await;
+        let mut events = response.events();
+        let event = events.next().await;
+
+        assert_eq!(event.id.as_deref(), Some("1"));
+        assert_eq!(event.event, "issue.created");
+        assert_eq!(event.data["id"], json!(issue_id));
+
+        // Ids from an unknown past can't be replayed.
+        let response = get_with_header(
+            &app,
+            format!("/projects/{CONTRIBUTOR_RID}/events"),
+            ("Last-Event-ID", "1000"),
+        )
+        .await;
+
+        assert_eq!(response.events().next().await.event, "resync");
+    }
+
+    #[tokio::test]
+    async fn test_projects_issues_create_forbidden() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ctx = contributor(tmp.path());
+        let app = super::router(ctx.to_owned());
+        let body = json!({
+            "title": "Issue #2",
+            "description": "Not allowed",
+            "labels": [],
+            "embeds": [],
+            "assignees": [],
+        });
+
+        create_session_with_scope(
+            ctx.to_owned(),
+            Scope {
+                capabilities: BTreeSet::new(),
+                repos: None,
+            },
+        )
+        .await;
+
+        let response = post(
+            &app,
+            format!("/projects/{CONTRIBUTOR_RID}/issues"),
+            Some(Body::from(serde_json::to_vec(&body).unwrap())),
+            Some(SESSION_ID.to_string()),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(
+            response.json().await,
+            json!({
+              "error": "Session lacks the `issues:write` capability",
+              "code": 403
+            })
+        );
+
+        create_session_with_scope(
+            ctx,
+            Scope {
+                capabilities: [Capability::IssuesWrite].into_iter().collect(),
+                repos: Some([RID.parse().unwrap()].into_iter().collect()),
+            },
+        )
+        .await;
+
+        let response = post(
+            &app,
+            format!("/projects/{CONTRIBUTOR_RID}/issues"),
+            Some(Body::from(serde_json::to_vec(&body).unwrap())),
+            Some(SESSION_ID.to_string()),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(
+            response.json().await,
+            json!({
+              "error": format!("Session is not allowed to access {CONTRIBUTOR_RID}"),
+              "code": 403
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_projects_issues_create_other_identity() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ctx = contributor(tmp.path());
+        let app = super::router(ctx.to_owned());
+        let member = radicle_crypto::test::signer::MockSigner::from_seed([0x01; 32]);
+        let issued_at = time::OffsetDateTime::now_utc();
+        let body = json!({
+            "title": "Issue #2",
+            "description": "Opened by another identity",
+            "labels": [],
+            "embeds": [],
+            "assignees": [],
+        });
+
+        ctx.sessions()
+            .insert(
+                SESSION_ID,
+                &crate::api::auth::Session {
+                    status: crate::api::auth::AuthState::Authorized,
+                    public_key: *radicle::crypto::Signer::public_key(&member),
+                    alias: radicle::node::Alias::new("member"),
+                    issued_at,
+                    expires_at: issued_at + time::Duration::hours(1),
+                    scope: Scope::default(),
+                },
+            )
+            .unwrap();
+
+        let response = post(
+            &app,
+            format!("/projects/{CONTRIBUTOR_RID}/issues"),
+            Some(Body::from(serde_json::to_vec(&body).unwrap())),
+            Some(SESSION_ID.to_string()),
+        )
+        .await;
+
+        // The httpd doesn't hold the keys of other identities, so their sessions are read-only.
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(
+            response.json().await,
+            json!({
+              "error": "Only the node operator can write, not did:key:z6Mkon3Necd6NkkyfoGoHxid2znGc59LU3K7mubaRcFbLfLX",
+              "code": 403
+            })
+        );
+    }
+
     #[tokio::test]
     async fn test_projects_issues_comment() {
         let tmp = tempfile::tempdir().unwrap();
@@ -2295,6 +3405,310 @@ mod routes {
         );
     }
 
+    #[tokio::test]
+    async fn test_projects_issues_batch() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ctx = contributor(tmp.path());
+        let app = super::router(ctx.to_owned());
+        let batch = format!("/projects/{CONTRIBUTOR_RID}/issues/batch");
+        let issue = format!("/projects/{CONTRIBUTOR_RID}/issues/{ISSUE_DISCUSSION_ID}");
+        let repo = ctx
+            .profile
+            .storage
+            .repository(RepoId::from_str(CONTRIBUTOR_RID).unwrap())
+            .unwrap();
+        let head = || {
+            repo.backend
+                .refname_to_id(&format!(
+                    "refs/namespaces/{}/refs/cobs/xyz.radicle.issue/{ISSUE_DISCUSSION_ID}",
+                    ctx.profile.public_key
+                ))
+                .unwrap()
+        };
+        let before = head();
+
+        create_session(ctx.clone()).await;
+
+        // In atomic mode, nothing is written unless every update applies.
+        let body = json!({
+          "atomic": true,
+          "updates": [
+            { "id": ISSUE_DISCUSSION_ID, "action": { "type": "edit", "title": "Batched" } },
+            {
+              "id": ISSUE_DISCUSSION_ID,
+              "action": { "type": "comment.redact", "id": ISSUE_DISCUSSION_ID },
+            },
+          ],
+        });
+        let response = post(
+            &app,
+            &batch,
+            Some(Body::from(body.to_string())),
+            Some(SESSION_ID.to_string()),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = response.json().await;
+        assert_eq!(response["success"], false);
+        assert!(response["results"][0]["error"].is_null());
+        assert_eq!(response["results"][0]["skipped"], true);
+        assert!(response["results"][1]["error"].is_string());
+        assert!(response["results"][1]["skipped"].is_null());
+
+        // The update that was written is undone.
+        assert_eq!(head(), before);
+        let response = get(&app, &issue).await;
+        assert_eq!(response.json().await["title"], "Issue #1");
+
+        // Otherwise, each update is applied on its own.
+        let body = json!({
+          "updates": [
+            { "id": ISSUE_DISCUSSION_ID, "action": { "type": "edit", "title": "Batched" } },
+            {
+              "id": ISSUE_DISCUSSION_ID,
+              "action": { "type": "comment", "body": "No reply", "embeds": [] },
+            },
+            {
+              "id": ISSUE_DISCUSSION_ID,
+              "action": { "type": "label", "labels": ["batch"] },
+            },
+          ],
+        });
+        let response = post(
+            &app,
+            &batch,
+            Some(Body::from(body.to_string())),
+            Some(SESSION_ID.to_string()),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = response.json().await;
+        assert_eq!(response["success"], false);
+        assert_eq!(
+            response["results"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|r| r["success"].as_bool().unwrap())
+                .collect::<Vec<_>>(),
+            vec![true, false, true]
+        );
+        assert_eq!(response["results"][0]["id"], ISSUE_DISCUSSION_ID);
+        assert!(response["results"][0]["entry"].is_string());
+        assert_eq!(response["results"][1]["error"], "`replyTo` missing");
+
+        let response = get(&app, &issue).await;
+        let response = response.json().await;
+        assert_eq!(response["title"], "Batched");
+        assert_eq!(response["labels"], json!(["batch"]));
+
+        // Later updates of an atomic batch see the earlier ones.
+        let body = json!({
+          "atomic": true,
+          "updates": [
+            {
+              "id": ISSUE_DISCUSSION_ID,
+              "action": { "type": "lifecycle", "state": { "status": "closed", "reason": "solved" } },
+            },
+            {
+              "id": ISSUE_DISCUSSION_ID,
+              "action": {
+                "type": "comment",
+                "body": "Done",
+                "embeds": [],
+                "replyTo": ISSUE_DISCUSSION_ID,
+              },
+            },
+          ],
+        });
+        let response = post(
+            &app,
+            &batch,
+            Some(Body::from(body.to_string())),
+            Some(SESSION_ID.to_string()),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.json().await["success"], true);
+
+        let response = get(&app, &issue).await;
+        let response = response.json().await;
+        assert_eq!(
+            response["state"],
+            json!({ "status": "closed", "reason": "solved" })
+        );
+        assert_eq!(response["discussion"][1]["body"], "Done");
+
+        let response = post(
+            &app,
+            &batch,
+            Some(Body::from(json!({ "updates": [] }).to_string())),
+            Some(SESSION_ID.to_string()),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_projects_issues_timeline() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ctx = contributor(tmp.path());
+        let app = super::router(ctx.to_owned());
+        let timeline = format!("/projects/{CONTRIBUTOR_RID}/issues/{ISSUE_DISCUSSION_ID}/timeline");
+
+        create_session(ctx).await;
+
+        let author = json!({ "id": CONTRIBUTOR_DID, "alias": CONTRIBUTOR_ALIAS });
+        let response = get(&app, &timeline).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.json().await,
+            json!([{
+              "entry": ISSUE_DISCUSSION_ID,
+              "author": author,
+              "timestamp": TIMESTAMP,
+              "type": "created",
+              "title": "Issue #1",
+            }])
+        );
+
+        let body = json!({
+          "updates": [
+            { "id": ISSUE_DISCUSSION_ID, "action": { "type": "edit", "title": "Timeline" } },
+            { "id": ISSUE_DISCUSSION_ID, "action": { "type": "label", "labels": ["bug"] } },
+            {
+              "id": ISSUE_DISCUSSION_ID,
+              "action": { "type": "assign", "assignees": [CONTRIBUTOR_DID] },
+            },
+            {
+              "id": ISSUE_DISCUSSION_ID,
+              "action": {
+                "type": "comment",
+                "body": "On it",
+                "embeds": [],
+                "replyTo": ISSUE_DISCUSSION_ID,
+              },
+            },
+            {
+              "id": ISSUE_DISCUSSION_ID,
+              "action": { "type": "label", "labels": ["good-first-issue"] },
+            },
+            {
+              "id": ISSUE_DISCUSSION_ID,
+              "action": { "type": "lifecycle", "state": { "status": "closed", "reason": "solved" } },
+            },
+          ],
+        });
+        let response = post(
+            &app,
+            format!("/projects/{CONTRIBUTOR_RID}/issues/batch"),
+            Some(Body::from(body.to_string())),
+            Some(SESSION_ID.to_string()),
+        )
+        .await;
+        let entries = response.json().await["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["entry"].clone())
+            .collect::<Vec<_>>();
+
+        let response = get(&app, &timeline).await;
+        let events = response.json().await;
+        let events = events.as_array().unwrap();
+        assert_eq!(events.len(), 8);
+        for event in &events[1..] {
+            assert_eq!(event["author"], author);
+        }
+        assert_eq!(
+            events[1],
+            json!({
+              "entry": entries[0],
+              "author": author,
+              "timestamp": TIMESTAMP,
+              "type": "title.changed",
+              "from": "Issue #1",
+              "to": "Timeline",
+            })
+        );
+        assert_eq!(
+            events[2..]
+                .iter()
+                .map(|e| {
+                    let mut e = e.clone();
+                    let e = e.as_object_mut().unwrap();
+                    e.remove("author");
+                    e.remove("timestamp");
+                    e.clone().into()
+                })
+                .collect::<Vec<serde_json::Value>>(),
+            vec![
+                json!({ "entry": entries[1], "type": "label.added", "label": "bug" }),
+                json!({ "entry": entries[2], "type": "assignee.added", "assignee": CONTRIBUTOR_DID }),
+                json!({
+                  "entry": entries[3],
+                  "type": "comment.added",
+                  "id": entries[3],
+                  "replyTo": ISSUE_DISCUSSION_ID,
+                }),
+                json!({ "entry": entries[4], "type": "label.added", "label": "good-first-issue" }),
+                json!({ "entry": entries[4], "type": "label.removed", "label": "bug" }),
+                json!({
+                  "entry": entries[5],
+                  "type": "state.changed",
+                  "state": { "status": "closed", "reason": "solved" },
+                }),
+            ]
+        );
+
+        let response = get(&app, format!("{timeline}?perPage=2")).await;
+        assert_eq!(response.headers()["x-total-count"], "8");
+        assert_eq!(response.json().await.as_array().unwrap().len(), 2);
+
+        let response = get(
+            &app,
+            format!("/projects/{CONTRIBUTOR_RID}/issues/{CONTRIBUTOR_PATCH_ID}/timeline"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_projects_issues_timeline_unauthorized() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ctx = contributor(tmp.path());
+        let app = super::router(ctx.to_owned());
+        let timeline = format!("/projects/{CONTRIBUTOR_RID}/issues/{ISSUE_DISCUSSION_ID}/timeline");
+        let other = MockSigner::from_seed([0xaa; 32]);
+        let rid = RepoId::from_str(CONTRIBUTOR_RID).unwrap();
+        let repo = ctx.profile.storage.repository(rid).unwrap();
+        let mut issues = ctx.profile.issues_mut(&repo).unwrap();
+        let id = ObjectId::from_str(ISSUE_DISCUSSION_ID).unwrap();
+        let mut issue = issues.get_mut(&id).unwrap();
+
+        // Only delegates can label issues, but anyone can comment on them.
+        issue
+            .label([Label::new("bug").unwrap()], &other)
+            .unwrap_err();
+        let comment = issue.comment("Me too", *id, vec![], &other).unwrap();
+
+        let response = get(&app, &timeline).await;
+        let events = response.json().await;
+        let events = events
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| (e["type"].as_str().unwrap(), e["entry"].clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            vec![
+                ("created", json!(ISSUE_DISCUSSION_ID)),
+                ("comment.added", json!(comment)),
+            ]
+        );
+    }
+
     #[tokio::test]
     async fn test_projects_issues_reply() {
         let tmp = tempfile::tempdir().unwrap();
@@ -2621,6 +4035,120 @@ mod routes {
         );
     }
 
+    #[tokio::test]
+    async fn test_projects_patches_filters() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ctx = contributor(tmp.path());
+        let app = super::router(ctx.to_owned());
+        create_session(ctx).await;
+
+        let ids = |query: String| {
+            let app = app.clone();
+            async move {
+                let response =
+                    get(&app, format!("/projects/{CONTRIBUTOR_RID}/patches?{query}")).await;
+                assert_eq!(response.status(), StatusCode::OK);
+                let total = response.headers()["x-total-count"]
+                    .to_str()
+                    .unwrap()
+                    .to_owned();
+                let ids = response
+                    .json()
+                    .await
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|p| p["id"].as_str().unwrap().to_owned())
+                    .collect::<Vec<_>>();
+                assert_eq!(total, ids.len().to_string());
+                ids
+            }
+        };
+        let found = [CONTRIBUTOR_PATCH_ID.to_owned()];
+        let response = get(
+            &app,
+            format!("/projects/{CONTRIBUTOR_RID}/patches/{CONTRIBUTOR_PATCH_ID}"),
+        )
+        .await;
+        let base = response.json().await["revisions"][0]["base"]
+            .as_str()
+            .unwrap()
+            .to_owned();
+
+        assert_eq!(ids(String::new()).await, found);
+        assert_eq!(ids(format!("author={CONTRIBUTOR_DID}")).await, found);
+        assert!(ids(format!("author={DID}")).await.is_empty());
+        assert!(ids("label=bug".to_owned()).await.is_empty());
+        assert!(ids(format!("assignee={CONTRIBUTOR_DID}")).await.is_empty());
+        assert_eq!(ids(format!("base={base}")).await, found);
+        assert!(ids(format!("base={HEAD}")).await.is_empty());
+        assert_eq!(ids("target=master".to_owned()).await, found);
+        assert!(ids("target=dev".to_owned()).await.is_empty());
+        assert_eq!(ids(format!("updatedSince={TIMESTAMP}")).await, found);
+        assert!(ids(format!("updatedSince={}", TIMESTAMP + 1))
+            .await
+            .is_empty());
+
+        // Waiting on review.
+        assert_eq!(
+            ids(format!("reviewer={CONTRIBUTOR_DID}&verdict=none")).await,
+            found
+        );
+        assert!(ids(format!("reviewer={CONTRIBUTOR_DID}")).await.is_empty());
+        assert!(ids("verdict=accepted".to_owned()).await.is_empty());
+
+        let body = serde_json::to_vec(&json!({
+          "type": "review",
+          "revision": CONTRIBUTOR_PATCH_ID,
+          "summary": "A small review",
+          "verdict": "accept",
+        }))
+        .unwrap();
+        let response = patch(
+            &app,
+            format!("/projects/{CONTRIBUTOR_RID}/patches/{CONTRIBUTOR_PATCH_ID}"),
+            Some(Body::from(body)),
+            Some(SESSION_ID.to_string()),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        assert_eq!(ids(format!("reviewer={CONTRIBUTOR_DID}")).await, found);
+        assert_eq!(ids("verdict=accepted".to_owned()).await, found);
+        assert!(ids("verdict=rejected".to_owned()).await.is_empty());
+        assert!(ids("verdict=none".to_owned()).await.is_empty());
+        assert!(ids(format!("reviewer={DID}")).await.is_empty());
+        assert_eq!(ids(format!("reviewer={DID}&verdict=none")).await, found);
+
+        let body = serde_json::to_vec(&json!({
+          "type": "merge",
+          "revision": CONTRIBUTOR_PATCH_ID,
+          "commit": PARENT,
+        }))
+        .unwrap();
+        let response = patch(
+            &app,
+            format!("/projects/{CONTRIBUTOR_RID}/patches/{CONTRIBUTOR_PATCH_ID}"),
+            Some(Body::from(body)),
+            Some(SESSION_ID.to_string()),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        assert_eq!(
+            ids(format!("state=merged&mergedBy={CONTRIBUTOR_DID}")).await,
+            found
+        );
+        assert!(ids(format!("state=merged&mergedBy={DID}")).await.is_empty());
+
+        let response = get(
+            &app,
+            format!("/projects/{CONTRIBUTOR_RID}/patches?verdict=maybe"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
     #[tokio::test]
     async fn test_projects_patches_assign() {
         let tmp = tempfile::tempdir().unwrap();
@@ -3592,6 +5120,112 @@ mod routes {
         );
     }
 
+    #[tokio::test]
+    async fn test_projects_patches_timeline() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ctx = contributor(tmp.path());
+        let app = super::router(ctx.to_owned());
+        let patch_path = format!("/projects/{CONTRIBUTOR_RID}/patches/{CONTRIBUTOR_PATCH_ID}");
+        create_session(ctx).await;
+
+        let mut entries = Vec::new();
+        for action in [
+            json!({
+              "type": "review",
+              "revision": CONTRIBUTOR_PATCH_ID,
+              "summary": "A small review",
+              "verdict": "reject",
+            }),
+            json!({
+              "type": "revision",
+              "description": "This is a new revision",
+              "base": PARENT,
+              "oid": HEAD,
+            }),
+            json!({
+              "type": "revision.comment",
+              "revision": CONTRIBUTOR_PATCH_ID,
+              "body": "Looks better",
+            }),
+            json!({ "type": "merge", "revision": CONTRIBUTOR_PATCH_ID, "commit": PARENT }),
+        ] {
+            let response = patch(
+                &app,
+                &patch_path,
+                Some(Body::from(action.to_string())),
+                Some(SESSION_ID.to_string()),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::OK, "{action}");
+            entries.push(response.id().await.to_string());
+        }
+
+        let response = get(&app, format!("{patch_path}/timeline")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let events = response.json().await;
+        let events = events.as_array().unwrap();
+        assert_eq!(
+            events[0],
+            json!({
+              "entry": CONTRIBUTOR_PATCH_ID,
+              "author": { "id": CONTRIBUTOR_DID, "alias": CONTRIBUTOR_ALIAS },
+              "timestamp": TIMESTAMP,
+              "type": "created",
+              "title": "A new `hello world`",
+            })
+        );
+        assert_eq!(
+            events[1..]
+                .iter()
+                .map(|e| {
+                    let mut e = e.clone();
+                    let e = e.as_object_mut().unwrap();
+                    e.remove("author");
+                    e.remove("timestamp");
+                    e.clone().into()
+                })
+                .collect::<Vec<serde_json::Value>>(),
+            vec![
+                json!({
+                  "entry": entries[0],
+                  "type": "review.submitted",
+                  "revision": CONTRIBUTOR_PATCH_ID,
+                  "review": entries[0],
+                  "verdict": "reject",
+                }),
+                json!({
+                  "entry": entries[1],
+                  "type": "revision.pushed",
+                  "revision": entries[1],
+                  "base": PARENT,
+                  "oid": HEAD,
+                }),
+                json!({
+                  "entry": entries[2],
+                  "type": "comment.added",
+                  "id": entries[2],
+                  "revision": CONTRIBUTOR_PATCH_ID,
+                  "replyTo": null,
+                }),
+                json!({
+                  "entry": entries[3],
+                  "type": "merged",
+                  "revision": CONTRIBUTOR_PATCH_ID,
+                  "commit": PARENT,
+                }),
+                json!({
+                  "entry": entries[3],
+                  "type": "state.changed",
+                  "state": {
+                    "status": "merged",
+                    "revision": CONTRIBUTOR_PATCH_ID,
+                    "commit": PARENT,
+                  },
+                }),
+            ]
+        );
+    }
+
     #[tokio::test]
     async fn test_projects_patches_merges() {
         let tmp = tempfile::tempdir().unwrap();
@@ -3680,6 +5314,60 @@ mod routes {
         );
     }
 
+    #[tokio::test]
+    async fn test_projects_patches_merge_forbidden() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ctx = contributor(tmp.path());
+        let app = super::router(ctx.to_owned());
+
+        create_session_with_scope(
+            ctx,
+            Scope {
+                capabilities: [Capability::PatchesWrite].into_iter().collect(),
+                repos: None,
+            },
+        )
+        .await;
+
+        let body = serde_json::to_vec(&json!({
+          "type": "merge",
+          "revision": CONTRIBUTOR_PATCH_ID,
+          "commit": PARENT,
+        }))
+        .unwrap();
+        let response = patch(
+            &app,
+            format!("/projects/{CONTRIBUTOR_RID}/patches/{CONTRIBUTOR_PATCH_ID}"),
+            Some(Body::from(body)),
+            Some(SESSION_ID.to_string()),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(
+            response.json().await,
+            json!({
+              "error": "Session lacks the `patches:merge` capability",
+              "code": 403
+            })
+        );
+
+        let body = serde_json::to_vec(&json!({
+          "type": "label",
+          "labels": ["bug"],
+        }))
+        .unwrap();
+        let response = patch(
+            &app,
+            format!("/projects/{CONTRIBUTOR_RID}/patches/{CONTRIBUTOR_PATCH_ID}"),
+            Some(Body::from(body)),
+            Some(SESSION_ID.to_string()),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
     #[tokio::test]
     async fn test_projects_private() {
         let tmp = tempfile::tempdir().unwrap();
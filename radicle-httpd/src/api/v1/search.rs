@@ -0,0 +1,119 @@
+use axum::extract::State;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+
+use radicle::storage::ReadStorage;
+
+use crate::api::error::Error;
+use crate::api::pagination::Pagination;
+use crate::api::search_index::Kind;
+use crate::api::{self, Context, SearchQuery};
+use crate::axum_extra::Query;
+
+pub fn router(ctx: Context) -> Router {
+    Router::new()
+        .route("/search", get(search_handler))
+        .with_state(ctx)
+}
+
+/// Search issues, patches and projects across all public repositories.
+/// `GET /search?q=<query>&type=<issue|patch|project>`
+async fn search_handler(
+    State(ctx): State<Context>,
+    Query(qs): Query<SearchQuery>,
+    pagination: Pagination,
+) -> impl IntoResponse {
+    let SearchQuery { q, kind } = qs;
+    let kinds = kind.map_or(Kind::ALL.to_vec(), |kind| vec![kind]);
+    let rids = ctx
+        .profile()
+        .storage
+        .repositories()?
+        .into_iter()
+        .filter(|info| info.doc.visibility.is_public())
+        .map(|info| info.rid)
+        .collect::<Vec<_>>();
+    let aliases = ctx.profile().aliases();
+    let hits = ctx
+        .search(q, kinds, rids, pagination)
+        .await?
+        .map(|hit| api::json::search_hit(&hit, &aliases));
+
+    Ok::<_, Error>(hits)
+}
+
+#[cfg(test)]
+mod routes {
+    use axum::http::{header, StatusCode};
+    use serde_json::Value;
+
+    use crate::api::search_index::index;
+    use crate::test::{self, get, RID};
+
+    #[tokio::test]
+    async fn test_search() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ctx = test::seed(tmp.path());
+        index(&ctx).unwrap();
+        let app = super::router(ctx);
+
+        let response = get(&app, "/search?q=hello&type=project").await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.json().await;
+        let hits = body.as_array().unwrap();
+        let mut names = hits
+            .iter()
+            .map(|h| h["title"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        names.sort();
+        // The private repository isn't searched.
+        assert_eq!(names, ["again-hello-world", "hello-world"]);
+        assert!(hits.iter().all(|h| h["type"] == "project"));
+
+        let response = get(&app, "/search?q=hello%20world").await;
+        let body = response.json().await;
+        let hits = body.as_array().unwrap();
+        let kinds = hits
+            .iter()
+            .map(|h| (h["type"].as_str().unwrap(), h["rid"].as_str().unwrap()))
+            .collect::<Vec<_>>();
+
+        for kind in ["issue", "patch"] {
+            assert!(kinds.contains(&(kind, RID)), "{body}");
+        }
+        assert!(kinds.contains(&("project", RID)), "{body}");
+        let scores = hits
+            .iter()
+            .map(|h| h["score"].as_f64().unwrap())
+            .collect::<Vec<_>>();
+        assert!(scores.windows(2).all(|w| w[0] >= w[1]), "{scores:?}");
+
+        let response = get(&app, "/search?q=hello%20world&perPage=1&page=1").await;
+        assert_eq!(response.json().await, Value::Array(vec![hits[1].clone()]));
+
+        let response = get(&app, "/search?q=hello%20world&perPage=1").await;
+        assert!(response.headers().contains_key("x-total-count"));
+        assert!(response.headers()[header::LINK]
+            .to_str()
+            .unwrap()
+            .contains("rel=\"next\""));
+        assert_eq!(response.json().await, Value::Array(vec![hits[0].clone()]));
+
+        // Page sizes are capped, and huge pages are out of range rather than an overflow.
+        let response = get(
+            &app,
+            format!("/search?q=hello&page={0}&perPage={0}", usize::MAX),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.json().await, Value::Array(vec![]));
+
+        let response = get(&app, "/search?q=nothing%20matches").await;
+        assert_eq!(response.json().await, Value::Array(vec![]));
+
+        let response = get(&app, "/search?q=hello&type=board").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
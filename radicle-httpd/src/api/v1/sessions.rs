@@ -1,5 +1,6 @@
 use std::iter::repeat_with;
 
+use axum::body::Bytes;
 use axum::extract::State;
 use axum::response::IntoResponse;
 use axum::routing::{post, put};
@@ -10,7 +11,7 @@ use radicle::crypto::{PublicKey, Signature};
 use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 
-use crate::api::auth::{self, AuthState, Session};
+use crate::api::auth::{self, AuthState, Scope, Session};
 use crate::api::error::Error;
 use crate::api::json;
 use crate::api::Context;
@@ -34,25 +35,50 @@ struct AuthChallenge {
     pk: PublicKey,
 }
 
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct SessionRequest {
+    /// Who the session is for. Defaults to the node operator.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pk: Option<PublicKey>,
+    /// What the session may do once authorized. Defaults to everything.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scope: Option<Scope>,
+}
+
 /// Create session.
+/// Sessions are for the node operator, unless the body names another public key, which then
+/// has to sign the challenge. Only the operator can write though: the httpd holds no keys for
+/// other identities, so their sessions are read-only, and endpoints that write to storage
+/// refuse them with `403 Forbidden`.
+/// The body may also narrow down what the session is allowed to do, with a `scope`, which is
+/// kept once the session is authorized.
 /// `POST /sessions`
-async fn session_create_handler(State(ctx): State<Context>) -> impl IntoResponse {
+async fn session_create_handler(State(ctx): State<Context>, body: Bytes) -> impl IntoResponse {
+    // An empty body stands for the operator, but a body that isn't a valid request is an error.
+    let request = if body.is_empty() {
+        SessionRequest::default()
+    } else {
+        serde_json::from_slice::<SessionRequest>(&body)
+            .map_err(|e| Error::BadRequest(format!("Invalid session request: {e}")))?
+    };
     let mut rng = fastrand::Rng::new();
     let session_id = repeat_with(|| rng.alphanumeric())
         .take(32)
         .collect::<String>();
-    let signer = ctx.profile.signer().map_err(Error::from)?;
+    let public_key = request.pk.unwrap_or(ctx.profile.public_key);
     let session = Session {
         status: AuthState::Unauthorized,
-        public_key: *signer.public_key(),
-        alias: ctx.profile.config.node.alias.clone(),
+        public_key,
+        alias: auth::alias(&ctx.profile, &public_key),
         issued_at: OffsetDateTime::now_utc(),
         expires_at: OffsetDateTime::now_utc()
             .checked_add(auth::UNAUTHORIZED_SESSIONS_EXPIRATION)
             .unwrap(),
+        scope: request.scope.unwrap_or_default(),
     };
-    let mut sessions = ctx.sessions.write().await;
-    sessions.insert(session_id.clone(), session.clone());
+    // Creating a session is a good moment to get rid of the ones nobody signed in to.
+    ctx.sessions.purge(OffsetDateTime::now_utc())?;
+    ctx.sessions.insert(&session_id, &session)?;
 
     Ok::<_, Error>((
         StatusCode::CREATED,
@@ -66,10 +92,9 @@ async fn session_handler(
     State(ctx): State<Context>,
     Path(session_id): Path<String>,
 ) -> impl IntoResponse {
-    let sessions = ctx.sessions.read().await;
-    let session = sessions.get(&session_id).ok_or(Error::NotFound)?;
+    let session = ctx.sessions.get(&session_id)?.ok_or(Error::NotFound)?;
 
-    Ok::<_, Error>(Json(json::session(session_id, session)))
+    Ok::<_, Error>(Json(json::session(session_id, &session)))
 }
 
 /// Update session.
@@ -79,8 +104,7 @@ async fn session_signin_handler(
     Path(session_id): Path<String>,
     Json(request): Json<AuthChallenge>,
 ) -> impl IntoResponse {
-    let mut sessions = ctx.sessions.write().await;
-    let session = sessions.get_mut(&session_id).ok_or(Error::NotFound)?;
+    let mut session = ctx.sessions.get(&session_id)?.ok_or(Error::NotFound)?;
     if session.status == AuthState::Unauthorized {
         if session.public_key != request.pk {
             return Err(Error::Auth("Invalid public key"));
@@ -97,6 +121,7 @@ async fn session_signin_handler(
         session.expires_at = OffsetDateTime::now_utc()
             .checked_add(auth::AUTHORIZED_SESSIONS_EXPIRATION)
             .unwrap();
+        ctx.sessions.insert(&session_id, &session)?;
 
         return Ok::<_, Error>(Json(json!({ "success": true })));
     }
@@ -114,8 +139,9 @@ async fn session_delete_handler(
     if token != session_id {
         return Err(Error::Auth("Not authorized to delete this session"));
     }
-    let mut sessions = ctx.sessions.write().await;
-    sessions.remove_entry(&token).ok_or(Error::NotFound)?;
+    if !ctx.sessions.remove(&token)? {
+        return Err(Error::NotFound);
+    }
 
     Ok::<_, Error>(Json(json!({ "success": true })))
 }
@@ -125,8 +151,10 @@ mod routes {
     use crate::commands::web::{sign, SessionInfo};
     use axum::body::Body;
     use axum::http::StatusCode;
+    use radicle::crypto::Signer as _;
+    use radicle_crypto::test::signer::MockSigner;
 
-    use crate::api::auth::{AuthState, Session};
+    use crate::api::auth::{AuthState, Capability, Scope, Session};
     use crate::test::{self, get, post, put};
 
     #[tokio::test]
@@ -180,4 +208,147 @@ mod routes {
         assert_eq!(status, StatusCode::OK);
         assert_eq!(body.status, AuthState::Authorized);
     }
+
+    #[tokio::test]
+    async fn test_session_other_identity() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ctx = test::seed(tmp.path());
+        let app = super::router(ctx.to_owned());
+        let member = MockSigner::from_seed([0x01; 32]);
+        let body = serde_json::to_vec(&super::SessionRequest {
+            pk: Some(*member.public_key()),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let response = post(&app, "/sessions", Some(Body::from(body)), None).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let session_info: SessionInfo = serde_json::from_value(response.json().await).unwrap();
+        assert_eq!(session_info.public_key, *member.public_key());
+
+        // Signing with the node's key doesn't authorize someone else's session.
+        let body = serde_json::to_vec(&super::AuthChallenge {
+            sig: sign(ctx.profile.signer().unwrap(), &session_info).unwrap(),
+            pk: session_info.public_key,
+        })
+        .unwrap();
+        let response = put(
+            &app,
+            format!("/sessions/{}", session_info.session_id),
+            Some(Body::from(body)),
+            None,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = serde_json::to_vec(&super::AuthChallenge {
+            sig: sign(Box::new(member), &session_info).unwrap(),
+            pk: session_info.public_key,
+        })
+        .unwrap();
+        let response = put(
+            &app,
+            format!("/sessions/{}", session_info.session_id),
+            Some(Body::from(body)),
+            None,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = get(&app, format!("/sessions/{}", session_info.session_id)).await;
+        let body: Session = serde_json::from_value(response.json().await).unwrap();
+
+        assert_eq!(body.status, AuthState::Authorized);
+        assert_eq!(body.public_key, session_info.public_key);
+
+        // Writes are signed with the node's key, which isn't this session's.
+        let app = super::super::projects::router(ctx.to_owned());
+        let body = serde_json::json!({
+            "title": "Issue #2",
+            "description": "Not the operator",
+            "labels": [],
+            "embeds": [],
+            "assignees": [],
+        });
+        let response = post(
+            &app,
+            format!("/projects/{}/issues", test::RID),
+            Some(Body::from(body.to_string())),
+            Some(session_info.session_id),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_session_scope() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ctx = test::seed(tmp.path());
+        let app = super::router(ctx.to_owned());
+        let scope = Scope {
+            capabilities: [Capability::PatchesWrite].into_iter().collect(),
+            repos: None,
+        };
+        let body = serde_json::to_vec(&super::SessionRequest {
+            scope: Some(scope.clone()),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let response = post(&app, "/sessions", Some(Body::from(body)), None).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let session_info: SessionInfo = serde_json::from_value(response.json().await).unwrap();
+        let body = serde_json::to_vec(&super::AuthChallenge {
+            sig: sign(ctx.profile.signer().unwrap(), &session_info).unwrap(),
+            pk: session_info.public_key,
+        })
+        .unwrap();
+        let response = put(
+            &app,
+            format!("/sessions/{}", session_info.session_id),
+            Some(Body::from(body)),
+            None,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // The scope is kept once authorized.
+        let response = get(&app, format!("/sessions/{}", session_info.session_id)).await;
+        let body: Session = serde_json::from_value(response.json().await).unwrap();
+
+        assert_eq!(body.status, AuthState::Authorized);
+        assert_eq!(body.scope, scope);
+
+        // Opening an issue needs a capability the session wasn't given.
+        let app = super::super::projects::router(ctx.to_owned());
+        let body = serde_json::json!({
+            "title": "Issue #2",
+            "description": "Out of scope",
+            "labels": [],
+            "embeds": [],
+            "assignees": [],
+        });
+        let response = post(
+            &app,
+            format!("/projects/{}/issues", test::RID),
+            Some(Body::from(body.to_string())),
+            Some(session_info.session_id),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_session_malformed_request() {
+        let tmp = tempfile::tempdir().unwrap();
+        let app = super::router(test::seed(tmp.path()));
+
+        let response = post(&app, "/sessions", Some(Body::from("{\"pk\": 1")), None).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = post(&app, "/sessions", None, None).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+    }
 }
@@ -0,0 +1,254 @@
+use std::iter::repeat_with;
+
+use axum::extract::State;
+use axum::response::IntoResponse;
+use axum::routing::{delete, post};
+use axum::{Json, Router};
+use axum_auth::AuthBearer;
+use hyper::StatusCode;
+use serde::{Deserialize, Serialize};
+use time::{Duration, OffsetDateTime};
+
+use crate::api::auth::{self, Scope, Token};
+use crate::api::error::Error;
+use crate::api::json;
+use crate::api::Context;
+use crate::axum_extra::Path;
+
+pub fn router(ctx: Context) -> Router {
+    Router::new()
+        .route("/tokens", post(token_create_handler).get(tokens_handler))
+        .route("/tokens/:id", delete(token_delete_handler))
+        .with_state(ctx)
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenRequest {
+    /// What the token is for.
+    pub name: String,
+    /// Lifetime of the token in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_in: Option<i64>,
+    /// What the token may do. Defaults to the scope of the session creating it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<Scope>,
+}
+
+/// Create an access token. Only sessions can create tokens, other tokens can't.
+/// The secret is part of this response only.
+/// `POST /tokens`
+async fn token_create_handler(
+    State(ctx): State<Context>,
+    AuthBearer(bearer): AuthBearer,
+    Json(request): Json<TokenRequest>,
+) -> impl IntoResponse {
+    let session = auth::validate_session(&ctx, &bearer).await?;
+    let name = request.name.trim();
+    if name.is_empty() {
+        return Err(Error::BadRequest("Token name must not be empty".to_owned()));
+    }
+    let expires_in = match request.expires_in {
+        Some(secs) if secs <= 0 => {
+            return Err(Error::BadRequest(
+                "Token expiry must be in the future".to_owned(),
+            ))
+        }
+        Some(secs) => Duration::seconds(secs),
+        None => auth::ACCESS_TOKENS_EXPIRATION,
+    };
+    let scope = request.scope.unwrap_or_else(|| session.scope.clone());
+    if !session.scope.contains(&scope) {
+        return Err(Error::Forbidden(
+            "Token scope exceeds the scope of the session".to_owned(),
+        ));
+    }
+
+    let mut rng = fastrand::Rng::new();
+    let id = repeat_with(|| rng.alphanumeric())
+        .take(16)
+        .collect::<String>();
+    let secret = auth::token_secret()?;
+    let issued_at = OffsetDateTime::now_utc();
+    let token = Token {
+        id,
+        name: name.to_owned(),
+        public_key: session.public_key,
+        alias: session.alias,
+        issued_at,
+        expires_at: issued_at
+            .checked_add(expires_in)
+            .ok_or_else(|| Error::BadRequest("Token expiry is out of range".to_owned()))?,
+        scope,
+    };
+    ctx.sessions.insert_token(&secret, &token)?;
+
+    let mut body = json::token(&token);
+    body["token"] = secret.into();
+
+    Ok::<_, Error>((StatusCode::CREATED, Json(body)))
+}
+
+/// List the access tokens of the session's identity.
+/// `GET /tokens`
+async fn tokens_handler(
+    State(ctx): State<Context>,
+    AuthBearer(bearer): AuthBearer,
+) -> impl IntoResponse {
+    let session = auth::validate_session(&ctx, &bearer).await?;
+    let tokens = ctx
+        .sessions
+        .tokens()?
+        .iter()
+        .filter(|t| t.public_key == session.public_key)
+        .map(json::token)
+        .collect::<Vec<_>>();
+
+    Ok::<_, Error>(Json(tokens))
+}
+
+/// Revoke an access token.
+/// `DELETE /tokens/:id`
+async fn token_delete_handler(
+    State(ctx): State<Context>,
+    AuthBearer(bearer): AuthBearer,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let session = auth::validate_session(&ctx, &bearer).await?;
+    let owned = ctx
+        .sessions
+        .tokens()?
+        .iter()
+        .any(|t| t.id == id && t.public_key == session.public_key);
+
+    if !owned || !ctx.sessions.remove_token(&id)? {
+        return Err(Error::NotFound);
+    }
+
+    Ok::<_, Error>(Json(json!({ "success": true })))
+}
+
+#[cfg(test)]
+mod routes {
+    use std::collections::BTreeSet;
+
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use serde_json::json;
+
+    use crate::api::auth::{self, Capability, Scope};
+    use crate::test::{self, delete, get, post, RID, SESSION_ID};
+
+    #[tokio::test]
+    async fn test_tokens() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ctx = test::seed(tmp.path());
+        let app = super::router(ctx.to_owned());
+
+        test::create_session(ctx.clone()).await;
+
+        let body = serde_json::to_vec(&json!({ "name": "ci", "expiresIn": 3600 })).unwrap();
+        let response = post(
+            &app,
+            "/tokens",
+            Some(Body::from(body)),
+            Some(SESSION_ID.to_string()),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let created = response.json().await;
+        let id = created["id"].as_str().unwrap().to_owned();
+        let secret = created["token"].as_str().unwrap().to_owned();
+
+        assert_eq!(created["name"], "ci");
+        assert_eq!(
+            created["expiresAt"].as_i64().unwrap() - created["issuedAt"].as_i64().unwrap(),
+            3600
+        );
+        assert!(secret.starts_with("radtok_"));
+
+        let session = auth::validate(&ctx, &secret).await.unwrap();
+        assert_eq!(session.public_key, ctx.profile().public_key);
+
+        // Tokens can't be used to manage tokens.
+        let response = get(&app, "/tokens").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = serde_json::to_vec(&json!({ "name": "again" })).unwrap();
+        let response = post(
+            &app,
+            "/tokens",
+            Some(Body::from(body)),
+            Some(secret.clone()),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = test::get_with_header(
+            &app,
+            "/tokens",
+            ("Authorization", &format!("Bearer {SESSION_ID}")),
+        )
+        .await;
+        let listed = response.json().await;
+
+        assert_eq!(listed.as_array().unwrap().len(), 1);
+        assert_eq!(listed[0]["id"], id.as_str());
+        assert!(listed[0].get("token").is_none());
+
+        let response = delete(&app, format!("/tokens/{id}"), Some(SESSION_ID.to_string())).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = delete(&app, format!("/tokens/{id}"), Some(SESSION_ID.to_string())).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(auth::validate(&ctx, &secret).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tokens_scope_exceeds_session() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ctx = test::seed(tmp.path());
+        let app = super::router(ctx.to_owned());
+
+        test::create_session_with_scope(
+            ctx,
+            Scope {
+                capabilities: BTreeSet::from([Capability::IssuesWrite]),
+                repos: Some(BTreeSet::from([RID.parse().unwrap()])),
+            },
+        )
+        .await;
+
+        let body = serde_json::to_vec(&json!({
+            "name": "ci",
+            "scope": { "capabilities": ["issues:write", "patches:merge"], "repos": [RID] },
+        }))
+        .unwrap();
+        let response = post(
+            &app,
+            "/tokens",
+            Some(Body::from(body)),
+            Some(SESSION_ID.to_string()),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // Without a scope, the token inherits the session's.
+        let body = serde_json::to_vec(&json!({ "name": "ci" })).unwrap();
+        let response = post(
+            &app,
+            "/tokens",
+            Some(Body::from(body)),
+            Some(SESSION_ID.to_string()),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(
+            response.json().await["scope"],
+            json!({ "capabilities": ["issues:write"], "repos": [RID] })
+        );
+    }
+}
@@ -1,10 +1,14 @@
 use radicle_cli::terminal as term;
+use radicle_httpd::commands::token as rad_token;
 use radicle_httpd::commands::web as rad_web;
 
 fn main() {
-    term::run_command_args::<rad_web::Options, _>(
-        rad_web::HELP,
-        rad_web::run,
-        std::env::args_os().skip(1).collect(),
-    )
+    let mut args = std::env::args_os().skip(1).collect::<Vec<_>>();
+
+    if args.first().is_some_and(|arg| arg == "token") {
+        args.remove(0);
+        term::run_command_args::<rad_token::Options, _>(rad_token::HELP, rad_token::run, args)
+    } else {
+        term::run_command_args::<rad_web::Options, _>(rad_web::HELP, rad_web::run, args)
+    }
 }
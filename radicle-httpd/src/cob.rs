@@ -0,0 +1,63 @@
+//! Collaborative object types specific to the HTTP daemon.
+pub mod board;
+pub mod labels;
+
+use radicle::cob::store;
+use radicle::prelude::ReadRepository;
+use radicle::storage::RepositoryError;
+
+/// Define the `type_name` function of a collaborative object type.
+macro_rules! type_name {
+    ($name:literal) => {
+        /// Type name of the collaborative object.
+        pub fn type_name() -> &'static radicle::cob::TypeName {
+            static TYPENAME: std::sync::OnceLock<radicle::cob::TypeName> =
+                std::sync::OnceLock::new();
+
+            TYPENAME.get_or_init(|| std::str::FromStr::from_str($name).expect("type name is valid"))
+        }
+    };
+}
+
+/// Implement [`radicle::cob::Evaluate`] for a [`store::Cob`] type, by decoding each entry into
+/// an operation. The type's error must convert from [`radicle::cob::op::OpEncodingError`].
+macro_rules! evaluate {
+    ($type:ty) => {
+        impl<R: radicle::prelude::ReadRepository> radicle::cob::Evaluate<R> for $type {
+            type Error = <$type as radicle::cob::store::Cob>::Error;
+
+            fn init(entry: &radicle::cob::Entry, repo: &R) -> Result<Self, Self::Error> {
+                let op = radicle::cob::Op::try_from(entry)?;
+
+                <$type as radicle::cob::store::Cob>::from_root(op, repo)
+            }
+
+            fn apply<'a, I>(
+                &mut self,
+                entry: &radicle::cob::Entry,
+                concurrent: I,
+                repo: &R,
+            ) -> Result<(), Self::Error>
+            where
+                I: Iterator<Item = (&'a radicle::cob::EntryId, &'a radicle::cob::Entry)>,
+            {
+                let op = radicle::cob::Op::try_from(entry)?;
+
+                <$type as radicle::cob::store::Cob>::op(self, op, concurrent.map(|(_, e)| e), repo)
+            }
+        }
+    };
+}
+
+use evaluate;
+use type_name;
+
+/// Open the store of a collaborative object type, at the current identity of the repository.
+pub fn open_store<T, R>(repository: &R) -> Result<store::Store<'_, T, R>, RepositoryError>
+where
+    R: ReadRepository + radicle::cob::Store,
+{
+    let identity = repository.identity_head()?;
+
+    Ok(store::Store::open(repository)?.identity(identity))
+}
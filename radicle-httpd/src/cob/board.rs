@@ -0,0 +1,516 @@
+//! Planning boards, stored as collaborative objects.
+//!
+//! A board is an ordered list of columns, each holding an ordered list of cards. Cards refer
+//! to other collaborative objects of the repository, usually issues or patches, by id. A card
+//! is on at most one column at a time.
+use std::str::FromStr;
+
+use nonempty::NonEmpty;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use radicle::cob;
+use radicle::cob::op::{Op, OpEncodingError};
+use radicle::cob::store::{self, CobAction};
+use radicle::cob::{ActorId, EntryId, ObjectId, Timestamp, TypeName};
+use radicle::crypto::Signer;
+use radicle::git;
+use radicle::prelude::ReadRepository;
+use radicle::storage::{RepositoryError, SignRepository};
+
+/// Longest board or column name accepted.
+const MAX_NAME_LEN: usize = 128;
+/// Longest column id accepted.
+const MAX_COLUMN_ID_LEN: usize = 64;
+
+super::type_name!("xyz.radicle.board");
+
+/// Identifier of a board.
+pub type BoardId = ObjectId;
+
+/// Identifier of a column, unique within its board, eg. `in-progress`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ColumnId(String);
+
+impl ColumnId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for ColumnId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::try_from(s.to_owned())
+    }
+}
+
+impl TryFrom<String> for ColumnId {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        if s.is_empty() || s.len() > MAX_COLUMN_ID_LEN || s.chars().any(char::is_whitespace) {
+            return Err(Error::InvalidColumnId(s));
+        }
+        Ok(Self(s))
+    }
+}
+
+impl From<ColumnId> for String {
+    fn from(id: ColumnId) -> Self {
+        id.0
+    }
+}
+
+/// Error applying an operation to a board.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("op decoding failed: {0}")]
+    Op(#[from] OpEncodingError),
+    #[error("initialization failed: {0}")]
+    Init(&'static str),
+    #[error("invalid name: {0:?}")]
+    InvalidName(String),
+    #[error("invalid column id: {0:?}")]
+    InvalidColumnId(String),
+    #[error("column `{0}` already exists")]
+    ColumnExists(String),
+    #[error("column `{0}` not found")]
+    ColumnNotFound(String),
+    #[error("card `{0}` not found")]
+    CardNotFound(git::Oid),
+}
+
+/// Board action.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Action {
+    /// Set the board's name and description.
+    #[serde(rename = "edit")]
+    Edit { name: String, description: String },
+    /// Add a column, at the end unless a position is given.
+    #[serde(rename = "column.add")]
+    ColumnAdd {
+        id: ColumnId,
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        position: Option<usize>,
+    },
+    /// Rename a column.
+    #[serde(rename = "column.edit")]
+    ColumnEdit { id: ColumnId, name: String },
+    /// Move a column to another position.
+    #[serde(rename = "column.move")]
+    ColumnMove { id: ColumnId, position: usize },
+    /// Remove a column, along with its cards.
+    #[serde(rename = "column.remove")]
+    ColumnRemove { id: ColumnId },
+    /// Put a card on a column, at the end unless a position is given.
+    /// A card already on the board is moved.
+    #[serde(rename = "card.move")]
+    CardMove {
+        card: git::Oid,
+        column: ColumnId,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        position: Option<usize>,
+    },
+    /// Take a card off the board.
+    #[serde(rename = "card.remove")]
+    CardRemove { card: git::Oid },
+}
+
+impl CobAction for Action {}
+
+/// A board column.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Column {
+    pub id: ColumnId,
+    pub name: String,
+    pub cards: Vec<git::Oid>,
+}
+
+/// A planning board.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Board {
+    name: String,
+    description: String,
+    columns: Vec<Column>,
+    author: ActorId,
+    timestamp: Timestamp,
+}
+
+impl Board {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    pub fn column(&self, id: &ColumnId) -> Option<&Column> {
+        self.columns.iter().find(|c| c.id == *id)
+    }
+
+    /// Author of the board.
+    pub fn author(&self) -> &ActorId {
+        &self.author
+    }
+
+    /// Time the board was created.
+    pub fn timestamp(&self) -> Timestamp {
+        self.timestamp
+    }
+
+    /// Apply actions to the board. Either all the actions are applied, or none are.
+    pub fn apply(&mut self, actions: impl IntoIterator<Item = Action>) -> Result<(), Error> {
+        let mut board = self.clone();
+        for action in actions {
+            board.action(action)?;
+        }
+        *self = board;
+
+        Ok(())
+    }
+
+    fn action(&mut self, action: Action) -> Result<(), Error> {
+        match action {
+            Action::Edit { name, description } => {
+                self.name = valid_name(name)?;
+                self.description = description;
+            }
+            Action::ColumnAdd { id, name, position } => {
+                if self.column(&id).is_some() {
+                    return Err(Error::ColumnExists(id.0));
+                }
+                let position = position.unwrap_or(self.columns.len());
+                self.columns.insert(
+                    position.min(self.columns.len()),
+                    Column {
+                        id,
+                        name: valid_name(name)?,
+                        cards: Vec::new(),
+                    },
+                );
+            }
+            Action::ColumnEdit { id, name } => {
+                self.column_mut(&id)?.name = valid_name(name)?;
+            }
+            Action::ColumnMove { id, position } => {
+                let ix = self.column_ix(&id)?;
+                let column = self.columns.remove(ix);
+                self.columns
+                    .insert(position.min(self.columns.len()), column);
+            }
+            Action::ColumnRemove { id } => {
+                let ix = self.column_ix(&id)?;
+                self.columns.remove(ix);
+            }
+            Action::CardMove {
+                card,
+                column,
+                position,
+            } => {
+                // Check the column before taking the card off the board.
+                self.column_ix(&column)?;
+                self.remove_card(&card);
+
+                let cards = &mut self.column_mut(&column)?.cards;
+                let position = position.unwrap_or(cards.len());
+                cards.insert(position.min(cards.len()), card);
+            }
+            Action::CardRemove { card } => {
+                if !self.remove_card(&card) {
+                    return Err(Error::CardNotFound(card));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn column_ix(&self, id: &ColumnId) -> Result<usize, Error> {
+        self.columns
+            .iter()
+            .position(|c| c.id == *id)
+            .ok_or_else(|| Error::ColumnNotFound(id.0.clone()))
+    }
+
+    fn column_mut(&mut self, id: &ColumnId) -> Result<&mut Column, Error> {
+        let ix = self.column_ix(id)?;
+        Ok(&mut self.columns[ix])
+    }
+
+    /// Take a card off the board, returning whether it was on it.
+    fn remove_card(&mut self, card: &git::Oid) -> bool {
+        self.columns.iter_mut().any(|column| {
+            let len = column.cards.len();
+            column.cards.retain(|c| c != card);
+            column.cards.len() != len
+        })
+    }
+}
+
+/// Check that a board with the given name and columns can be created.
+pub fn check(name: &str, columns: &[(ColumnId, String)]) -> Result<(), Error> {
+    valid_name(name.to_owned())?;
+    for (i, (id, name)) in columns.iter().enumerate() {
+        if columns[..i].iter().any(|(other, _)| other == id) {
+            return Err(Error::ColumnExists(id.0.clone()));
+        }
+        valid_name(name.clone())?;
+    }
+    Ok(())
+}
+
+fn valid_name(name: String) -> Result<String, Error> {
+    let trimmed = name.trim();
+    if trimmed.is_empty() || trimmed.len() > MAX_NAME_LEN {
+        return Err(Error::InvalidName(name));
+    }
+    Ok(trimmed.to_owned())
+}
+
+impl store::Cob for Board {
+    type Action = Action;
+    type Error = Error;
+
+    fn type_name() -> &'static TypeName {
+        type_name()
+    }
+
+    fn from_root<R: ReadRepository>(op: Op<Action>, _repo: &R) -> Result<Self, Self::Error> {
+        let mut actions = op.actions.into_iter();
+        let Some(Action::Edit { name, description }) = actions.next() else {
+            return Err(Error::Init("the first action must be of type `edit`"));
+        };
+        let mut board = Board {
+            name: valid_name(name)?,
+            description,
+            columns: Vec::new(),
+            author: op.author,
+            timestamp: op.timestamp,
+        };
+        board.apply(actions)?;
+
+        Ok(board)
+    }
+
+    fn op<'a, R: ReadRepository, I: IntoIterator<Item = &'a cob::Entry>>(
+        &mut self,
+        op: Op<Action>,
+        _concurrent: I,
+        _repo: &R,
+    ) -> Result<(), Error> {
+        self.apply(op.actions)
+    }
+}
+
+super::evaluate!(Board);
+
+/// Boards of a repository.
+pub struct Boards<'a, R> {
+    raw: store::Store<'a, Board, R>,
+    repository: &'a R,
+}
+
+impl<'a, R> Boards<'a, R>
+where
+    R: ReadRepository + cob::Store,
+{
+    /// Open the boards of a repository.
+    pub fn open(repository: &'a R) -> Result<Self, RepositoryError> {
+        let raw = super::open_store(repository)?;
+
+        Ok(Self { raw, repository })
+    }
+
+    /// Get a board.
+    pub fn get(&self, id: &BoardId) -> Result<Option<Board>, store::Error> {
+        self.raw.get(id)
+    }
+
+    /// All boards, in no particular order.
+    pub fn all(
+        &self,
+    ) -> Result<impl Iterator<Item = Result<(BoardId, Board), store::Error>> + 'a, store::Error>
+    {
+        self.raw.all()
+    }
+}
+
+impl<'a, R> Boards<'a, R>
+where
+    R: ReadRepository + SignRepository + cob::Store,
+{
+    /// Create a board, with the given columns.
+    pub fn create<G: Signer>(
+        &self,
+        name: String,
+        description: String,
+        columns: impl IntoIterator<Item = (ColumnId, String)>,
+        signer: &G,
+    ) -> Result<(BoardId, Board), store::Error> {
+        let mut actions = NonEmpty::new(Action::Edit { name, description });
+        actions.extend(columns.into_iter().map(|(id, name)| Action::ColumnAdd {
+            id,
+            name,
+            position: None,
+        }));
+
+        self.raw.create("Create board", actions, vec![], signer)
+    }
+
+    /// Apply actions to a board, returning the id of the new entry.
+    pub fn update<G: Signer>(
+        &self,
+        id: BoardId,
+        message: &str,
+        actions: NonEmpty<Action>,
+        signer: &G,
+    ) -> Result<EntryId, store::Error> {
+        let updated = self.raw.update(id, message, actions, vec![], signer)?;
+
+        Ok(updated.head)
+    }
+
+    /// Remove the signer's copy of a board, returning whether the signer had one.
+    ///
+    /// Like other collaborative objects, a board is stored in the namespace of every peer that
+    /// created or changed it. Only the copy in the signer's namespace is removed: the board
+    /// remains as long as other peers, eg. its author, hold a copy.
+    pub fn remove<G: Signer>(&self, id: &BoardId, signer: &G) -> Result<bool, store::Error> {
+        let name = git::refs::storage::cob(signer.public_key(), type_name(), id);
+        match self
+            .repository
+            .reference_oid(signer.public_key(), &name.strip_namespace())
+        {
+            Ok(_) => {}
+            Err(err) if err.code() == git::raw::ErrorCode::NotFound => return Ok(false),
+            Err(err) => {
+                return Err(store::Error::RefLookup {
+                    name: name.to_ref_string(),
+                    err,
+                })
+            }
+        }
+        self.raw.remove(id, signer)?;
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn board() -> Board {
+        Board {
+            name: "Roadmap".to_owned(),
+            description: String::new(),
+            columns: Vec::new(),
+            author: ActorId::from_str("z6MknSLrJoTcukLrE435hVNQT4JUhbvWLX4kUzqkEStBU8Vi").unwrap(),
+            timestamp: Timestamp::from_secs(0),
+        }
+    }
+
+    fn column(id: &str) -> ColumnId {
+        ColumnId::from_str(id).unwrap()
+    }
+
+    fn card(n: u8) -> git::Oid {
+        git::Oid::from_str(&format!("{n:040x}")).unwrap()
+    }
+
+    fn cards(board: &Board) -> Vec<(&str, Vec<git::Oid>)> {
+        board
+            .columns()
+            .iter()
+            .map(|c| (c.id.as_str(), c.cards.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_cards() {
+        let mut board = board();
+        board
+            .apply([
+                Action::ColumnAdd {
+                    id: column("todo"),
+                    name: "To do".to_owned(),
+                    position: None,
+                },
+                Action::ColumnAdd {
+                    id: column("done"),
+                    name: "Done".to_owned(),
+                    position: None,
+                },
+                Action::CardMove {
+                    card: card(1),
+                    column: column("todo"),
+                    position: None,
+                },
+                Action::CardMove {
+                    card: card(2),
+                    column: column("todo"),
+                    position: Some(0),
+                },
+                Action::CardMove {
+                    card: card(1),
+                    column: column("done"),
+                    position: None,
+                },
+            ])
+            .unwrap();
+
+        assert_eq!(
+            cards(&board),
+            [("todo", vec![card(2)]), ("done", vec![card(1)])]
+        );
+
+        board
+            .apply([
+                Action::ColumnMove {
+                    id: column("done"),
+                    position: 0,
+                },
+                Action::CardRemove { card: card(2) },
+            ])
+            .unwrap();
+        assert_eq!(cards(&board), [("done", vec![card(1)]), ("todo", vec![])]);
+    }
+
+    #[test]
+    fn test_apply_is_atomic() {
+        let mut board = board();
+        let err = board
+            .apply([
+                Action::ColumnAdd {
+                    id: column("todo"),
+                    name: "To do".to_owned(),
+                    position: None,
+                },
+                Action::CardMove {
+                    card: card(1),
+                    column: column("missing"),
+                    position: None,
+                },
+            ])
+            .unwrap_err();
+
+        assert!(matches!(err, Error::ColumnNotFound(_)));
+        assert!(board.columns().is_empty());
+        assert!(board
+            .apply([Action::ColumnRemove { id: column("todo") }])
+            .is_err());
+        assert!(ColumnId::from_str("in progress").is_err());
+    }
+}
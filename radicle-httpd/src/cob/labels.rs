@@ -0,0 +1,274 @@
+//! Label metadata, stored as a collaborative object.
+//!
+//! Issues and patches are labelled with plain names. A repository can further describe its
+//! labels, with a color and a description, in a label registry. There is one registry per
+//! repository: should there be more than one, eg. because two peers created one at the same
+//! time, the oldest is used.
+use std::collections::BTreeMap;
+
+use nonempty::NonEmpty;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use radicle::cob;
+use radicle::cob::op::{Op, OpEncodingError};
+use radicle::cob::store::{self, CobAction};
+use radicle::cob::{Color, EntryId, Label, ObjectId, Timestamp, TypeName};
+use radicle::crypto::Signer;
+use radicle::prelude::ReadRepository;
+use radicle::storage::{RepositoryError, SignRepository};
+
+/// Longest label description accepted.
+const MAX_DESCRIPTION_LEN: usize = 256;
+
+super::type_name!("xyz.radicle.labels");
+
+/// Identifier of a label registry.
+pub type RegistryId = ObjectId;
+
+/// Error applying an operation to a label registry.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("op decoding failed: {0}")]
+    Op(#[from] OpEncodingError),
+    #[error("invalid description for label `{0}`")]
+    InvalidDescription(Label),
+    #[error("label `{0}` not found")]
+    LabelNotFound(Label),
+}
+
+/// Label registry action.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Action {
+    /// Describe a label, replacing its previous description.
+    #[serde(rename = "label.edit")]
+    LabelEdit {
+        label: Label,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        color: Option<Color>,
+        #[serde(default)]
+        description: String,
+    },
+    /// Remove the description of a label.
+    #[serde(rename = "label.remove")]
+    LabelRemove { label: Label },
+}
+
+impl CobAction for Action {}
+
+/// Description of a label.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LabelMeta {
+    pub color: Option<Color>,
+    pub description: String,
+}
+
+/// Descriptions of the labels of a repository.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Registry {
+    labels: BTreeMap<Label, LabelMeta>,
+    timestamp: Timestamp,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self {
+            labels: BTreeMap::new(),
+            timestamp: Timestamp::from_secs(0),
+        }
+    }
+}
+
+impl Registry {
+    /// Described labels, by name.
+    pub fn labels(&self) -> &BTreeMap<Label, LabelMeta> {
+        &self.labels
+    }
+
+    pub fn get(&self, label: &Label) -> Option<&LabelMeta> {
+        self.labels.get(label)
+    }
+
+    /// Apply actions to the registry. Either all the actions are applied, or none are.
+    pub fn apply(&mut self, actions: impl IntoIterator<Item = Action>) -> Result<(), Error> {
+        let mut registry = self.clone();
+        for action in actions {
+            registry.action(action)?;
+        }
+        *self = registry;
+
+        Ok(())
+    }
+
+    fn action(&mut self, action: Action) -> Result<(), Error> {
+        match action {
+            Action::LabelEdit {
+                label,
+                color,
+                description,
+            } => {
+                let description = description.trim();
+                if description.len() > MAX_DESCRIPTION_LEN || description.contains('\n') {
+                    return Err(Error::InvalidDescription(label));
+                }
+                self.labels.insert(
+                    label,
+                    LabelMeta {
+                        color,
+                        description: description.to_owned(),
+                    },
+                );
+            }
+            Action::LabelRemove { label } => {
+                if self.labels.remove(&label).is_none() {
+                    return Err(Error::LabelNotFound(label));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl store::Cob for Registry {
+    type Action = Action;
+    type Error = Error;
+
+    fn type_name() -> &'static TypeName {
+        type_name()
+    }
+
+    fn from_root<R: ReadRepository>(op: Op<Action>, _repo: &R) -> Result<Self, Self::Error> {
+        let mut registry = Registry {
+            labels: BTreeMap::new(),
+            timestamp: op.timestamp,
+        };
+        registry.apply(op.actions)?;
+
+        Ok(registry)
+    }
+
+    fn op<'a, R: ReadRepository, I: IntoIterator<Item = &'a cob::Entry>>(
+        &mut self,
+        op: Op<Action>,
+        _concurrent: I,
+        _repo: &R,
+    ) -> Result<(), Error> {
+        self.apply(op.actions)
+    }
+}
+
+super::evaluate!(Registry);
+
+/// Label registries of a repository.
+pub struct Registries<'a, R> {
+    raw: store::Store<'a, Registry, R>,
+}
+
+impl<'a, R> Registries<'a, R>
+where
+    R: ReadRepository + cob::Store,
+{
+    /// Open the label registries of a repository.
+    pub fn open(repository: &'a R) -> Result<Self, RepositoryError> {
+        let raw = super::open_store(repository)?;
+
+        Ok(Self { raw })
+    }
+
+    /// The label registry of the repository, if any.
+    pub fn registry(&self) -> Result<Option<(RegistryId, Registry)>, store::Error> {
+        let mut oldest: Option<(RegistryId, Registry)> = None;
+        for result in self.raw.all()? {
+            let (id, registry) = result?;
+            let older = oldest.as_ref().map_or(true, |(other_id, other)| {
+                (registry.timestamp, id) < (other.timestamp, *other_id)
+            });
+            if older {
+                oldest = Some((id, registry));
+            }
+        }
+        Ok(oldest)
+    }
+}
+
+impl<'a, R> Registries<'a, R>
+where
+    R: ReadRepository + SignRepository + cob::Store,
+{
+    /// Apply actions to the label registry of the repository, creating it if there is none.
+    /// Returns the id of the new entry.
+    pub fn update<G: Signer>(
+        &self,
+        message: &str,
+        actions: NonEmpty<Action>,
+        signer: &G,
+    ) -> Result<EntryId, store::Error> {
+        match self.registry()? {
+            Some((id, _)) => {
+                let updated = self.raw.update(id, message, actions, vec![], signer)?;
+
+                Ok(updated.head)
+            }
+            None => {
+                let (id, _) = self.raw.create(message, actions, vec![], signer)?;
+
+                // The root entry of an object is its identifier.
+                Ok(*id)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn label(name: &str) -> Label {
+        Label::new(name).unwrap()
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut registry = Registry::default();
+        registry
+            .apply([
+                Action::LabelEdit {
+                    label: label("bug"),
+                    color: Some(Color::from_str("#FF0000").unwrap()),
+                    description: " Something isn't working ".to_owned(),
+                },
+                Action::LabelEdit {
+                    label: label("docs"),
+                    color: None,
+                    description: String::new(),
+                },
+            ])
+            .unwrap();
+
+        assert_eq!(
+            registry.get(&label("bug")),
+            Some(&LabelMeta {
+                color: Some(Color::from_str("#ff0000").unwrap()),
+                description: "Something isn't working".to_owned(),
+            })
+        );
+
+        // Removing an unknown label leaves the registry as it was.
+        let err = registry
+            .apply([
+                Action::LabelRemove {
+                    label: label("docs"),
+                },
+                Action::LabelRemove {
+                    label: label("missing"),
+                },
+            ])
+            .unwrap_err();
+        assert!(matches!(err, Error::LabelNotFound(_)));
+        assert_eq!(registry.labels().len(), 2);
+    }
+}
@@ -1,2 +1,3 @@
 //! Extra CLI commands relating to HTTPd.
+pub mod token;
 pub mod web;
@@ -0,0 +1,171 @@
+use std::collections::BTreeSet;
+use std::ffi::OsString;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use anyhow::anyhow;
+use serde::Deserialize;
+use serde_json::json;
+
+use radicle::identity::RepoId;
+
+use radicle_cli::terminal as term;
+use radicle_cli::terminal::args::{Args, Error, Help};
+
+use super::web::{sign, SessionInfo};
+use crate::api::auth::{Capability, Scope};
+
+pub const HELP: Help = Help {
+    name: "token",
+    description: "Create a long-lived access token for the HTTP daemon",
+    version: env!("RADICLE_VERSION"),
+    usage: r#"
+Usage
+
+    rad web token --name <name> [<option>...]
+
+    Signs in to a running Radicle HTTP Daemon with the local identity and prints
+    a new access token, eg. for use by CI or bots. The token is only shown once.
+
+Options
+
+    --name, -n <name>         What the token is used for
+    --connect, -c <addr>      Address of the HTTP daemon (default: 127.0.0.1:8080)
+    --expires-in <days>       Days until the token expires (default: 90)
+    --capability <cap>        Capability to grant; may be repeated (default: all)
+                              One of `issues:write`, `patches:write`, `patches:merge`
+                              or `policies:write`
+    --repo <rid>              Repository to restrict the token to; may be repeated
+    --help                    Print help
+"#,
+};
+
+#[derive(Debug)]
+pub struct Options {
+    pub name: String,
+    pub connect: SocketAddr,
+    pub expires_in: Option<i64>,
+    pub capabilities: BTreeSet<Capability>,
+    pub repos: BTreeSet<RepoId>,
+}
+
+impl Args for Options {
+    fn from_args(args: Vec<OsString>) -> anyhow::Result<(Self, Vec<OsString>)> {
+        use lexopt::prelude::*;
+
+        let mut parser = lexopt::Parser::from_args(args);
+        let mut name = None;
+        let mut connect = None;
+        let mut expires_in = None;
+        let mut capabilities = BTreeSet::new();
+        let mut repos = BTreeSet::new();
+
+        while let Some(arg) = parser.next()? {
+            match arg {
+                Long("name") | Short('n') if name.is_none() => {
+                    let val = parser.value()?;
+                    name = Some(term::args::string(&val));
+                }
+                Long("connect") | Short('c') if connect.is_none() => {
+                    let val = parser.value()?;
+                    connect = Some(term::args::socket_addr(&val)?);
+                }
+                Long("expires-in") if expires_in.is_none() => {
+                    let val = parser.value()?;
+                    let days = term::args::number(&val)?;
+                    let secs = i64::try_from(days)
+                        .ok()
+                        .and_then(|days| days.checked_mul(24 * 60 * 60))
+                        .ok_or_else(|| anyhow!("`--expires-in` is out of range: {days} days"))?;
+                    expires_in = Some(secs);
+                }
+                Long("capability") => {
+                    let val = parser.value()?;
+                    let cap = term::args::string(&val)
+                        .parse()
+                        .map_err(|e: String| anyhow!(e))?;
+                    capabilities.insert(cap);
+                }
+                Long("repo") => {
+                    let val = parser.value()?;
+                    repos.insert(term::args::rid(&val)?);
+                }
+                Long("help") | Short('h') => {
+                    return Err(Error::Help.into());
+                }
+                _ => {
+                    return Err(anyhow!(arg.unexpected()));
+                }
+            }
+        }
+
+        Ok((
+            Options {
+                name: name.ok_or_else(|| anyhow!("a token name must be given with `--name`"))?,
+                connect: connect.unwrap_or(SocketAddr::new(
+                    IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                    8080,
+                )),
+                expires_in,
+                capabilities,
+                repos,
+            },
+            vec![],
+        ))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TokenInfo {
+    id: String,
+    token: String,
+    expires_at: i64,
+}
+
+pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
+    let profile = ctx.profile()?;
+    let api = format!("http://{}/api/v1", options.connect);
+
+    let session = ureq::post(&format!("{api}/sessions"))
+        .call()?
+        .into_json::<SessionInfo>()?;
+    let signature = sign(profile.signer()?, &session)?;
+    ureq::put(&format!("{api}/sessions/{}", session.session_id)).send_json(json!({
+        "sig": signature,
+        "pk": session.public_key,
+    }))?;
+
+    let bearer = format!("Bearer {}", session.session_id);
+    let scope = (!options.capabilities.is_empty() || !options.repos.is_empty()).then(|| Scope {
+        capabilities: if options.capabilities.is_empty() {
+            Capability::ALL.into_iter().collect()
+        } else {
+            options.capabilities
+        },
+        repos: (!options.repos.is_empty()).then_some(options.repos),
+    });
+    let result = ureq::post(&format!("{api}/tokens"))
+        .set("Authorization", &bearer)
+        .send_json(json!({
+            "name": options.name,
+            "expiresIn": options.expires_in,
+            "scope": scope,
+        }));
+    // The session was only needed to create the token.
+    ureq::delete(&format!("{api}/sessions/{}", session.session_id))
+        .set("Authorization", &bearer)
+        .call()
+        .ok();
+
+    let token = result?.into_json::<TokenInfo>()?;
+    let expires_at = time::OffsetDateTime::from_unix_timestamp(token.expires_at)?;
+
+    term::success!(
+        "Created access token {} expiring at {}",
+        term::format::highlight(&token.id),
+        term::format::dim(expires_at.date())
+    );
+    term::print(token.token);
+
+    Ok(())
+}
@@ -21,8 +21,10 @@ pub const HELP: Help = Help {
 Usage
 
     rad web [<option>...] [<explorer-url>]
+    rad web token --name <name> [<option>...]
 
     Runs the Radicle HTTP Daemon and opens a Radicle web explorer to authenticate with it.
+    The `token` subcommand creates a long-lived access token instead, see `rad web token --help`.
 
 Options
 
@@ -131,8 +133,18 @@ pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
             .expect("failed to create threaded runtime");
         let httpd_handle = runtime.spawn(crate::run(crate::Options {
             aliases: Default::default(),
-            listen: options.listen,
+            listen: crate::Listen::Tcp(options.listen),
+            socket_mode: None,
             cache: None,
+            tls: None,
+            sessions: Default::default(),
+            rate_limits: Default::default(),
+            cors: Default::default(),
+            body_limit: None,
+            features: Default::default(),
+            metrics_listen: None,
+            shutdown_timeout: crate::DEFAULT_SHUTDOWN_TIMEOUT,
+            reload: None,
         }));
         Some((runtime, httpd_handle))
     } else {
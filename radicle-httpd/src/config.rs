@@ -0,0 +1,519 @@
+//! Configuration file.
+//!
+//! The file is read as JSON if its name ends in `.json`, and as TOML otherwise, eg.
+//!
+//! ```toml
+//! listen = "0.0.0.0:8080" # Or eg. "unix:/run/radicle-httpd.sock"
+//! cache = 100
+//! shutdownTimeout = 30
+//!
+//! [aliases]
+//! heartwood = "rad:z3gqcJUoA1n9HaHKufZs5FCSGazv5"
+//!
+//! [cors]
+//! origins = ["https://app.radicle.xyz", "https://*.radicle.garden"]
+//! apiMethods = ["GET", "POST", "PATCH", "PUT"]
+//! credentials = true
+//!
+//! [plugins.radiclePlanningBoards]
+//! enabled = true
+//! origin = "https://boards.example.com"
+//!
+//! [limits]
+//! body = 2097152
+//! rate = { api = "10/s", git = "600/m:50" }
+//!
+//! [auth]
+//! sessions = "sqlite"
+//!
+//! [features]
+//! raw = false
+//!
+//! [metrics]
+//! listen = "127.0.0.1:9100"
+//! ```
+//!
+//! Command line options take precedence over the file.
+use std::collections::{BTreeMap, HashMap};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{fs, io};
+
+use axum::http::Method;
+use serde::Deserialize;
+
+use radicle::identity::RepoId;
+
+use crate::{CorsOptions, Features, Listen, Options, Origin, RateLimits, TlsOptions};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// I/O error.
+    #[error("i/o error: {0}")]
+    Io(#[from] io::Error),
+    /// TOML decoding error.
+    #[error("toml: {0}")]
+    Toml(#[from] toml::de::Error),
+    /// JSON decoding error.
+    #[error("json: {0}")]
+    Json(#[from] serde_json::Error),
+    /// The configuration doesn't make sense.
+    #[error("{0}")]
+    Invalid(String),
+}
+
+/// HTTPd configuration, as found in a configuration file or given on the command line.
+/// Everything is optional, so that configurations can be layered.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Config {
+    /// Address to listen on, or unix socket, eg. `unix:/run/radicle-httpd.sock`.
+    pub listen: Option<Listen>,
+    /// Permissions of the unix socket, in octal, eg. `"660"`.
+    pub socket_mode: Option<String>,
+    /// Short names for repositories, used in git clone URLs.
+    #[serde(default)]
+    pub aliases: HashMap<String, RepoId>,
+    /// Max amount of items in the cache for `/tree` endpoints. Zero disables the cache.
+    pub cache: Option<usize>,
+    /// Seconds to wait for requests in flight to finish on shutdown.
+    pub shutdown_timeout: Option<u64>,
+    pub tls: Option<Tls>,
+    #[serde(default)]
+    pub cors: Cors,
+    #[serde(default)]
+    pub limits: Limits,
+    #[serde(default)]
+    pub auth: Auth,
+    #[serde(default)]
+    pub features: FeatureToggles,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    #[serde(default)]
+    pub plugins: Plugins,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Tls {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    #[serde(default)]
+    pub http2: bool,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Cors {
+    /// Origins allowed to make cross-origin requests, either exact, eg. `https://app.radicle.xyz`
+    /// or for all subdomains, eg. `https://*.radicle.xyz`. Any origin is, if not set.
+    pub origins: Option<Vec<String>>,
+    /// Methods allowed on the `/api` routes.
+    pub api_methods: Option<Vec<String>>,
+    /// Methods allowed on the `/raw` routes.
+    pub raw_methods: Option<Vec<String>>,
+    /// Allow requests with credentials. Requires `origins` to be set.
+    pub credentials: Option<bool>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Limits {
+    /// Max size of request bodies, in bytes.
+    pub body: Option<usize>,
+    /// Request quotas by router, see [`crate::Quota`].
+    #[serde(default)]
+    pub rate: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Auth {
+    /// Where to keep sessions, `sqlite` or `memory`.
+    pub sessions: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct FeatureToggles {
+    /// Serve repositories over git's smart HTTP protocol.
+    pub git: Option<bool>,
+    /// Serve raw files under `/raw`.
+    pub raw: Option<bool>,
+    /// Serve Prometheus metrics. Off by default, unless `metrics.listen` is set, since metrics
+    /// served under `/metrics` on the main address are public.
+    pub metrics: Option<bool>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// Serve metrics on this separate address only, eg. `127.0.0.1:9100`.
+    pub listen: Option<Listen>,
+}
+
+/// Web explorer plugins that need access to the API.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Plugins {
+    pub radicle_planning_boards: Option<Plugin>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Plugin {
+    #[serde(default = "Plugin::enabled")]
+    pub enabled: bool,
+    /// Origin the plugin is served from. It is allowed to make cross-origin requests.
+    pub origin: String,
+}
+
+impl Plugin {
+    fn enabled() -> bool {
+        true
+    }
+}
+
+impl Config {
+    /// Read a configuration file.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let contents = fs::read_to_string(path)?;
+
+        if path.extension().is_some_and(|ext| ext == "json") {
+            Ok(serde_json::from_str(&contents)?)
+        } else {
+            Ok(toml::from_str(&contents)?)
+        }
+    }
+
+    /// Layer `other` on top of `self`. Settings of `other` win.
+    pub fn merge(mut self, other: Config) -> Config {
+        self.listen = other.listen.or(self.listen);
+        self.socket_mode = other.socket_mode.or(self.socket_mode);
+        self.aliases.extend(other.aliases);
+        self.cache = other.cache.or(self.cache);
+        self.shutdown_timeout = other.shutdown_timeout.or(self.shutdown_timeout);
+        self.tls = other.tls.or(self.tls);
+        self.cors.origins = other.cors.origins.or(self.cors.origins);
+        self.cors.api_methods = other.cors.api_methods.or(self.cors.api_methods);
+        self.cors.raw_methods = other.cors.raw_methods.or(self.cors.raw_methods);
+        self.cors.credentials = other.cors.credentials.or(self.cors.credentials);
+        self.limits.body = other.limits.body.or(self.limits.body);
+        self.limits.rate.extend(other.limits.rate);
+        self.auth.sessions = other.auth.sessions.or(self.auth.sessions);
+        self.features.git = other.features.git.or(self.features.git);
+        self.features.raw = other.features.raw.or(self.features.raw);
+        self.features.metrics = other.features.metrics.or(self.features.metrics);
+        self.metrics.listen = other.metrics.listen.or(self.metrics.listen);
+        self.plugins.radicle_planning_boards = other
+            .plugins
+            .radicle_planning_boards
+            .or(self.plugins.radicle_planning_boards);
+        self
+    }
+
+    /// Turn the configuration into server options, filling in defaults.
+    pub fn options(&self) -> Result<Options, Error> {
+        let mut rate_limits = RateLimits::default();
+        for (router, quota) in &self.limits.rate {
+            rate_limits
+                .set(&format!("{router}={quota}"))
+                .map_err(Error::Invalid)?;
+        }
+        let cors = self.cors()?;
+        let sessions = match &self.auth.sessions {
+            Some(sessions) => sessions.parse().map_err(Error::Invalid)?,
+            None => Default::default(),
+        };
+        let listen = self.listen.clone().unwrap_or_default();
+        let socket_mode = match &self.socket_mode {
+            Some(mode) => Some(
+                u32::from_str_radix(mode.trim_start_matches("0o"), 8)
+                    .ok()
+                    .filter(|mode| *mode <= 0o777)
+                    .ok_or_else(|| Error::Invalid(format!("invalid socket mode '{mode}'")))?,
+            ),
+            None => None,
+        };
+        if let Listen::Tcp(_) = listen {
+            if socket_mode.is_some() {
+                return Err(Error::Invalid(
+                    "the socket mode only applies to unix sockets".to_owned(),
+                ));
+            }
+        } else if self.tls.is_some() {
+            return Err(Error::Invalid(
+                "TLS is not supported on unix sockets".to_owned(),
+            ));
+        }
+
+        Ok(Options {
+            aliases: self.aliases.clone(),
+            listen,
+            socket_mode,
+            cache: match self.cache {
+                Some(size) => NonZeroUsize::new(size),
+                None => Some(crate::DEFAULT_CACHE_SIZE),
+            },
+            tls: self.tls.as_ref().map(|tls| TlsOptions {
+                cert: tls.cert.clone(),
+                key: tls.key.clone(),
+                http2: tls.http2,
+            }),
+            sessions,
+            rate_limits,
+            cors,
+            body_limit: self.limits.body,
+            features: Features {
+                git: self.features.git.unwrap_or(true),
+                raw: self.features.raw.unwrap_or(true),
+                metrics: self
+                    .features
+                    .metrics
+                    .unwrap_or(self.metrics.listen.is_some()),
+            },
+            metrics_listen: self.metrics.listen.clone(),
+            shutdown_timeout: self
+                .shutdown_timeout
+                .map_or(crate::DEFAULT_SHUTDOWN_TIMEOUT, Duration::from_secs),
+            reload: None,
+        })
+    }
+}
+
+impl Config {
+    fn cors(&self) -> Result<CorsOptions, Error> {
+        let defaults = CorsOptions::default();
+        let methods = |methods: &Option<Vec<String>>, default: Vec<Method>| match methods {
+            Some(methods) => methods
+                .iter()
+                .map(|m| {
+                    Method::from_bytes(m.to_ascii_uppercase().as_bytes())
+                        .map_err(|_| Error::Invalid(format!("invalid CORS method '{m}'")))
+                })
+                .collect::<Result<Vec<_>, _>>(),
+            None => Ok(default),
+        };
+        let mut origins = self
+            .cors
+            .origins
+            .iter()
+            .flatten()
+            .map(|origin| origin.parse::<Origin>().map_err(Error::Invalid))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // If only some origins are allowed, the planning boards have to be one of them.
+        if let Some(boards) = &self.plugins.radicle_planning_boards {
+            if boards.enabled && !origins.is_empty() {
+                origins.push(boards.origin.parse().map_err(Error::Invalid)?);
+            }
+        }
+        let credentials = self.cors.credentials.unwrap_or(defaults.credentials);
+        if credentials && origins.is_empty() {
+            return Err(Error::Invalid(
+                "CORS credentials require the allowed origins to be set".to_owned(),
+            ));
+        }
+
+        Ok(CorsOptions {
+            origins,
+            api_methods: methods(&self.cors.api_methods, defaults.api_methods)?,
+            raw_methods: methods(&self.cors.raw_methods, defaults.raw_methods)?,
+            credentials,
+        })
+    }
+}
+
+/// Where to reload the configuration from.
+#[derive(Debug, Clone)]
+pub struct Reload {
+    /// The configuration file.
+    pub path: PathBuf,
+    /// Command line options, applied on top of the file.
+    pub overrides: Config,
+    /// Offer HTTP/2, whether TLS is configured in the file or on the command line.
+    pub http2: bool,
+}
+
+impl Reload {
+    /// Re-read the configuration file and get the resulting options.
+    pub fn options(&self) -> Result<Options, Error> {
+        let mut options = Config::load(&self.path)?
+            .merge(self.overrides.clone())
+            .options()?;
+        if self.http2 {
+            match options.tls.as_mut() {
+                Some(tls) => tls.http2 = true,
+                None => {
+                    return Err(Error::Invalid(
+                        "`--http2` requires TLS to be configured".to_owned(),
+                    ))
+                }
+            }
+        }
+        options.reload = Some(self.clone());
+
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+    use crate::test::RID;
+
+    #[test]
+    fn test_config_toml() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("httpd.toml");
+        fs::write(
+            &path,
+            format!(
+                r#"
+                listen = "127.0.0.1:9090"
+                cache = 0
+                shutdownTimeout = 5
+
+                [aliases]
+                hello = "{RID}"
+
+                [cors]
+                origins = ["https://app.radicle.xyz"]
+                apiMethods = ["get", "POST"]
+
+                [plugins.radiclePlanningBoards]
+                origin = "https://boards.example.com"
+
+                [limits]
+                body = 1024
+                rate = {{ api = "10/s" }}
+
+                [auth]
+                sessions = "memory"
+
+                [features]
+                raw = false
+                "#
+            ),
+        )
+        .unwrap();
+        let options = Config::load(&path).unwrap().options().unwrap();
+
+        assert_eq!(options.listen, Listen::Tcp(([127, 0, 0, 1], 9090).into()));
+        assert_eq!(options.cache, None);
+        assert_eq!(options.shutdown_timeout, Duration::from_secs(5));
+        assert_eq!(options.aliases["hello"], RID.parse().unwrap());
+        assert_eq!(
+            options.cors.origins,
+            vec![
+                Origin::Exact("https://app.radicle.xyz".to_owned()),
+                Origin::Exact("https://boards.example.com".to_owned())
+            ]
+        );
+        assert_eq!(options.cors.api_methods, vec![Method::GET, Method::POST]);
+        assert_eq!(options.body_limit, Some(1024));
+        assert_eq!(options.rate_limits.api, "10/s".parse().ok());
+        assert_eq!(options.sessions, crate::SessionBackend::Memory);
+        assert_eq!(
+            options.features,
+            Features {
+                git: true,
+                raw: false,
+                metrics: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_config_json_merge() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("httpd.json");
+        fs::write(
+            &path,
+            r#"{ "listen": "127.0.0.1:9090", "cache": 10, "limits": { "rate": { "git": "1/s" } } }"#,
+        )
+        .unwrap();
+        let overrides = Config {
+            cache: Some(20),
+            limits: Limits {
+                rate: BTreeMap::from([("api".to_owned(), "2/s".to_owned())]),
+                ..Limits::default()
+            },
+            ..Config::default()
+        };
+        let reload = Reload {
+            path,
+            overrides,
+            http2: false,
+        };
+        let options = reload.options().unwrap();
+
+        assert_eq!(options.listen, Listen::Tcp(([127, 0, 0, 1], 9090).into()));
+        assert_eq!(options.cache, NonZeroUsize::new(20));
+        assert_eq!(options.rate_limits.git, "1/s".parse().ok());
+        assert_eq!(options.rate_limits.api, "2/s".parse().ok());
+        assert!(options.reload.is_some());
+
+        // `--http2` is kept on reload, and needs TLS.
+        let reload = Reload {
+            http2: true,
+            ..reload
+        };
+        assert!(reload.options().is_err());
+
+        let reload = Reload {
+            overrides: Config {
+                tls: Some(Tls {
+                    cert: "cert.pem".into(),
+                    key: "key.pem".into(),
+                    http2: false,
+                }),
+                ..Config::default()
+            },
+            ..reload
+        };
+        assert!(reload.options().unwrap().tls.unwrap().http2);
+    }
+
+    #[test]
+    fn test_config_invalid() {
+        assert!(toml::from_str::<Config>("unknown = 1").is_err());
+
+        let config = Config {
+            auth: Auth {
+                sessions: Some("redis".to_owned()),
+            },
+            ..Config::default()
+        };
+        assert!(config.options().is_err());
+
+        let config = Config {
+            cors: Cors {
+                credentials: Some(true),
+                ..Cors::default()
+            },
+            ..Config::default()
+        };
+        assert!(config.options().is_err());
+
+        let config = Config {
+            socket_mode: Some("660".to_owned()),
+            ..Config::default()
+        };
+        assert!(config.options().is_err());
+    }
+
+    #[test]
+    fn test_config_unix_socket() {
+        let config: Config =
+            toml::from_str("listen = \"unix:/run/httpd.sock\"\nsocketMode = \"660\"").unwrap();
+        let options = config.options().unwrap();
+
+        assert_eq!(options.listen, Listen::Unix("/run/httpd.sock".into()));
+        assert_eq!(options.socket_mode, Some(0o660));
+        assert!(toml::from_str::<Config>("listen = \"unix:\"").is_err());
+    }
+}
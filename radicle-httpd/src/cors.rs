@@ -0,0 +1,228 @@
+//! Cross-origin resource sharing policy.
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use axum::http::header::{AUTHORIZATION, CONTENT_TYPE, LINK};
+use axum::http::{HeaderName, HeaderValue, Method};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+/// How long browsers may cache the result of a preflight request.
+const MAX_AGE: Duration = Duration::from_secs(86400);
+
+/// An origin allowed to make cross-origin requests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    /// Exactly this origin, eg. `https://app.radicle.xyz`.
+    Exact(String),
+    /// Any subdomain of `domain`, eg. `https://*.radicle.xyz`. The domain itself isn't included.
+    Subdomains { scheme: String, domain: String },
+}
+
+impl Origin {
+    /// Check whether the `Origin` header of a request matches.
+    pub fn matches(&self, origin: &HeaderValue) -> bool {
+        let Ok(origin) = origin.to_str() else {
+            return false;
+        };
+        match self {
+            Self::Exact(exact) => origin == exact,
+            Self::Subdomains { scheme, domain } => origin
+                .strip_prefix(scheme.as_str())
+                .and_then(|rest| rest.strip_prefix("://"))
+                .and_then(|host| host.strip_suffix(domain.as_str()))
+                .and_then(|sub| sub.strip_suffix('.'))
+                .is_some_and(|sub| {
+                    !sub.is_empty()
+                        && sub
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+                }),
+        }
+    }
+}
+
+impl FromStr for Origin {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid CORS origin '{s}'");
+        let (scheme, host) = s.split_once("://").ok_or_else(invalid)?;
+
+        if scheme != "http" && scheme != "https" {
+            return Err(invalid());
+        }
+        if let Some(domain) = host.strip_prefix("*.") {
+            let url = url::Url::parse(&format!("{scheme}://{domain}")).map_err(|_| invalid())?;
+            if url.path() != "/" || url.port().is_some() || domain.contains('*') {
+                return Err(invalid());
+            }
+            return Ok(Self::Subdomains {
+                scheme: scheme.to_owned(),
+                domain: domain.to_ascii_lowercase(),
+            });
+        }
+        let url = url::Url::parse(s).map_err(|_| invalid())?;
+        if url.path() != "/" || url.query().is_some() || host.ends_with('/') {
+            return Err(invalid());
+        }
+        Ok(Self::Exact(url.origin().ascii_serialization()))
+    }
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exact(origin) => f.write_str(origin),
+            Self::Subdomains { scheme, domain } => write!(f, "{scheme}://*.{domain}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorsOptions {
+    /// Origins allowed to make requests. If empty, any origin is.
+    pub origins: Vec<Origin>,
+    /// Methods allowed on the `/api` routes.
+    pub api_methods: Vec<Method>,
+    /// Methods allowed on the `/raw` routes.
+    pub raw_methods: Vec<Method>,
+    /// Allow requests with credentials, eg. cookies. Requires explicit origins.
+    pub credentials: bool,
+}
+
+impl Default for CorsOptions {
+    fn default() -> Self {
+        Self {
+            origins: Vec::new(),
+            api_methods: vec![
+                Method::GET,
+                Method::POST,
+                Method::PATCH,
+                Method::PUT,
+                Method::DELETE,
+            ],
+            raw_methods: vec![Method::GET],
+            credentials: false,
+        }
+    }
+}
+
+impl CorsOptions {
+    /// Build a CORS layer allowing the given methods.
+    pub fn layer(&self, methods: &[Method]) -> CorsLayer {
+        let origins = if self.origins.is_empty() {
+            AllowOrigin::any()
+        } else {
+            let origins = self.origins.clone();
+            AllowOrigin::predicate(move |origin, _| origins.iter().any(|o| o.matches(origin)))
+        };
+
+        let request_id = HeaderName::from_static(crate::tracing_extra::REQUEST_ID_HEADER);
+        let total_count = HeaderName::from_static(crate::api::TOTAL_COUNT_HEADER);
+
+        CorsLayer::new()
+            .max_age(MAX_AGE)
+            .allow_origin(origins)
+            .allow_methods(methods.to_vec())
+            .allow_headers([CONTENT_TYPE, AUTHORIZATION, request_id.clone()])
+            .expose_headers([request_id, total_count, LINK])
+            .allow_credentials(self.credentials)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+    use axum::routing::get;
+    use axum::Router;
+    use tower::ServiceExt as _;
+
+    use super::*;
+
+    #[test]
+    fn test_origin_from_str() {
+        assert_eq!(
+            "https://app.radicle.xyz".parse(),
+            Ok(Origin::Exact("https://app.radicle.xyz".to_owned()))
+        );
+        assert_eq!(
+            "http://localhost:3000".parse(),
+            Ok(Origin::Exact("http://localhost:3000".to_owned()))
+        );
+        assert_eq!(
+            "https://*.radicle.xyz".parse(),
+            Ok(Origin::Subdomains {
+                scheme: "https".to_owned(),
+                domain: "radicle.xyz".to_owned()
+            })
+        );
+        assert!("app.radicle.xyz".parse::<Origin>().is_err());
+        assert!("https://app.radicle.xyz/path".parse::<Origin>().is_err());
+        assert!("https://*.*.radicle.xyz".parse::<Origin>().is_err());
+        assert!("ftp://radicle.xyz".parse::<Origin>().is_err());
+    }
+
+    #[test]
+    fn test_origin_matches() {
+        let exact: Origin = "https://app.radicle.xyz".parse().unwrap();
+        let wildcard: Origin = "https://*.radicle.xyz".parse().unwrap();
+        let origin = HeaderValue::from_static;
+
+        assert!(exact.matches(&origin("https://app.radicle.xyz")));
+        assert!(!exact.matches(&origin("https://app.radicle.xyz.evil.com")));
+        assert!(wildcard.matches(&origin("https://app.radicle.xyz")));
+        assert!(wildcard.matches(&origin("https://a.b.radicle.xyz")));
+        assert!(!wildcard.matches(&origin("https://radicle.xyz")));
+        assert!(!wildcard.matches(&origin("https://evilradicle.xyz")));
+        assert!(!wildcard.matches(&origin("http://app.radicle.xyz")));
+        assert!(!wildcard.matches(&origin("https://app.radicle.xyz:8080")));
+    }
+
+    #[tokio::test]
+    async fn test_cors_layer() {
+        let options = CorsOptions {
+            origins: vec!["https://*.radicle.xyz".parse().unwrap()],
+            credentials: true,
+            ..CorsOptions::default()
+        };
+        let app = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .layer(options.layer(&[Method::GET]));
+        let preflight = |origin: &'static str, method: &'static str| {
+            Request::builder()
+                .method(Method::OPTIONS)
+                .uri("/")
+                .header(header::ORIGIN, origin)
+                .header(header::ACCESS_CONTROL_REQUEST_METHOD, method)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(preflight("https://app.radicle.xyz", "GET"))
+            .await
+            .unwrap();
+        let headers = response.headers();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://app.radicle.xyz"
+        );
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_METHODS], "GET");
+
+        let response = app
+            .oneshot(preflight("https://example.com", "GET"))
+            .await
+            .unwrap();
+
+        assert!(response
+            .headers()
+            .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .is_none());
+    }
+}
@@ -1,9 +1,10 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::io::prelude::*;
 use std::net::SocketAddr;
 use std::path::Path;
-use std::process::{Command, Stdio};
-use std::sync::Arc;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use std::{io, net, str};
 
 use axum::body::Bytes;
@@ -21,15 +22,85 @@ use radicle::profile::Profile;
 use radicle::storage::{ReadRepository, ReadStorage};
 
 use crate::error::GitError as Error;
+use crate::metrics::Metrics;
 
-pub fn router(profile: Arc<Profile>, aliases: HashMap<String, RepoId>) -> Router {
+/// Running `git http-backend` processes, by process id.
+#[derive(Debug, Default, Clone)]
+pub struct Backends {
+    running: Arc<Mutex<BTreeSet<u32>>>,
+    metrics: Metrics,
+}
+
+impl Backends {
+    /// Create a registry that records backend processes in the given metrics.
+    pub fn new(metrics: Metrics) -> Self {
+        Self {
+            running: Arc::default(),
+            metrics,
+        }
+    }
+
+    /// Keep track of a backend process until the returned guard is dropped.
+    fn register(&self, child: &Child) -> Backend {
+        #[allow(clippy::unwrap_used)]
+        self.running.lock().unwrap().insert(child.id());
+        self.metrics.git_backend_spawned();
+
+        Backend {
+            id: child.id(),
+            started: Instant::now(),
+            backends: self.clone(),
+        }
+    }
+
+    /// Ask the backend processes still running to terminate, eg. on shutdown.
+    /// Returns how many there were.
+    pub fn terminate(&self) -> usize {
+        #[allow(clippy::unwrap_used)]
+        let backends = self.running.lock().unwrap();
+
+        for id in backends.iter() {
+            tracing::warn!("git-http-backend: terminating process {id}");
+            #[cfg(unix)]
+            // SAFETY: Sending a signal has no memory safety implications, and the process is
+            // still ours, since it is only unregistered after it was waited for.
+            unsafe {
+                libc::kill(*id as libc::pid_t, libc::SIGTERM);
+            }
+        }
+        backends.len()
+    }
+}
+
+/// A registered backend process.
+struct Backend {
+    id: u32,
+    started: Instant,
+    backends: Backends,
+}
+
+impl Drop for Backend {
+    fn drop(&mut self) {
+        #[allow(clippy::unwrap_used)]
+        self.backends.running.lock().unwrap().remove(&self.id);
+        self.backends
+            .metrics
+            .git_backend_exited(self.started.elapsed());
+    }
+}
+
+pub fn router(
+    profile: Arc<Profile>,
+    aliases: HashMap<String, RepoId>,
+    backends: Backends,
+) -> Router {
     Router::new()
         .route("/:project/*request", any(git_handler))
-        .with_state((profile, aliases))
+        .with_state((profile, aliases, backends))
 }
 
 async fn git_handler(
-    State((profile, aliases)): State<(Arc<Profile>, HashMap<String, RepoId>)>,
+    State((profile, aliases, backends)): State<(Arc<Profile>, HashMap<String, RepoId>, Backends)>,
     AxumPath((project, request)): AxumPath<(String, String)>,
     method: Method,
     headers: HeaderMap,
@@ -50,7 +121,7 @@ async fn git_handler(
     };
 
     let (status, headers, body) = git_http_backend(
-        &profile, method, headers, body, remote, rid, &request, query,
+        &profile, &backends, method, headers, body, remote, rid, &request, query,
     )
     .await?;
 
@@ -65,8 +136,10 @@ async fn git_handler(
     Ok::<_, Error>((status, response_headers, body))
 }
 
+#[tracing::instrument(name = "git.http_backend", skip_all, fields(rid = %id, path = %path))]
 async fn git_http_backend(
     profile: &Profile,
+    backends: &Backends,
     method: Method,
     headers: HeaderMap,
     mut body: Bytes,
@@ -119,6 +192,7 @@ async fn git_http_backend(
         .stdout(Stdio::piped())
         .stdin(Stdio::piped())
         .spawn()?;
+    let _backend = backends.register(&child);
 
     // Whether the request body is compressed.
     let gzip = matches!(
@@ -225,7 +299,7 @@ mod routes {
     async fn test_info_request() {
         let tmp = tempfile::tempdir().unwrap();
         let ctx = test::seed(tmp.path());
-        let app = super::router(ctx.profile().to_owned(), HashMap::new())
+        let app = super::router(ctx.profile().to_owned(), HashMap::new(), Default::default())
             .layer(MockConnectInfo(SocketAddr::from(([0, 0, 0, 0], 8080))));
 
         let response = get(&app, format!("/{RID}.git/info/refs")).await;
@@ -240,6 +314,7 @@ mod routes {
         let app = super::router(
             ctx.profile().to_owned(),
             HashMap::from_iter([(String::from("heartwood"), RepoId::from_str(RID).unwrap())]),
+            Default::default(),
         )
         .layer(MockConnectInfo(SocketAddr::from(([0, 0, 0, 0], 8080))));
 
@@ -0,0 +1,186 @@
+//! Health checks for orchestrators: `GET /healthz` tells whether the process is alive, and
+//! `GET /readyz` whether it can serve traffic.
+use std::fs;
+use std::process::Command;
+use std::time::Instant;
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::{Json, Router};
+use serde_json::json;
+
+use radicle::node::{Handle as _, Node};
+
+use crate::api::Context;
+
+pub fn router(ctx: Context) -> Router {
+    Router::new()
+        .route("/healthz", get(health_handler))
+        .route("/readyz", get(ready_handler))
+        .with_state(ctx)
+}
+
+/// Return whether the process is alive.
+/// `GET /healthz`
+async fn health_handler() -> impl IntoResponse {
+    Json(json!({ "status": "ok" }))
+}
+
+/// Run the readiness checks, returning `503 Service Unavailable` if any of them fails.
+/// `GET /readyz`
+async fn ready_handler(State(ctx): State<Context>) -> impl IntoResponse {
+    let checks = tokio::task::spawn_blocking(move || checks(&ctx))
+        .await
+        .unwrap_or_default();
+    let ready = !checks.is_empty() && checks.iter().all(|(_, c)| c.is_ok());
+    let (status, label) = if ready {
+        (StatusCode::OK, "ok")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "fail")
+    };
+    let checks = checks
+        .into_iter()
+        .map(|(name, check)| (name.to_owned(), check.json()))
+        .collect::<serde_json::Map<_, _>>();
+
+    (status, Json(json!({ "status": label, "checks": checks })))
+}
+
+/// Outcome of a readiness check.
+struct Check {
+    result: Result<(), String>,
+    duration_ms: f64,
+}
+
+impl Check {
+    fn run(f: impl FnOnce() -> Result<(), String>) -> Self {
+        let started = Instant::now();
+        let result = f();
+
+        Self {
+            result,
+            duration_ms: started.elapsed().as_secs_f64() * 1000.,
+        }
+    }
+
+    fn is_ok(&self) -> bool {
+        self.result.is_ok()
+    }
+
+    fn json(&self) -> serde_json::Value {
+        match &self.result {
+            Ok(()) => json!({ "status": "ok", "durationMs": self.duration_ms }),
+            Err(e) => json!({ "status": "fail", "error": e, "durationMs": self.duration_ms }),
+        }
+    }
+}
+
+/// Run the readiness checks. They block, so run them off the async runtime.
+fn checks(ctx: &Context) -> Vec<(&'static str, Check)> {
+    let profile = ctx.profile();
+
+    vec![
+        (
+            "profile",
+            Check::run(|| {
+                let home = profile.home.path();
+                if home.is_dir() {
+                    Ok(())
+                } else {
+                    Err(format!("radicle home {} not found", home.display()))
+                }
+            }),
+        ),
+        (
+            "storage",
+            Check::run(|| {
+                fs::read_dir(profile.storage.path())
+                    .map(|_| ())
+                    .map_err(|e| format!("storage is not readable: {e}"))
+            }),
+        ),
+        (
+            "git",
+            Check::run(|| match Command::new("git").arg("version").output() {
+                Ok(output) if output.status.success() => Ok(()),
+                Ok(output) => Err(format!("'git version' failed with {}", output.status)),
+                Err(e) => Err(format!("'git' command must be available: {e}")),
+            }),
+        ),
+        (
+            "node",
+            Check::run(|| {
+                if Node::new(profile.socket()).is_running() {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "node is not reachable at {}",
+                        profile.socket().display()
+                    ))
+                }
+            }),
+        ),
+        (
+            "database",
+            Check::run(|| {
+                profile
+                    .database()
+                    .map(|_| ())
+                    .map_err(|e| format!("node database can't be opened: {e}"))?;
+                ctx.sessions()
+                    .active(time::OffsetDateTime::now_utc())
+                    .map(|_| ())
+                    .map_err(|e| format!("session store can't be read: {e}"))
+            }),
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod routes {
+    use std::net::SocketAddr;
+
+    use axum::extract::connect_info::MockConnectInfo;
+    use axum::http::StatusCode;
+    use serde_json::json;
+
+    use crate::test::{self, get};
+
+    #[tokio::test]
+    async fn test_healthz() {
+        let tmp = tempfile::tempdir().unwrap();
+        let app = super::router(test::seed(tmp.path()))
+            .layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 8080))));
+        let response = get(&app, "/healthz").await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.json().await, json!({ "status": "ok" }));
+    }
+
+    #[tokio::test]
+    async fn test_readyz() {
+        let tmp = tempfile::tempdir().unwrap();
+        let app = super::router(test::seed(tmp.path()))
+            .layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 8080))));
+        let response = get(&app, "/readyz").await;
+
+        // There is no node running in tests.
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let body = response.json().await;
+        let checks = &body["checks"];
+
+        assert_eq!(body["status"], "fail");
+        for check in ["profile", "storage", "git", "database"] {
+            assert_eq!(checks[check]["status"], "ok", "{check}: {body}");
+            assert!(checks[check]["durationMs"].is_f64());
+        }
+        assert_eq!(checks["node"]["status"], "fail");
+        assert!(checks["node"]["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("node is not reachable"));
+    }
+}
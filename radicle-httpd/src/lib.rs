@@ -2,6 +2,7 @@
 #![allow(clippy::too_many_arguments)]
 #![recursion_limit = "256"]
 pub mod commands;
+pub mod config;
 pub mod error;
 
 use std::collections::HashMap;
@@ -9,15 +10,16 @@ use std::net::SocketAddr;
 use std::num::NonZeroUsize;
 use std::process::Command;
 use std::str;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use std::time::Duration;
 
 use anyhow::Context as _;
 use axum::body::{Body, HttpBody};
+use axum::extract::DefaultBodyLimit;
 use axum::http::{Request, Response};
 use axum::middleware;
 use axum::Router;
-use tokio::net::TcpListener;
+use tower::ServiceExt as _;
 use tower_http::trace::TraceLayer;
 use tracing::Span;
 
@@ -29,20 +31,78 @@ use tracing_extra::{tracing_middleware, ColoredStatus, Paint, RequestId, Tracing
 mod api;
 mod axum_extra;
 mod cache;
+mod cob;
+mod cors;
 mod git;
+mod health;
+mod listen;
+mod metrics;
+mod rate_limit;
 mod raw;
+mod shutdown;
+#[cfg(feature = "otel")]
+mod telemetry;
 #[cfg(test)]
 mod test;
+mod tls;
 mod tracing_extra;
 
+pub use api::auth::store::Backend as SessionBackend;
+pub use cors::{CorsOptions, Origin};
+pub use listen::Listen;
+pub use rate_limit::{Quota, RateLimits};
+pub use tls::TlsOptions;
+
 /// Default cache HTTP size.
 pub const DEFAULT_CACHE_SIZE: NonZeroUsize = unsafe { NonZeroUsize::new_unchecked(100) };
+/// Default time given to requests in flight to finish on shutdown.
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
 
 #[derive(Debug, Clone)]
 pub struct Options {
     pub aliases: HashMap<String, RepoId>,
-    pub listen: SocketAddr,
+    pub listen: Listen,
+    /// Permissions of the unix socket listened on, eg. `0o660`.
+    pub socket_mode: Option<u32>,
     pub cache: Option<NonZeroUsize>,
+    /// Serve HTTPS instead of plain HTTP.
+    pub tls: Option<TlsOptions>,
+    /// Where to keep sessions.
+    pub sessions: SessionBackend,
+    /// Request quotas per client.
+    pub rate_limits: RateLimits,
+    /// Cross-origin request policy.
+    pub cors: CorsOptions,
+    /// Max size of request bodies, in bytes.
+    pub body_limit: Option<usize>,
+    pub features: Features,
+    /// Serve metrics on this address only, instead of on the main one.
+    pub metrics_listen: Option<Listen>,
+    /// How long to wait for requests in flight to finish on shutdown.
+    pub shutdown_timeout: Duration,
+    /// Where to reload the configuration from on `SIGHUP`.
+    pub reload: Option<config::Reload>,
+}
+
+/// Optional parts of the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Features {
+    /// Serve repositories over git's smart HTTP protocol.
+    pub git: bool,
+    /// Serve raw files under `/raw`.
+    pub raw: bool,
+    /// Serve Prometheus metrics, under `/metrics` or on the metrics address.
+    pub metrics: bool,
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Self {
+            git: true,
+            raw: true,
+            metrics: false,
+        }
+    }
 }
 
 /// Run the Server.
@@ -55,26 +115,203 @@ pub async fn run(options: Options) -> anyhow::Result<()> {
 
     tracing::info!("{}", str::from_utf8(&git_version)?.trim());
 
-    let listener = TcpListener::bind(options.listen).await?;
+    let listener = listen::bind(&options.listen, options.socket_mode)
+        .await
+        .with_context(|| format!("failed to listen on {}", options.listen))?;
+    let tls = options.tls.clone();
 
-    tracing::info!("listening on http://{}", options.listen);
+    match (&options.listen, &tls) {
+        (Listen::Unix(_), _) => tracing::info!("listening on {}", options.listen),
+        (_, Some(_)) => tracing::info!("listening on https://{}", options.listen),
+        (_, None) => tracing::info!("listening on http://{}", options.listen),
+    }
 
     let profile = Profile::load()?;
-    let request_id = RequestId::new();
-
     tracing::info!("using radicle home at {}", profile.home().path().display());
 
-    let app =
-        router(options, profile)?
-        .layer(middleware::from_fn(tracing_middleware))
+    let ctx = api::Context::new(Arc::new(profile), &options)?;
+    let requests = shutdown::Requests::default();
+    let backends = git::Backends::new(ctx.metrics().clone());
+    let buckets = rate_limit::RouterBuckets::default();
+    let current = Arc::new(RwLock::new(routes(
+        &options,
+        ctx.clone(),
+        &backends,
+        &buckets,
+    )));
+
+    tokio::spawn(api::auth::store::purge_periodically(ctx.sessions().clone()));
+    tokio::spawn(api::index_periodically(ctx.clone()));
+
+    // Event streams never end on their own, so they are closed as soon as shutdown begins.
+    let signal = {
+        let ctx = ctx.clone();
+        async move {
+            shutdown::signal().await;
+            ctx.close_events();
+        }
+    };
+
+    if let (true, Some(addr)) = (options.features.metrics, &options.metrics_listen) {
+        let listener = listen::bind(addr, None)
+            .await
+            .with_context(|| format!("failed to listen on {addr}"))?;
+        let app = metrics::router(ctx.clone());
+
+        tracing::info!("serving metrics on {addr}");
+        tokio::spawn(async move {
+            let result = match listener {
+                listen::Listener::Tcp(listener) => axum::serve(listener, app).await,
+                #[cfg(unix)]
+                listen::Listener::Unix(listener) => {
+                    listen::serve_unix(listener, app, std::future::pending()).await
+                }
+            };
+            if let Err(err) = result {
+                tracing::error!("Error serving metrics: {err}");
+            }
+        });
+    }
+
+    #[cfg(unix)]
+    if let Some(reload) = options.reload.clone() {
+        tokio::spawn(reload_on_hangup(
+            reload,
+            options.clone(),
+            ctx,
+            backends.clone(),
+            buckets,
+            current.clone(),
+        ));
+    }
+
+    // Requests are handed to the current router, so that it can be swapped out on reload
+    // without affecting open connections.
+    let app = layers(
+        Router::new().fallback_service(tower::service_fn(move |request: Request<Body>| {
+            #[allow(clippy::unwrap_used)]
+            let router = current.read().unwrap().clone();
+            router.oneshot(request)
+        })),
+        RequestId::new(),
+        requests.clone(),
+    );
+
+    let timeout = options.shutdown_timeout;
+    let summary = match (listener, tls) {
+        #[cfg(unix)]
+        (listen::Listener::Unix(listener), _) => {
+            let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+            let server = listen::serve_unix(listener, app, async move {
+                stopped.await.ok();
+            });
+            let summary = shutdown::graceful(
+                server,
+                signal,
+                move || {
+                    stop.send(()).ok();
+                },
+                timeout,
+                &requests,
+                &backends,
+            )
+            .await?;
+
+            if let Listen::Unix(path) = &options.listen {
+                std::fs::remove_file(path).ok();
+            }
+            summary
+        }
+        (listen::Listener::Tcp(listener), Some(tls)) => {
+            let config = tls::config(&tls)
+                .await
+                .with_context(|| format!("failed to load TLS certificate {:?}", tls.cert))?;
+            #[cfg(unix)]
+            tokio::spawn(tls::reload_on_hangup(config.clone(), tls));
+
+            let handle = axum_server::Handle::new();
+            let server = axum_server::from_tcp_rustls(listener.into_std()?, config)
+                .handle(handle.clone())
+                .serve(app.into_make_service_with_connect_info::<SocketAddr>());
+
+            shutdown::graceful(
+                server,
+                signal,
+                move || handle.graceful_shutdown(None),
+                timeout,
+                &requests,
+                &backends,
+            )
+            .await?
+        }
+        (listen::Listener::Tcp(listener), None) => {
+            let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+            let app = app.into_make_service_with_connect_info::<SocketAddr>();
+            let server = axum::serve(listener, app).with_graceful_shutdown(async move {
+                stopped.await.ok();
+            });
+
+            shutdown::graceful(
+                server,
+                signal,
+                move || {
+                    stop.send(()).ok();
+                },
+                timeout,
+                &requests,
+                &backends,
+            )
+            .await?
+        }
+    };
+
+    if summary.aborted > 0 {
+        tracing::warn!("shutdown deadline of {timeout:?} reached: {summary}");
+    } else {
+        tracing::info!("shutdown complete: {summary}");
+    }
+    #[cfg(feature = "otel")]
+    tokio::task::spawn_blocking(telemetry::shutdown).await.ok();
+
+    Ok(())
+}
+
+/// Wrap the app with request ids, access logs and request tracking.
+fn layers(app: Router, request_id: RequestId, requests: shutdown::Requests) -> Router {
+    app.layer(middleware::from_fn(tracing_middleware))
         .layer(
             TraceLayer::new_for_http()
-                .make_span_with(move |_request: &Request<Body>| {
-                    tracing::info_span!("request", id = %request_id.clone().next())
+                .make_span_with(|request: &Request<Body>| {
+                    let span = tracing::info_span!(
+                        "request",
+                        request_id = %tracing_extra::request_id(request)
+                    );
+                    #[cfg(feature = "otel")]
+                    telemetry::set_parent(&span, request.headers());
+
+                    span
                 })
                 .on_response(
                     |response: &Response<Body>, latency: Duration, _span: &Span| {
-                        if let Some(info) = response.extensions().get::<TracingInfo>() {
+                        let Some(info) = response.extensions().get::<TracingInfo>() else {
+                            tracing::info!("Processed");
+                            return;
+                        };
+                        let size = response.body().size_hint().exact().unwrap_or(0);
+
+                        if cfg!(feature = "logjson") {
+                            tracing::info!(
+                                request_id = %info.request_id,
+                                remote = %info.connect_info.0,
+                                method = %info.method,
+                                uri = %info.uri,
+                                version = ?info.version,
+                                status = response.status().as_u16(),
+                                latency_ms = latency.as_secs_f64() * 1000.,
+                                size,
+                                "access"
+                            );
+                        } else {
                             tracing::info!(
                                 "{} \"{} {} {:?}\" {} {:?} {}",
                                 info.connect_info.0,
@@ -83,55 +320,173 @@ pub async fn run(options: Options) -> anyhow::Result<()> {
                                 info.version,
                                 ColoredStatus(response.status()),
                                 latency,
-                                Paint::dim(
-                                    response
-                                        .body()
-                                        .size_hint()
-                                        .exact()
-                                        .map(|n| n.to_string())
-                                        .unwrap_or("0".to_string())
-                                        .into()
-                                ),
+                                Paint::dim(size.to_string().into()),
                             );
-                        } else {
-                            tracing::info!("Processed");
                         }
                     },
                 ),
         )
-        .into_make_service_with_connect_info::<SocketAddr>();
+        .layer(middleware::from_fn_with_state(
+            request_id,
+            tracing_extra::request_id_middleware,
+        ))
+        .layer(middleware::from_fn_with_state(
+            requests,
+            shutdown::track_middleware,
+        ))
+}
 
-    axum::serve(listener, app)
-        .await
-        .map_err(anyhow::Error::from)
+/// Re-read the configuration every time the process receives `SIGHUP`, and swap in a router
+/// built from it. The listen addresses, TLS, session and shutdown settings only change on restart.
+#[cfg(unix)]
+async fn reload_on_hangup(
+    reload: config::Reload,
+    options: Options,
+    ctx: api::Context,
+    backends: git::Backends,
+    buckets: rate_limit::RouterBuckets,
+    current: Arc<RwLock<Router>>,
+) -> std::io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = signal(SignalKind::hangup())?;
+    while hangup.recv().await.is_some() {
+        match reload_router(&reload, &options, &ctx, &backends, &buckets, &current).await {
+            Ok(()) => tracing::info!("reloaded configuration from {}", reload.path.display()),
+            Err(err) => tracing::error!("Error reloading {}: {err}", reload.path.display()),
+        }
+    }
+    Ok(())
+}
+
+/// Re-read the configuration and swap in a router built from it. The response cache and the
+/// rate limiter buckets are carried over to the new router.
+#[cfg(unix)]
+async fn reload_router(
+    reload: &config::Reload,
+    options: &Options,
+    ctx: &api::Context,
+    backends: &git::Backends,
+    buckets: &rate_limit::RouterBuckets,
+    current: &RwLock<Router>,
+) -> Result<(), config::Error> {
+    let reloaded = reload.options()?;
+
+    if reloaded.listen != options.listen
+        || reloaded.socket_mode != options.socket_mode
+        || reloaded.tls.is_some() != options.tls.is_some()
+        || reloaded.sessions != options.sessions
+        || reloaded.shutdown_timeout != options.shutdown_timeout
+        || reloaded.metrics_listen != options.metrics_listen
+    {
+        tracing::warn!(
+            "listen, tls, session, shutdown and metrics listen settings only take effect \
+             after a restart"
+        );
+    }
+    let ctx = ctx.clone().with_cache(reloaded.cache).await;
+    let router = routes(&reloaded, ctx, backends, buckets);
+    #[allow(clippy::unwrap_used)]
+    let mut current = current.write().unwrap();
+    *current = router;
+
+    Ok(())
 }
 
 /// Create a router consisting of other sub-routers.
+#[cfg(test)]
 fn router(options: Options, profile: Profile) -> anyhow::Result<Router> {
-    let profile = Arc::new(profile);
-    let ctx = api::Context::new(profile.clone(), &options);
+    let ctx = api::Context::new(Arc::new(profile), &options)?;
 
-    let api_router = api::router(ctx);
-    let git_router = git::router(profile.clone(), options.aliases);
-    let raw_router = raw::router(profile);
+    Ok(routes(
+        &options,
+        ctx,
+        &git::Backends::default(),
+        &Default::default(),
+    ))
+}
 
-    let app = Router::new()
-        .merge(git_router)
-        .nest("/api", api_router)
-        .nest("/raw", raw_router);
+/// Create the sub-routers for the given options, sharing the API context, the registry of
+/// running git backends and the rate limiter buckets.
+fn routes(
+    options: &Options,
+    ctx: api::Context,
+    backends: &git::Backends,
+    buckets: &rate_limit::RouterBuckets,
+) -> Router {
+    let profile = ctx.profile().clone();
+    let sessions = ctx.sessions().clone();
+    let metrics = ctx.metrics().clone();
+    let limits = options.rate_limits;
+    let mut app = Router::new();
 
-    Ok(app)
+    if options.features.git {
+        app = app.merge(rate_limit::layer(
+            git::router(profile.clone(), options.aliases.clone(), backends.clone()),
+            limits.git,
+            sessions.clone(),
+            &buckets.git,
+        ));
+    }
+    app = app.merge(health::router(ctx.clone()));
+    if options.features.metrics && options.metrics_listen.is_none() {
+        app = app.merge(metrics::router(ctx.clone()));
+    }
+    let api_router = api::router(ctx).layer(options.cors.layer(&options.cors.api_methods));
+    app = app.nest(
+        "/api",
+        rate_limit::layer(api_router, limits.api, sessions.clone(), &buckets.api),
+    );
+    if options.features.raw {
+        let raw_router = raw::router(profile).layer(options.cors.layer(&options.cors.raw_methods));
+        app = app.nest(
+            "/raw",
+            rate_limit::layer(raw_router, limits.raw, sessions, &buckets.raw),
+        );
+    }
+    if let Some(limit) = options.body_limit {
+        app = app.layer(DefaultBodyLimit::max(limit));
+    }
+    app.layer(middleware::from_fn_with_state(
+        metrics,
+        metrics::track_middleware,
+    ))
 }
 
 pub mod logger {
     use tracing::dispatcher::Dispatch;
+    use tracing_subscriber::registry::LookupSpan;
 
+    /// Install the global subscriber. With the `otel` feature, spans are also exported when a
+    /// collector endpoint is configured. Must be called from within the tokio runtime.
     pub fn init() -> Result<(), tracing::subscriber::SetGlobalDefaultError> {
-        tracing::dispatcher::set_global_default(Dispatch::new(subscriber()))
+        let subscriber = subscriber();
+        #[cfg(feature = "otel")]
+        let subscriber = {
+            use tracing_subscriber::layer::SubscriberExt as _;
+
+            subscriber.with(crate::telemetry::layer())
+        };
+        tracing::dispatcher::set_global_default(Dispatch::new(subscriber))
+    }
+
+    /// Log JSON lines, with the fields of the current request, eg. its id.
+    #[cfg(feature = "logjson")]
+    pub fn subscriber() -> impl tracing::Subscriber + for<'a> LookupSpan<'a> + Send + Sync {
+        use tracing_subscriber::EnvFilter;
+
+        tracing_subscriber::fmt()
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .with_env_filter(
+                EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+            )
+            .finish()
     }
 
-    #[cfg(feature = "logfmt")]
-    pub fn subscriber() -> impl tracing::Subscriber {
+    #[cfg(all(feature = "logfmt", not(feature = "logjson")))]
+    pub fn subscriber() -> impl tracing::Subscriber + for<'a> LookupSpan<'a> + Send + Sync {
         use tracing_subscriber::layer::SubscriberExt as _;
         use tracing_subscriber::EnvFilter;
 
@@ -140,8 +495,8 @@ pub mod logger {
             .with(tracing_logfmt::layer())
     }
 
-    #[cfg(not(feature = "logfmt"))]
-    pub fn subscriber() -> impl tracing::Subscriber {
+    #[cfg(not(any(feature = "logfmt", feature = "logjson")))]
+    pub fn subscriber() -> impl tracing::Subscriber + for<'a> LookupSpan<'a> + Send + Sync {
         tracing_subscriber::FmtSubscriber::builder()
             .with_target(false)
             .with_max_level(tracing::Level::DEBUG)
@@ -154,10 +509,13 @@ mod routes {
     use std::collections::HashMap;
     use std::net::SocketAddr;
 
+    use axum::body::Body;
     use axum::extract::connect_info::MockConnectInfo;
     use axum::http::StatusCode;
+    use serde_json::json;
 
-    use crate::test::{self, get};
+    use crate::config;
+    use crate::test::{self, get, put, RID};
 
     #[tokio::test]
     async fn test_invalid_route_returns_404() {
@@ -165,8 +523,18 @@ mod routes {
         let app = super::router(
             super::Options {
                 aliases: HashMap::new(),
-                listen: SocketAddr::from(([0, 0, 0, 0], 8080)),
+                listen: super::Listen::default(),
+                socket_mode: None,
                 cache: None,
+                tls: None,
+                sessions: super::SessionBackend::Memory,
+                rate_limits: Default::default(),
+                cors: Default::default(),
+                body_limit: None,
+                features: Default::default(),
+                metrics_listen: None,
+                shutdown_timeout: super::DEFAULT_SHUTDOWN_TIMEOUT,
+                reload: None,
             },
             test::profile(tmp.path(), [0xff; 32]),
         )
@@ -177,4 +545,180 @@ mod routes {
 
         assert_eq!(response.status(), StatusCode::NOT_FOUND);
     }
+
+    #[tokio::test]
+    async fn test_routes_options() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ctx = test::seed(tmp.path());
+        let options = config::Config {
+            limits: config::Limits {
+                body: Some(16),
+                ..Default::default()
+            },
+            features: config::FeatureToggles {
+                raw: Some(false),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+        .options()
+        .unwrap();
+        let app = super::routes(&options, ctx, &Default::default(), &Default::default())
+            .layer(MockConnectInfo(SocketAddr::from(([0, 0, 0, 0], 8080))));
+
+        let response = get(&app, format!("/raw/{RID}/head/README")).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let body = Body::from(vec![b' '; 17]);
+        let response = put(&app, "/api/v1/sessions/abc", Some(body), None).await;
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_reload_router() {
+        use std::sync::{Arc, RwLock};
+
+        use axum::Router;
+        use tower::ServiceExt as _;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("httpd.toml");
+        std::fs::write(&path, "cache = 10\n[limits]\nrate = { api = \"1/h\" }").unwrap();
+
+        let reload = config::Reload {
+            path: path.clone(),
+            overrides: config::Config::default(),
+            http2: false,
+        };
+        let options = reload.options().unwrap();
+        let ctx = test::seed(tmp.path()).with_cache(options.cache).await;
+        let backends = Default::default();
+        let buckets = Default::default();
+        let current = Arc::new(RwLock::new(super::routes(
+            &options,
+            ctx.clone(),
+            &backends,
+            &buckets,
+        )));
+        let app = {
+            let current = current.clone();
+            Router::new()
+                .fallback_service(tower::service_fn(move |request| {
+                    let router = current.read().unwrap().clone();
+                    router.oneshot(request)
+                }))
+                .layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 8080))))
+        };
+        let readme = format!("/raw/{RID}/head/README");
+
+        assert_eq!(get(&app, "/api/v1").await.status(), StatusCode::OK);
+        assert_eq!(
+            get(&app, "/api/v1").await.status(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_ne!(get(&app, &readme).await.status(), StatusCode::NOT_FOUND);
+        ctx.cache().unwrap().tree.lock().await.put(
+            (
+                RID.parse().unwrap(),
+                test::HEAD.parse().unwrap(),
+                "/".to_owned(),
+            ),
+            json!({}),
+        );
+
+        std::fs::write(
+            &path,
+            "cache = 20\n[limits]\nrate = { api = \"1/h\" }\n[features]\nraw = false",
+        )
+        .unwrap();
+        super::reload_router(&reload, &options, &ctx, &backends, &buckets, &current)
+            .await
+            .unwrap();
+
+        // The new configuration applies, but clients keep their budget and the cache its
+        // entries.
+        assert_eq!(get(&app, &readme).await.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            get(&app, "/api/v1").await.status(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        let cache = ctx.cache().unwrap().tree.lock().await;
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.cap().get(), 20);
+    }
+
+    #[tokio::test]
+    async fn test_request_id() {
+        use std::sync::{Arc, Mutex};
+
+        use axum::extract::ConnectInfo;
+        use axum::Extension;
+
+        #[derive(Clone, Default)]
+        struct Logs(Arc<Mutex<Vec<u8>>>);
+
+        impl std::io::Write for Logs {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let tmp = tempfile::tempdir().unwrap();
+        let ctx = test::seed(tmp.path());
+        let options = config::Config::default().options().unwrap();
+        let app = super::layers(
+            super::routes(&options, ctx, &Default::default(), &Default::default()),
+            super::RequestId::new(),
+            Default::default(),
+        )
+        .layer(Extension(ConnectInfo(SocketAddr::from((
+            [127, 0, 0, 1],
+            8080,
+        )))));
+        let logs = Logs::default();
+        let _guard = tracing::subscriber::set_default(
+            tracing_subscriber::fmt()
+                .with_ansi(false)
+                .with_writer({
+                    let logs = logs.clone();
+                    move || logs.clone()
+                })
+                .finish(),
+        );
+
+        // Ids from upstream are echoed.
+        let response = test::get_with_header(&app, "/healthz", ("X-Request-Id", "lb-42")).await;
+        assert_eq!(response.headers()["x-request-id"], "lb-42");
+
+        // Otherwise, or if they're invalid, they're generated.
+        let response = get(&app, "/healthz").await;
+        let first = response.headers()["x-request-id"]
+            .to_str()
+            .unwrap()
+            .to_owned();
+        let response = test::get_with_header(&app, "/healthz", ("X-Request-Id", "a b")).await;
+        let second = response.headers()["x-request-id"]
+            .to_str()
+            .unwrap()
+            .to_owned();
+        assert_ne!(first, second);
+        assert_ne!(second, "a b");
+
+        // Error logs mention the request they're about.
+        let response =
+            test::get_with_header(&app, "/unknown.git/info/refs", ("X-Request-Id", "lb-43")).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(
+            logs.lines()
+                .any(|l| l.contains("ERROR") && l.contains("request{request_id=lb-43}")),
+            "{logs}"
+        );
+    }
 }
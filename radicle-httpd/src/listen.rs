@@ -0,0 +1,245 @@
+//! Where to accept connections: a TCP address or a unix domain socket.
+use std::future::Future;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::{fmt, io};
+
+use axum::extract::ConnectInfo;
+use axum::{Extension, Router};
+use tokio::net::TcpListener;
+
+/// Address unix socket peers appear to connect from. They are on the same machine, so they're
+/// treated as local, like loopback connections.
+pub const UNIX_PEER: SocketAddr = SocketAddr::new(std::net::IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+
+/// Prefix of unix socket addresses.
+const UNIX_PREFIX: &str = "unix:";
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub enum Listen {
+    /// Listen on a TCP address, eg. `0.0.0.0:8080`.
+    Tcp(SocketAddr),
+    /// Listen on a unix domain socket, eg. `unix:/run/radicle-httpd.sock`.
+    Unix(PathBuf),
+}
+
+impl Default for Listen {
+    fn default() -> Self {
+        Self::Tcp(([0, 0, 0, 0], 8080).into())
+    }
+}
+
+impl FromStr for Listen {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix(UNIX_PREFIX) {
+            Some("") => Err(format!(
+                "invalid listen address '{s}', the socket path is missing"
+            )),
+            Some(path) => Ok(Self::Unix(PathBuf::from(path))),
+            None => s
+                .parse()
+                .map(Self::Tcp)
+                .map_err(|_| format!("invalid listen address '{s}'")),
+        }
+    }
+}
+
+impl TryFrom<String> for Listen {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for Listen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(addr) => write!(f, "{addr}"),
+            Self::Unix(path) => write!(f, "{UNIX_PREFIX}{}", path.display()),
+        }
+    }
+}
+
+/// A bound listener.
+pub enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener),
+}
+
+/// Bind to the address. Unix sockets get the given permissions, eg. `0o660`, and replace stale
+/// sockets left behind at the same path.
+pub async fn bind(listen: &Listen, mode: Option<u32>) -> io::Result<Listener> {
+    match listen {
+        Listen::Tcp(addr) => TcpListener::bind(addr).await.map(Listener::Tcp),
+        #[cfg(unix)]
+        Listen::Unix(path) => {
+            use std::fs;
+            use std::os::unix::fs::{FileTypeExt as _, PermissionsExt as _};
+
+            if fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+                fs::remove_file(path)?;
+            }
+            let listener = tokio::net::UnixListener::bind(path)?;
+            if let Some(mode) = mode {
+                fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+            }
+            Ok(Listener::Unix(listener))
+        }
+        #[cfg(not(unix))]
+        Listen::Unix(_) => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "unix sockets are not supported on this platform",
+        )),
+    }
+}
+
+/// Serve `app` on a unix socket until `signal` completes, then stop accepting connections and
+/// wait for the open ones to finish.
+#[cfg(unix)]
+pub async fn serve_unix(
+    listener: tokio::net::UnixListener,
+    app: Router,
+    signal: impl Future<Output = ()> + Send + 'static,
+) -> io::Result<()> {
+    use hyper::body::Incoming;
+    use hyper_util::rt::{TokioExecutor, TokioIo};
+    use hyper_util::server::conn::auto::Builder;
+    use tokio::sync::watch;
+    use tower::ServiceExt as _;
+
+    let app = app.layer(Extension(ConnectInfo(UNIX_PEER)));
+    // The signal receiver is dropped on shutdown, which closes the sender. Connections hold on to
+    // a close receiver each; once they're all dropped, we're done.
+    let (signal_tx, signal_rx) = watch::channel(());
+    let signal_tx = Arc::new(signal_tx);
+    let (close_tx, close_rx) = watch::channel(());
+    tokio::spawn(async move {
+        signal.await;
+        drop(signal_rx);
+    });
+
+    loop {
+        let stream = tokio::select! {
+            result = listener.accept() => match result {
+                Ok((stream, _)) => stream,
+                Err(err) => {
+                    tracing::error!("accept error: {err}");
+                    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                    continue;
+                }
+            },
+            () = signal_tx.closed() => break,
+        };
+        let app = app.clone();
+        let signal_tx = signal_tx.clone();
+        let close_rx = close_rx.clone();
+
+        tokio::spawn(async move {
+            let service = hyper::service::service_fn(move |request: hyper::Request<Incoming>| {
+                app.clone().oneshot(request)
+            });
+            let builder = Builder::new(TokioExecutor::new());
+            let conn = builder.serve_connection_with_upgrades(TokioIo::new(stream), service);
+            tokio::pin!(conn);
+
+            let mut shutting_down = false;
+            loop {
+                tokio::select! {
+                    result = conn.as_mut() => {
+                        if let Err(err) = result {
+                            tracing::debug!("failed to serve connection: {err:#}");
+                        }
+                        break;
+                    }
+                    () = signal_tx.closed(), if !shutting_down => {
+                        shutting_down = true;
+                        conn.as_mut().graceful_shutdown();
+                    }
+                }
+            }
+            drop(close_rx);
+        });
+    }
+    drop(close_rx);
+    drop(listener);
+    close_tx.closed().await;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_listen_from_str() {
+        assert_eq!(
+            "127.0.0.1:8080".parse(),
+            Ok(Listen::Tcp(([127, 0, 0, 1], 8080).into()))
+        );
+        assert_eq!(
+            "unix:/run/httpd.sock".parse(),
+            Ok(Listen::Unix(PathBuf::from("/run/httpd.sock")))
+        );
+        assert_eq!(
+            Listen::Unix(PathBuf::from("/run/httpd.sock")).to_string(),
+            "unix:/run/httpd.sock"
+        );
+        assert!("unix:".parse::<Listen>().is_err());
+        assert!("localhost".parse::<Listen>().is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_serve_unix() {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        use axum::routing::get;
+        use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+        use tokio::net::UnixStream;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("httpd.sock");
+        let listen = Listen::Unix(path.clone());
+        let Listener::Unix(listener) = bind(&listen, Some(0o600)).await.unwrap() else {
+            panic!("expected a unix listener");
+        };
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+
+        let app = Router::new().route(
+            "/",
+            get(|ConnectInfo(addr): ConnectInfo<SocketAddr>| async move { addr.to_string() }),
+        );
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(serve_unix(listener, app, async move {
+            stopped.await.ok();
+        }));
+
+        let mut stream = UnixStream::connect(&path).await.unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+        assert!(response.ends_with(&UNIX_PEER.to_string()), "{response}");
+
+        stop.send(()).unwrap();
+        server.await.unwrap().unwrap();
+
+        // Stale sockets are replaced.
+        assert!(bind(&listen, None).await.is_ok());
+    }
+}
@@ -1,9 +1,12 @@
-use std::num::NonZeroUsize;
-use std::{collections::HashMap, process};
+use std::path::PathBuf;
+use std::process;
+
+use anyhow::anyhow;
 
 use radicle::prelude::RepoId;
 use radicle::version::Version;
 use radicle_httpd as httpd;
+use radicle_httpd::config::{self, Config};
 
 pub const VERSION: Version = Version {
     name: "radicle-httpd",
@@ -19,10 +22,29 @@ Usage
    
 Options
 
-    --listen       <address>         Address to listen on (default: 0.0.0.0:8080)
+    --config       <path>            Configuration file, TOML or JSON if the name ends in '.json'; reloaded
+                                     on SIGHUP. Other options take precedence over it
+    --listen       <address>         Address to listen on, or unix socket, e.g. 'unix:/run/radicle-httpd.sock',
+                                     whose peers are treated as local (default: 0.0.0.0:8080)
+    --socket-mode  <mode>            Permissions of the unix socket, in octal, e.g. 660
     --alias, -a    <alias> <rid>     Provide alias and RID pairs to shorten git clone commands for repositories,
                                      e.g. heartwood and rad:z3gqcJUoA1n9HaHKufZs5FCSGazv5 to produce https://seed.radicle.xyz/heartwood.git
     --cache        <number>          Max amount of items in cache for /tree endpoints (default: 100)
+    --tls-cert     <path>            PEM encoded certificate chain to serve HTTPS with; reloaded on SIGHUP
+    --tls-key      <path>            PEM encoded private key of the TLS certificate
+    --http2                          Offer HTTP/2 to clients, requires TLS
+    --sessions     <store>           Where to keep sessions, 'sqlite' or 'memory' (default: sqlite)
+    --rate-limit   <router>=<quota>  Limit the requests each client can make to the 'api', 'git' or 'raw'
+                                     router, or 'all' of them, e.g. 'api=10/s' or 'git=600/m:50', where
+                                     the optional last number is the burst size (default: no limit)
+    --cors-origin  <origin>          Origin allowed to make cross-origin requests, exact or for all
+                                     subdomains, e.g. 'https://*.radicle.xyz'; may be repeated (default: any)
+    --shutdown-timeout <secs>        Time given to requests in flight to finish on SIGTERM or SIGINT,
+                                     after which they are aborted (default: 30)
+    --metrics                        Serve Prometheus metrics at /metrics, publicly (default: off)
+    --metrics-listen <address>       Serve Prometheus metrics on this admin address only, instead of at
+                                     /metrics on the main one; implies --metrics
+    --no-metrics                     Don't serve metrics
     --version, -v                    Print program version
     --help, -h                       Print help
 "#;
@@ -46,26 +68,35 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
-/// Parse command-line arguments into HTTP options.
-fn parse_options() -> Result<httpd::Options, lexopt::Error> {
+/// Parse command-line arguments into HTTP options, layered on top of the configuration file
+/// if one is given.
+fn parse_options() -> anyhow::Result<httpd::Options> {
     use lexopt::prelude::*;
 
     let mut parser = lexopt::Parser::from_env();
-    let mut listen = None;
-    let mut aliases = HashMap::new();
-    let mut cache = Some(httpd::DEFAULT_CACHE_SIZE);
+    let mut overrides = Config::default();
+    let mut config = None;
+    let mut tls_cert: Option<PathBuf> = None;
+    let mut tls_key: Option<PathBuf> = None;
+    let mut http2 = false;
 
     while let Some(arg) = parser.next()? {
         match arg {
+            Long("config") => {
+                config = Some(PathBuf::from(parser.value()?));
+            }
             Long("listen") => {
                 let addr = parser.value()?.parse()?;
-                listen = Some(addr);
+                overrides.listen = Some(addr);
+            }
+            Long("socket-mode") => {
+                overrides.socket_mode = Some(parser.value()?.parse()?);
             }
             Long("alias") | Short('a') => {
                 let alias: String = parser.value()?.parse()?;
                 let id: RepoId = parser.value()?.parse()?;
 
-                aliases.insert(alias, id);
+                overrides.aliases.insert(alias, id);
             }
             Long("version") | Short('v') => {
                 if let Err(e) = VERSION.write(std::io::stdout()) {
@@ -76,18 +107,80 @@ fn parse_options() -> Result<httpd::Options, lexopt::Error> {
             }
             Long("cache") => {
                 let size = parser.value()?.parse()?;
-                cache = NonZeroUsize::new(size);
+                overrides.cache = Some(size);
+            }
+            Long("tls-cert") => {
+                tls_cert = Some(parser.value()?.into());
+            }
+            Long("tls-key") => {
+                tls_key = Some(parser.value()?.into());
+            }
+            Long("http2") => {
+                http2 = true;
+            }
+            Long("sessions") => {
+                overrides.auth.sessions = Some(parser.value()?.parse()?);
+            }
+            Long("rate-limit") => {
+                let value: String = parser.value()?.parse()?;
+                let (router, quota) = value
+                    .split_once('=')
+                    .ok_or_else(|| anyhow!("invalid rate limit '{value}'"))?;
+
+                overrides
+                    .limits
+                    .rate
+                    .insert(router.to_owned(), quota.to_owned());
+            }
+            Long("cors-origin") => {
+                let origin: String = parser.value()?.parse()?;
+
+                overrides
+                    .cors
+                    .origins
+                    .get_or_insert_with(Vec::new)
+                    .push(origin);
+            }
+            Long("metrics-listen") => {
+                overrides.metrics.listen = Some(parser.value()?.parse()?);
+            }
+            Long("metrics") => {
+                overrides.features.metrics = Some(true);
+            }
+            Long("no-metrics") => {
+                overrides.features.metrics = Some(false);
+            }
+            Long("shutdown-timeout") => {
+                let secs = parser.value()?.parse()?;
+                overrides.shutdown_timeout = Some(secs);
             }
             Long("help") | Short('h') => {
                 println!("{HELP_MSG}");
                 process::exit(0);
             }
-            _ => return Err(arg.unexpected()),
+            _ => return Err(arg.unexpected().into()),
+        }
+    }
+    overrides.tls = match (tls_cert, tls_key) {
+        (Some(cert), Some(key)) => Some(config::Tls { cert, key, http2 }),
+        (None, None) => None,
+        _ => anyhow::bail!("`--tls-cert` and `--tls-key` must be used together"),
+    };
+
+    let mut options = match config {
+        Some(path) => config::Reload {
+            path,
+            overrides,
+            http2,
+        }
+        .options()?,
+        None => overrides.options()?,
+    };
+    if http2 {
+        match options.tls.as_mut() {
+            Some(tls) => tls.http2 = true,
+            None => anyhow::bail!("`--http2` requires `--tls-cert` and `--tls-key`"),
         }
     }
-    Ok(httpd::Options {
-        aliases,
-        listen: listen.unwrap_or_else(|| ([0, 0, 0, 0], 8080).into()),
-        cache,
-    })
+    Ok(options)
 }
@@ -0,0 +1,400 @@
+//! Prometheus metrics, served in the text exposition format at `GET /metrics`, either on the
+//! main listener or on a separate admin listener.
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::body::Body;
+use axum::extract::{MatchedPath, State};
+use axum::http::{header, Method, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+
+use radicle::storage::ReadStorage;
+
+use crate::api::{Context, Error};
+
+/// Prefix of all metric names.
+const PREFIX: &str = "radicle_httpd";
+/// Route label of requests that didn't match any route.
+const UNMATCHED: &str = "unmatched";
+/// Upper bounds of the latency histogram buckets, in seconds.
+const BUCKETS: [f64; 12] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1., 2.5, 5., 10., 30.,
+];
+
+#[derive(Debug, Default, Clone)]
+struct Histogram {
+    /// Observations per bucket, not cumulative.
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, duration: Duration) {
+        let secs = duration.as_secs_f64();
+        if let Some(i) = BUCKETS.iter().position(|le| secs <= *le) {
+            self.buckets[i] += 1;
+        }
+        self.count += 1;
+        self.sum += secs;
+    }
+
+    fn write(&self, out: &mut String, name: &str, labels: &str) {
+        let sep = if labels.is_empty() { "" } else { "," };
+        let mut cumulative = 0;
+
+        for (le, n) in BUCKETS.iter().zip(self.buckets) {
+            cumulative += n;
+            writeln!(
+                out,
+                "{name}_bucket{{{labels}{sep}le=\"{le}\"}} {cumulative}"
+            )
+            .ok();
+        }
+        writeln!(
+            out,
+            "{name}_bucket{{{labels}{sep}le=\"+Inf\"}} {}",
+            self.count
+        )
+        .ok();
+        writeln!(out, "{name}_sum{{{labels}}} {}", self.sum).ok();
+        writeln!(out, "{name}_count{{{labels}}} {}", self.count).ok();
+    }
+}
+
+#[derive(Debug, Default)]
+struct Route {
+    /// Responses by status class, eg. `2xx`.
+    statuses: BTreeMap<&'static str, u64>,
+    latency: Histogram,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    /// Requests by route template and method.
+    requests: Mutex<BTreeMap<(String, &'static str), Route>>,
+    git_backends: Mutex<Histogram>,
+    git_backend_spawns: AtomicU64,
+    git_backends_running: AtomicI64,
+    tree_cache_hits: AtomicU64,
+    tree_cache_misses: AtomicU64,
+}
+
+/// Metrics collected while serving.
+#[derive(Debug, Default, Clone)]
+pub struct Metrics(Arc<Inner>);
+
+impl Metrics {
+    /// Record a response to a request.
+    pub fn request(&self, route: &str, method: &Method, status: StatusCode, latency: Duration) {
+        #[allow(clippy::unwrap_used)]
+        let mut requests = self.0.requests.lock().unwrap();
+        let route = requests
+            .entry((route.to_owned(), method_label(method)))
+            .or_default();
+
+        *route.statuses.entry(status_class(status)).or_default() += 1;
+        route.latency.observe(latency);
+    }
+
+    /// Record that a `git http-backend` process was spawned.
+    pub fn git_backend_spawned(&self) {
+        self.0.git_backend_spawns.fetch_add(1, Ordering::Relaxed);
+        self.0.git_backends_running.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record that a `git http-backend` process exited after `duration`.
+    pub fn git_backend_exited(&self, duration: Duration) {
+        self.0.git_backends_running.fetch_sub(1, Ordering::Relaxed);
+        #[allow(clippy::unwrap_used)]
+        self.0.git_backends.lock().unwrap().observe(duration);
+    }
+
+    /// Record a lookup in the tree cache.
+    pub fn tree_cache(&self, hit: bool) {
+        if hit {
+            self.0.tree_cache_hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.0.tree_cache_misses.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Render the metrics, along with the given gauges.
+    fn render(&self, gauges: &[(&str, &str, usize)]) -> String {
+        let mut out = String::new();
+
+        {
+            #[allow(clippy::unwrap_used)]
+            let requests = self.0.requests.lock().unwrap();
+
+            header(
+                &mut out,
+                "requests_total",
+                "counter",
+                "Requests handled, by route template, method and status class.",
+            );
+            for ((route, method), r) in requests.iter() {
+                for (status, n) in &r.statuses {
+                    writeln!(
+                        out,
+                        "{PREFIX}_requests_total{{route=\"{}\",method=\"{method}\",status=\"{status}\"}} {n}",
+                        escape(route)
+                    )
+                    .ok();
+                }
+            }
+            header(
+                &mut out,
+                "request_duration_seconds",
+                "histogram",
+                "Time to respond to requests, by route template and method.",
+            );
+            for ((route, method), r) in requests.iter() {
+                r.latency.write(
+                    &mut out,
+                    &format!("{PREFIX}_request_duration_seconds"),
+                    &format!("route=\"{}\",method=\"{method}\"", escape(route)),
+                );
+            }
+        }
+        #[allow(clippy::unwrap_used)]
+        let git = self.0.git_backends.lock().unwrap().clone();
+
+        header(
+            &mut out,
+            "git_backend_spawns_total",
+            "counter",
+            "git http-backend processes spawned.",
+        );
+        writeln!(
+            out,
+            "{PREFIX}_git_backend_spawns_total {}",
+            self.0.git_backend_spawns.load(Ordering::Relaxed)
+        )
+        .ok();
+        header(
+            &mut out,
+            "git_backend_duration_seconds",
+            "histogram",
+            "Time git http-backend processes ran for.",
+        );
+        git.write(
+            &mut out,
+            &format!("{PREFIX}_git_backend_duration_seconds"),
+            "",
+        );
+        header(
+            &mut out,
+            "git_backends_running",
+            "gauge",
+            "git http-backend processes running.",
+        );
+        writeln!(
+            out,
+            "{PREFIX}_git_backends_running {}",
+            self.0.git_backends_running.load(Ordering::Relaxed)
+        )
+        .ok();
+
+        for (name, help, value) in [
+            (
+                "tree_cache_hits_total",
+                "Tree requests answered from the cache.",
+                self.0.tree_cache_hits.load(Ordering::Relaxed),
+            ),
+            (
+                "tree_cache_misses_total",
+                "Tree requests not found in the cache.",
+                self.0.tree_cache_misses.load(Ordering::Relaxed),
+            ),
+        ] {
+            header(&mut out, name, "counter", help);
+            writeln!(out, "{PREFIX}_{name} {value}").ok();
+        }
+        for (name, help, value) in gauges {
+            header(&mut out, name, "gauge", help);
+            writeln!(out, "{PREFIX}_{name} {value}").ok();
+        }
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(out, "# HELP {PREFIX}_{name} {help}").ok();
+    writeln!(out, "# TYPE {PREFIX}_{name} {kind}").ok();
+}
+
+/// Escape a label value.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Method label, without letting arbitrary methods create new series.
+fn method_label(method: &Method) -> &'static str {
+    match *method {
+        Method::GET => "GET",
+        Method::HEAD => "HEAD",
+        Method::POST => "POST",
+        Method::PUT => "PUT",
+        Method::PATCH => "PATCH",
+        Method::DELETE => "DELETE",
+        Method::OPTIONS => "OPTIONS",
+        _ => "other",
+    }
+}
+
+fn status_class(status: StatusCode) -> &'static str {
+    match status.as_u16() {
+        100..=199 => "1xx",
+        200..=299 => "2xx",
+        300..=399 => "3xx",
+        400..=499 => "4xx",
+        _ => "5xx",
+    }
+}
+
+/// Record requests by the route they matched.
+pub async fn track_middleware(
+    State(metrics): State<Metrics>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_owned());
+    let method = request.method().clone();
+    let started = Instant::now();
+    let response = next.run(request).await;
+
+    metrics.request(
+        route.as_deref().unwrap_or(UNMATCHED),
+        &method,
+        response.status(),
+        started.elapsed(),
+    );
+    response
+}
+
+pub fn router(ctx: Context) -> Router {
+    Router::new()
+        .route("/metrics", get(metrics_handler))
+        .with_state(ctx)
+}
+
+/// Return metrics in the Prometheus text format.
+/// `GET /metrics`
+async fn metrics_handler(State(ctx): State<Context>) -> impl IntoResponse {
+    let sessions = ctx.sessions().active(time::OffsetDateTime::now_utc())?;
+    let repos = ctx.profile().storage.repositories()?.len();
+    let mut gauges = vec![
+        ("sessions_active", "Authorized sessions.", sessions),
+        ("repositories", "Repositories in storage.", repos),
+    ];
+    if let Some(cache) = ctx.cache() {
+        gauges.push((
+            "tree_cache_entries",
+            "Entries in the tree cache.",
+            cache.tree.lock().await.len(),
+        ));
+    }
+
+    Ok::<_, Error>((
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        ctx.metrics().render(&gauges),
+    ))
+}
+
+#[cfg(test)]
+mod routes {
+    use std::net::SocketAddr;
+
+    use axum::extract::connect_info::MockConnectInfo;
+    use axum::http::StatusCode;
+
+    use crate::test::{self, get, HEAD, RID};
+
+    #[tokio::test]
+    async fn test_metrics() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ctx = test::seed(tmp.path());
+        let options = crate::config::Config {
+            features: crate::config::FeatureToggles {
+                metrics: Some(true),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+        .options()
+        .unwrap();
+        let app = crate::routes(
+            &options,
+            ctx.clone(),
+            &Default::default(),
+            &Default::default(),
+        )
+        .layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 8080))));
+
+        get(&app, format!("/api/v1/projects/{RID}/tree/{HEAD}/")).await;
+        get(&app, format!("/api/v1/projects/{RID}/tree/{HEAD}/")).await;
+        get(&app, "/api/v1/projects/rad:z4ii/tree/none/").await;
+        get(&app, "/nowhere").await;
+
+        let response = get(&app, "/metrics").await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.body().await;
+        let body = String::from_utf8_lossy(&body);
+        let lines = body.lines().collect::<Vec<_>>();
+
+        for line in [
+            r#"radicle_httpd_requests_total{route="/api/v1/projects/:project/tree/:sha/",method="GET",status="2xx"} 2"#,
+            r#"radicle_httpd_requests_total{route="/api/v1/projects/:project/tree/:sha/",method="GET",status="4xx"} 1"#,
+            r#"radicle_httpd_requests_total{route="unmatched",method="GET",status="4xx"} 1"#,
+            r#"radicle_httpd_request_duration_seconds_count{route="/api/v1/projects/:project/tree/:sha/",method="GET"} 3"#,
+            "radicle_httpd_tree_cache_hits_total 1",
+            "radicle_httpd_tree_cache_misses_total 1",
+            "radicle_httpd_tree_cache_entries 1",
+            "radicle_httpd_git_backend_spawns_total 0",
+            "radicle_httpd_sessions_active 0",
+            "radicle_httpd_repositories 3",
+        ] {
+            assert!(lines.contains(&line), "missing `{line}` in:\n{body}");
+        }
+
+        // Metrics are off by default.
+        let options = crate::config::Config::default().options().unwrap();
+        let app = crate::routes(&options, ctx, &Default::default(), &Default::default())
+            .layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 8080))));
+        let response = get(&app, "/metrics").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_git_backend_spawns() {
+        let metrics = super::Metrics::default();
+        metrics.git_backend_spawned();
+        metrics.git_backend_spawned();
+        metrics.git_backend_exited(std::time::Duration::from_millis(5));
+
+        // Backends still running are counted as spawned.
+        let body = metrics.render(&[]);
+        let lines = body.lines().collect::<Vec<_>>();
+        for line in [
+            "radicle_httpd_git_backend_spawns_total 2",
+            "radicle_httpd_git_backend_duration_seconds_count{} 1",
+            "radicle_httpd_git_backends_running 1",
+        ] {
+            assert!(lines.contains(&line), "missing `{line}` in:\n{body}");
+        }
+    }
+}
@@ -0,0 +1,425 @@
+//! Token-bucket rate limiting.
+//!
+//! Every client gets a bucket that holds up to `burst` tokens and is refilled at a steady rate.
+//! Each request takes a token; when the bucket is empty the request is rejected with
+//! `429 Too Many Requests` and a `Retry-After` header. Clients are identified by their bearer
+//! token if it belongs to an authorized session or a valid access token, and by their IP
+//! address otherwise.
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::num::NonZeroUsize;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::body::Body;
+use axum::extract::{ConnectInfo, State};
+use axum::http::{header, Request, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::{Json, Router};
+use lru::LruCache;
+use serde_json::json;
+
+use crate::api::auth::{self, store::SessionStore};
+
+/// Most buckets kept. Above this, the least recently used bucket is dropped.
+const MAX_BUCKETS: NonZeroUsize = match NonZeroUsize::new(4096) {
+    Some(n) => n,
+    None => panic!(),
+};
+
+/// How many requests a client may make.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quota {
+    /// Requests allowed per `period`, on average.
+    pub requests: u32,
+    pub period: Duration,
+    /// Requests allowed in a row, before the rate applies.
+    pub burst: u32,
+}
+
+impl Quota {
+    fn per_second(&self) -> f64 {
+        self.requests as f64 / self.period.as_secs_f64()
+    }
+}
+
+impl FromStr for Quota {
+    type Err = String;
+
+    /// Parse a quota of the form `<requests>/<s|m|h>[:<burst>]`, eg. `10/s` or `600/m:50`.
+    /// The burst defaults to the number of requests.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid rate limit '{s}', expected eg. '10/s' or '600/m:50'");
+        let (rate, burst) = match s.split_once(':') {
+            Some((rate, burst)) => (rate, Some(burst)),
+            None => (s, None),
+        };
+        let (requests, period) = rate.split_once('/').ok_or_else(invalid)?;
+        let requests = requests.parse::<u32>().map_err(|_| invalid())?;
+        let period = match period {
+            "s" => Duration::from_secs(1),
+            "m" => Duration::from_secs(60),
+            "h" => Duration::from_secs(60 * 60),
+            _ => return Err(invalid()),
+        };
+        let burst = match burst {
+            Some(burst) => burst.parse::<u32>().map_err(|_| invalid())?,
+            None => requests,
+        };
+        if requests == 0 || burst == 0 {
+            return Err(invalid());
+        }
+        Ok(Self {
+            requests,
+            period,
+            burst,
+        })
+    }
+}
+
+/// Quotas of the routers. Routers without a quota aren't limited.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct RateLimits {
+    pub api: Option<Quota>,
+    pub git: Option<Quota>,
+    pub raw: Option<Quota>,
+}
+
+impl RateLimits {
+    /// Set quotas from an option of the form `<router>=<quota>`, where the router is one of
+    /// `api`, `git`, `raw` or `all`.
+    pub fn set(&mut self, s: &str) -> Result<(), String> {
+        let (router, quota) = s
+            .split_once('=')
+            .ok_or_else(|| format!("invalid rate limit '{s}', expected '<router>=<quota>'"))?;
+        let quota = Some(quota.parse()?);
+
+        match router {
+            "api" => self.api = quota,
+            "git" => self.git = quota,
+            "raw" => self.raw = quota,
+            "all" => {
+                self.api = quota;
+                self.git = quota;
+                self.raw = quota;
+            }
+            _ => return Err(format!("unknown router '{router}' in rate limit")),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Client {
+    Ip(IpAddr),
+    Token(String),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Buckets of the clients of one router.
+#[derive(Clone)]
+pub struct Buckets(Arc<Mutex<LruCache<Client, Bucket>>>);
+
+impl Default for Buckets {
+    fn default() -> Self {
+        Self(Arc::new(Mutex::new(LruCache::new(MAX_BUCKETS))))
+    }
+}
+
+/// Buckets of every router. They outlive the routers, so that a router built on reload doesn't
+/// give every client a fresh budget.
+#[derive(Clone, Default)]
+pub struct RouterBuckets {
+    pub api: Buckets,
+    pub git: Buckets,
+    pub raw: Buckets,
+}
+
+/// Rate limiter for one router.
+#[derive(Clone)]
+pub struct Limiter {
+    quota: Quota,
+    sessions: Arc<dyn SessionStore>,
+    buckets: Buckets,
+}
+
+impl Limiter {
+    pub fn new(quota: Quota, sessions: Arc<dyn SessionStore>, buckets: Buckets) -> Self {
+        Self {
+            quota,
+            sessions,
+            buckets,
+        }
+    }
+
+    /// Take a token from the client's bucket. If it's empty, returns how long until it isn't.
+    fn acquire(&self, client: Client, now: Instant) -> Result<(), Duration> {
+        let rate = self.quota.per_second();
+        let burst = self.quota.burst as f64;
+        #[allow(clippy::unwrap_used)]
+        let mut buckets = self.buckets.0.lock().unwrap();
+
+        let bucket = buckets.get_or_insert_mut(client, || Bucket {
+            tokens: burst,
+            updated: now,
+        });
+        bucket.tokens =
+            (bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate).min(burst);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1. {
+            bucket.tokens -= 1.;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1. - bucket.tokens) / rate))
+        }
+    }
+
+    /// Identify the client making a request, given its bearer token and address. Bearer
+    /// tokens only count if they're valid, so that clients can't get a fresh budget by making
+    /// one up, or by holding on to one that expired or was never authorized.
+    async fn client(&self, bearer: Option<String>, ip: IpAddr) -> Client {
+        if let Some(bearer) = bearer {
+            let sessions = self.sessions.clone();
+            let token = bearer.clone();
+            // Looking the token up reads the session store, which mustn't block the runtime.
+            let valid = tokio::task::spawn_blocking(move || {
+                auth::authenticate(sessions.as_ref(), &token).is_ok()
+            })
+            .await;
+
+            if let Ok(true) = valid {
+                return Client::Token(bearer);
+            }
+        }
+        Client::Ip(ip)
+    }
+}
+
+/// The bearer token and address of the client making a request.
+fn credentials(request: &Request<Body>) -> (Option<String>, IpAddr) {
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::to_owned);
+    let ip = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED), |ConnectInfo(addr)| {
+            addr.ip()
+        });
+
+    (bearer, ip)
+}
+
+/// Limit the rate of requests to `router`, if a quota is given.
+pub fn layer(
+    router: Router,
+    quota: Option<Quota>,
+    sessions: Arc<dyn SessionStore>,
+    buckets: &Buckets,
+) -> Router {
+    match quota {
+        Some(quota) => router.layer(middleware::from_fn_with_state(
+            Limiter::new(quota, sessions, buckets.clone()),
+            rate_limit_middleware,
+        )),
+        None => router,
+    }
+}
+
+async fn rate_limit_middleware(
+    State(limiter): State<Limiter>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let (bearer, ip) = credentials(&request);
+    let client = limiter.client(bearer, ip).await;
+
+    match limiter.acquire(client, Instant::now()) {
+        Ok(()) => next.run(request).await,
+        Err(wait) => {
+            let status = StatusCode::TOO_MANY_REQUESTS;
+            let retry_after = wait.as_secs_f64().ceil().max(1.) as u64;
+            let body = Json(json!({
+                "error": status.canonical_reason(),
+                "code": status.as_u16()
+            }));
+
+            (
+                status,
+                [(header::RETRY_AFTER, retry_after.to_string())],
+                body,
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use axum::extract::connect_info::MockConnectInfo;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use axum::Router;
+
+    use super::*;
+    use radicle::node::Alias;
+
+    use crate::api::auth::store::MemoryStore;
+    use crate::api::auth::{AuthState, Scope, Session};
+    use crate::test;
+
+    #[test]
+    fn test_quota_from_str() {
+        assert_eq!(
+            "10/s".parse::<Quota>().unwrap(),
+            Quota {
+                requests: 10,
+                period: Duration::from_secs(1),
+                burst: 10
+            }
+        );
+        assert_eq!(
+            "600/m:50".parse::<Quota>().unwrap(),
+            Quota {
+                requests: 600,
+                period: Duration::from_secs(60),
+                burst: 50
+            }
+        );
+        assert!("10".parse::<Quota>().is_err());
+        assert!("0/s".parse::<Quota>().is_err());
+        assert!("10/d".parse::<Quota>().is_err());
+
+        let mut limits = RateLimits::default();
+        limits.set("all=10/s").unwrap();
+        limits.set("git=1/m").unwrap();
+        assert_eq!(limits.api, "10/s".parse().ok());
+        assert_eq!(limits.git, "1/m".parse().ok());
+        assert!(limits.set("tree=1/s").is_err());
+    }
+
+    #[test]
+    fn test_bucket_refill() {
+        let limiter = Limiter::new(
+            "2/s".parse().unwrap(),
+            Arc::new(MemoryStore::default()),
+            Buckets::default(),
+        );
+        let client = Client::Ip([127, 0, 0, 1].into());
+        let now = Instant::now();
+
+        assert!(limiter.acquire(client.clone(), now).is_ok());
+        assert!(limiter.acquire(client.clone(), now).is_ok());
+        assert_eq!(
+            limiter.acquire(client.clone(), now),
+            Err(Duration::from_millis(500))
+        );
+        assert!(limiter
+            .acquire(Client::Ip([127, 0, 0, 2].into()), now)
+            .is_ok());
+        assert!(limiter
+            .acquire(client, now + Duration::from_millis(500))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_bucket_eviction() {
+        let limiter = Limiter::new(
+            "1/h".parse().unwrap(),
+            Arc::new(MemoryStore::default()),
+            Buckets::default(),
+        );
+        let client = Client::Ip([127, 0, 0, 1].into());
+        let now = Instant::now();
+
+        assert!(limiter.acquire(client.clone(), now).is_ok());
+        for i in 1..MAX_BUCKETS.get() as u32 {
+            assert!(limiter
+                .acquire(Client::Ip(Ipv4Addr::from(i).into()), now)
+                .is_ok());
+        }
+        // The client was used most recently, so it isn't the one evicted for a new client.
+        assert!(limiter.acquire(client.clone(), now).is_err());
+        assert!(limiter
+            .acquire(Client::Ip(Ipv4Addr::from(u32::MAX).into()), now)
+            .is_ok());
+        assert!(limiter.acquire(client, now).is_err());
+        assert!(limiter
+            .acquire(Client::Ip(Ipv4Addr::from(1).into()), now)
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ctx = test::seed(tmp.path());
+        let sessions = ctx.sessions().clone();
+        let router = Router::new().route("/", get(|| async { "ok" }));
+        let app = layer(
+            router,
+            "1/h".parse().ok(),
+            sessions.clone(),
+            &Buckets::default(),
+        )
+        .layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 8080))));
+
+        assert_eq!(test::get(&app, "/").await.status(), StatusCode::OK);
+
+        let response = test::get(&app, "/").await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "3600");
+
+        // Unknown bearer tokens are limited by IP.
+        let response = test::get_with_header(&app, "/", ("Authorization", "Bearer made-up")).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // So are sessions that weren't authorized.
+        let now = time::OffsetDateTime::now_utc();
+        let session = Session {
+            status: AuthState::Unauthorized,
+            public_key: *ctx.profile().id(),
+            alias: Alias::new("seed"),
+            issued_at: now,
+            expires_at: now + time::Duration::hours(1),
+            scope: Scope::default(),
+        };
+        sessions.insert("unauthorized", &session).unwrap();
+        let response =
+            test::get_with_header(&app, "/", ("Authorization", "Bearer unauthorized")).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // Or that expired.
+        sessions
+            .insert(
+                "expired",
+                &Session {
+                    status: AuthState::Authorized,
+                    expires_at: now,
+                    ..session
+                },
+            )
+            .unwrap();
+        let response = test::get_with_header(&app, "/", ("Authorization", "Bearer expired")).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // Valid ones have a budget of their own.
+        test::create_session(ctx).await;
+        let auth = format!("Bearer {}", test::SESSION_ID);
+        let response = test::get_with_header(&app, "/", ("Authorization", &auth)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
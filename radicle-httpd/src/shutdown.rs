@@ -0,0 +1,346 @@
+//! Graceful shutdown.
+//!
+//! When the process receives `SIGTERM` or `SIGINT`, the server stops accepting connections and
+//! gives the requests in flight until a deadline to finish. Requests still running after that
+//! are dropped, and the `git http-backend` processes serving them are terminated, so that they
+//! don't outlive the server. A request is in flight until its response body is sent, and the
+//! event streams of clients are closed when shutdown begins, since they never end on their own.
+use std::future::{Future, IntoFuture};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use std::{fmt, io};
+
+use axum::body::{Body, Bytes, HttpBody};
+use axum::extract::State;
+use axum::http::Request;
+use axum::middleware::Next;
+use axum::response::Response;
+use http_body::{Frame, SizeHint};
+
+use crate::git;
+
+/// Requests handled by the server.
+#[derive(Debug, Default, Clone)]
+pub struct Requests(Arc<Counters>);
+
+#[derive(Debug, Default)]
+struct Counters {
+    active: AtomicUsize,
+    served: AtomicUsize,
+}
+
+impl Requests {
+    /// Requests currently being handled.
+    pub fn active(&self) -> usize {
+        self.0.active.load(Ordering::SeqCst)
+    }
+
+    /// Requests that got a response.
+    pub fn served(&self) -> usize {
+        self.0.served.load(Ordering::SeqCst)
+    }
+}
+
+/// A request being handled. Stops counting as active when dropped, even if it never completes.
+struct InFlight(Requests);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0 .0.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// A response body, keeping its request in flight until it's fully sent or dropped.
+struct Tracked {
+    body: Body,
+    _in_flight: InFlight,
+}
+
+impl HttpBody for Tracked {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        Pin::new(&mut self.body).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.body.size_hint()
+    }
+}
+
+/// Keep count of requests, so that we know what we're waiting for on shutdown. A request
+/// counts as active until its response body is sent, since bodies can be streamed.
+pub async fn track_middleware(
+    State(requests): State<Requests>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    requests.0.active.fetch_add(1, Ordering::SeqCst);
+    let in_flight = InFlight(requests.clone());
+    let response = next.run(request).await;
+    requests.0.served.fetch_add(1, Ordering::SeqCst);
+
+    response.map(|body| {
+        Body::new(Tracked {
+            body,
+            _in_flight: in_flight,
+        })
+    })
+}
+
+/// What happened on shutdown.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Summary {
+    /// Requests served over the lifetime of the server.
+    pub served: usize,
+    /// Requests in flight when shutdown began.
+    pub draining: usize,
+    /// Requests still in flight at the deadline, which were dropped.
+    pub aborted: usize,
+    /// `git http-backend` processes that had to be terminated.
+    pub terminated: usize,
+    /// Time it took to shut down.
+    pub elapsed: Duration,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} request(s) served, {} drained and {} aborted in {:?}, {} git backend(s) terminated",
+            self.served,
+            self.draining.saturating_sub(self.aborted),
+            self.aborted,
+            self.elapsed,
+            self.terminated,
+        )
+    }
+}
+
+/// Wait until the process is asked to terminate, with `SIGTERM` or `SIGINT`.
+pub async fn signal() {
+    let interrupt = tokio::signal::ctrl_c();
+
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => tokio::select! {
+                Ok(()) = interrupt => {}
+                Some(()) = terminate.recv() => {}
+            },
+            Err(err) => {
+                tracing::error!("Error listening for SIGTERM: {err}");
+                if interrupt.await.is_err() {
+                    std::future::pending::<()>().await;
+                }
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    if interrupt.await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Run `server` until `signal` completes. Then call `stop`, which should make the server stop
+/// accepting connections, and give it `timeout` to finish the requests in flight.
+pub async fn graceful<S>(
+    server: S,
+    signal: impl Future<Output = ()>,
+    stop: impl FnOnce(),
+    timeout: Duration,
+    requests: &Requests,
+    backends: &git::Backends,
+) -> io::Result<Summary>
+where
+    S: IntoFuture<Output = io::Result<()>>,
+{
+    let mut server = Box::pin(server.into_future());
+
+    tokio::select! {
+        result = &mut server => {
+            return result.map(|()| Summary {
+                served: requests.served(),
+                ..Summary::default()
+            });
+        }
+        () = signal => {}
+    }
+    let started = Instant::now();
+    let draining = requests.active();
+
+    tracing::info!(
+        "shutting down, waiting up to {timeout:?} for {draining} request(s) to finish.."
+    );
+    stop();
+
+    let aborted = match tokio::time::timeout(timeout, &mut server).await {
+        Ok(result) => {
+            result?;
+            0
+        }
+        Err(_) => requests.active(),
+    };
+    let terminated = backends.terminate();
+    drop(server);
+
+    Ok(Summary {
+        served: requests.served(),
+        draining,
+        aborted,
+        terminated,
+        elapsed: started.elapsed(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use axum::middleware;
+    use axum::routing::{get, MethodRouter};
+    use axum::Router;
+    use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::sync::oneshot;
+    use tokio::task::JoinHandle;
+
+    use super::*;
+
+    /// A route that takes `delay` to respond.
+    fn delayed(delay: Duration) -> MethodRouter {
+        get(move || async move {
+            tokio::time::sleep(delay).await;
+            "done"
+        })
+    }
+
+    /// Serve `route`, until the returned sender is used.
+    async fn serve(
+        route: MethodRouter,
+        timeout: Duration,
+    ) -> (
+        TcpStream,
+        Requests,
+        oneshot::Sender<()>,
+        JoinHandle<io::Result<Summary>>,
+    ) {
+        let requests = Requests::default();
+        let app = Router::new()
+            .route("/", route)
+            .layer(middleware::from_fn_with_state(
+                requests.clone(),
+                track_middleware,
+            ));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (stop, stopped) = oneshot::channel::<()>();
+        let (signal, signalled) = oneshot::channel::<()>();
+        let server = axum::serve(listener, app).with_graceful_shutdown(async move {
+            stopped.await.ok();
+        });
+        let task = tokio::spawn({
+            let requests = requests.clone();
+            async move {
+                graceful(
+                    server,
+                    async move {
+                        signalled.await.ok();
+                    },
+                    move || {
+                        stop.send(()).ok();
+                    },
+                    timeout,
+                    &requests,
+                    &git::Backends::default(),
+                )
+                .await
+            }
+        });
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+
+        while requests.active() == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        (stream, requests, signal, task)
+    }
+
+    #[tokio::test]
+    async fn test_in_flight_request_completes() {
+        let (mut stream, _, signal, task) =
+            serve(delayed(Duration::from_millis(300)), Duration::from_secs(10)).await;
+
+        signal.send(()).unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let summary = task.await.unwrap().unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+        assert!(response.ends_with("done"), "{response}");
+        assert_eq!(summary.served, 1);
+        assert_eq!(summary.draining, 1);
+        assert_eq!(summary.aborted, 0);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_deadline() {
+        let (_stream, requests, signal, task) =
+            serve(delayed(Duration::from_secs(60)), Duration::from_millis(100)).await;
+
+        signal.send(()).unwrap();
+
+        let summary = tokio::time::timeout(Duration::from_secs(10), task)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(summary.served, 0);
+        assert_eq!(summary.draining, 1);
+        assert_eq!(summary.aborted, 1);
+        assert_eq!(requests.served(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_streaming_response_deadline() {
+        let route = get(|| async {
+            Body::from_stream(futures_util::stream::pending::<Result<Bytes, io::Error>>())
+        });
+        let (_stream, requests, signal, task) = serve(route, Duration::from_millis(100)).await;
+
+        // The handler returned, but the body is still being sent.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(requests.served(), 1);
+        assert_eq!(requests.active(), 1);
+
+        signal.send(()).unwrap();
+
+        let summary = tokio::time::timeout(Duration::from_secs(10), task)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(summary.served, 1);
+        assert_eq!(summary.draining, 1);
+        assert_eq!(summary.aborted, 1);
+    }
+}
@@ -0,0 +1,200 @@
+//! Export of trace spans over OTLP, with the `otel` feature.
+//!
+//! Exporting is enabled by setting `OTEL_EXPORTER_OTLP_ENDPOINT` to the gRPC endpoint of a
+//! collector, eg. `http://localhost:4317`. Requests carrying a W3C `traceparent` header are
+//! traced as part of the caller's trace.
+use std::env;
+
+use axum::http::HeaderMap;
+use opentelemetry::propagation::{Extractor, TextMapPropagator as _};
+use opentelemetry::trace::TraceError;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig as _;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::Tracer;
+use opentelemetry_sdk::{runtime, Resource};
+use tracing::Span;
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt as _};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Environment variable holding the collector endpoint.
+pub const ENDPOINT_VAR: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+/// Name spans are exported under.
+const SERVICE_NAME: &str = "radicle-httpd";
+
+/// Layer exporting spans to the collector, if an endpoint is configured.
+pub fn layer<S>() -> Option<OpenTelemetryLayer<S, Tracer>>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+{
+    let endpoint = env::var(ENDPOINT_VAR).ok()?;
+
+    match tracer(&endpoint) {
+        Ok(tracer) => Some(tracing_opentelemetry::layer().with_tracer(tracer)),
+        Err(err) => {
+            eprintln!("Error setting up span export to {endpoint}: {err}");
+            None
+        }
+    }
+}
+
+/// Set up a tracer exporting spans in batches to the collector at `endpoint`.
+/// Must be called from within the tokio runtime.
+fn tracer(endpoint: &str) -> Result<Tracer, TraceError> {
+    opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+
+    opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint),
+        )
+        .with_trace_config(
+            opentelemetry_sdk::trace::config()
+                .with_resource(Resource::new([KeyValue::new("service.name", SERVICE_NAME)])),
+        )
+        .install_batch(runtime::Tokio)
+}
+
+/// Make `span` part of the trace given by the request's `traceparent` header, if any.
+pub fn set_parent(span: &Span, headers: &HeaderMap) {
+    let parent = TraceContextPropagator::new().extract(&Headers(headers));
+    span.set_parent(parent);
+}
+
+/// Export the spans that are still buffered.
+pub fn shutdown() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+struct Headers<'a>(&'a HeaderMap);
+
+impl Extractor for Headers<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|k| k.as_str()).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fmt::Write as _;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+
+    use axum::extract::ConnectInfo;
+    use axum::http::StatusCode;
+    use axum::Extension;
+    use opentelemetry_proto::tonic::collector::trace::v1::trace_service_server::{
+        TraceService, TraceServiceServer,
+    };
+    use opentelemetry_proto::tonic::collector::trace::v1::{
+        ExportTraceServiceRequest, ExportTraceServiceResponse,
+    };
+    use opentelemetry_proto::tonic::trace::v1::Span;
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    use crate::test::{self, get_with_header, HEAD, RID};
+
+    /// OTLP collector stub, keeping the spans it receives.
+    #[derive(Clone, Default)]
+    struct Collector(Arc<Mutex<Vec<Span>>>);
+
+    #[tonic::async_trait]
+    impl TraceService for Collector {
+        async fn export(
+            &self,
+            request: tonic::Request<ExportTraceServiceRequest>,
+        ) -> Result<tonic::Response<ExportTraceServiceResponse>, tonic::Status> {
+            let spans = request
+                .into_inner()
+                .resource_spans
+                .into_iter()
+                .flat_map(|r| r.scope_spans)
+                .flat_map(|s| s.spans);
+            self.0.lock().unwrap().extend(spans);
+
+            Ok(tonic::Response::new(ExportTraceServiceResponse {
+                partial_success: None,
+            }))
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_export() {
+        let collector = Collector::default();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(TraceServiceServer::new(collector.clone()))
+                .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener)),
+        );
+
+        let tracer = super::tracer(&endpoint).unwrap();
+        let provider = tracer.provider().unwrap();
+        let subscriber = tracing_subscriber::Registry::default()
+            .with(tracing_opentelemetry::layer().with_tracer(tracer));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let tmp = tempfile::tempdir().unwrap();
+        let options = crate::config::Config::default().options().unwrap();
+        let app = crate::layers(
+            crate::routes(
+                &options,
+                test::seed(tmp.path()),
+                &Default::default(),
+                &Default::default(),
+            ),
+            crate::RequestId::new(),
+            Default::default(),
+        )
+        .layer(Extension(ConnectInfo(SocketAddr::from((
+            [127, 0, 0, 1],
+            8080,
+        )))));
+        let response = get_with_header(
+            &app,
+            format!("/api/v1/projects/{RID}/commits?parent={HEAD}"),
+            (
+                "traceparent",
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        // The request span closes with the response body.
+        drop(response);
+
+        tokio::task::block_in_place(|| provider.force_flush());
+        let spans = collector.0.lock().unwrap().clone();
+        let span = |name: &str| {
+            spans
+                .iter()
+                .find(|s| s.name == name)
+                .unwrap_or_else(|| panic!("missing span `{name}` in {spans:?}"))
+        };
+        let request = span("request");
+        let trace_id = hex(&request.trace_id);
+
+        assert_eq!(trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(hex(&request.parent_span_id), "00f067aa0ba902b7");
+
+        for name in ["storage.open", "surf.history"] {
+            let child = span(name);
+            assert_eq!(hex(&child.trace_id), trace_id, "{name}");
+            assert_eq!(child.parent_span_id, request.span_id, "{name}");
+        }
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().fold(String::new(), |mut s, b| {
+            write!(s, "{b:02x}").unwrap();
+            s
+        })
+    }
+}
@@ -3,10 +3,13 @@ use std::fs;
 use std::path::Path;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
-use axum::body::{Body, Bytes};
+use axum::body::{Body, BodyDataStream, Bytes};
+use axum::http::header::HeaderName;
 use axum::http::{Method, Request};
 use axum::Router;
+use futures_util::StreamExt as _;
 use serde_json::Value;
 use time::OffsetDateTime;
 use tower::ServiceExt;
@@ -24,6 +27,7 @@ use radicle::{node, profile};
 use radicle_crypto::test::signer::MockSigner;
 
 use crate::api::{auth, Context};
+use crate::SessionBackend;
 
 pub const RID: &str = "rad:z4FucBZHZMCsxTyQE1dfE2YR59Qbp";
 pub const RID_PRIVATE: &str = "rad:zLuTzcmoWMcdK37xqArS8eckp9vK";
@@ -38,6 +42,7 @@ pub const SESSION_ID: &str = "u9MGAkkfkMOv0uDDB2WeUHBT7HbsO2Dy";
 pub const TIMESTAMP: u64 = 1671125284;
 pub const CONTRIBUTOR_RID: &str = "rad:z4XaCmN3jLSeiMvW15YTDpNbDHFhG";
 pub const CONTRIBUTOR_DID: &str = "did:key:z6Mkk7oqY4pPxhMmGEotDYsFo97vhCj85BLY1H256HrJmjN8";
+pub const CONTRIBUTOR_NID: &str = "z6Mkk7oqY4pPxhMmGEotDYsFo97vhCj85BLY1H256HrJmjN8";
 pub const CONTRIBUTOR_ALIAS: &str = "seed";
 pub const CONTRIBUTOR_PATCH_ID: &str = "3e3f0dc34b3eeb64cfbc7218fbd52b97246e0564";
 
@@ -310,29 +315,46 @@ fn seed_with_signer<G: Signer>(dir: &Path, profile: radicle::Profile, signer: &G
 
     let options = crate::Options {
         aliases: std::collections::HashMap::new(),
-        listen: std::net::SocketAddr::from(([0, 0, 0, 0], 8080)),
+        listen: crate::Listen::default(),
+        socket_mode: None,
         cache: Some(crate::DEFAULT_CACHE_SIZE),
+        tls: None,
+        sessions: SessionBackend::Sqlite,
+        rate_limits: Default::default(),
+        cors: Default::default(),
+        body_limit: None,
+        features: Default::default(),
+        metrics_listen: None,
+        shutdown_timeout: crate::DEFAULT_SHUTDOWN_TIMEOUT,
+        reload: None,
     };
 
-    Context::new(Arc::new(profile), &options)
+    Context::new(Arc::new(profile), &options).unwrap()
 }
 
-/// Adds an authorized session to the Context::sessions HashMap.
+/// Adds an authorized session to the Context::sessions store.
 pub async fn create_session(ctx: Context) {
+    create_session_with_scope(ctx, auth::Scope::default()).await
+}
+
+/// Adds an authorized session with the given scope to the Context::sessions store.
+pub async fn create_session_with_scope(ctx: Context, scope: auth::Scope) {
     let issued_at = OffsetDateTime::now_utc();
-    let mut sessions = ctx.sessions().write().await;
-    sessions.insert(
-        String::from(SESSION_ID),
-        auth::Session {
-            status: auth::AuthState::Authorized,
-            public_key: ctx.profile().public_key,
-            alias: ctx.profile().config.node.alias.clone(),
-            issued_at,
-            expires_at: issued_at
-                .checked_add(auth::AUTHORIZED_SESSIONS_EXPIRATION)
-                .unwrap(),
-        },
-    );
+    ctx.sessions()
+        .insert(
+            SESSION_ID,
+            &auth::Session {
+                status: auth::AuthState::Authorized,
+                public_key: ctx.profile().public_key,
+                alias: ctx.profile().config.node.alias.clone(),
+                issued_at,
+                expires_at: issued_at
+                    .checked_add(auth::AUTHORIZED_SESSIONS_EXPIRATION)
+                    .unwrap(),
+                scope,
+            },
+        )
+        .unwrap();
 }
 
 pub async fn get(app: &Router, path: impl ToString) -> Response {
@@ -344,6 +366,19 @@ pub async fn get(app: &Router, path: impl ToString) -> Response {
     )
 }
 
+pub async fn get_with_header(
+    app: &Router,
+    path: impl ToString,
+    (name, value): (&str, &str),
+) -> Response {
+    let mut request = request(path, Method::GET, None, None);
+    request
+        .headers_mut()
+        .insert(name.parse::<HeaderName>().unwrap(), value.parse().unwrap());
+
+    Response(app.clone().oneshot(request).await.unwrap())
+}
+
 pub async fn post(
     app: &Router,
     path: impl ToString,
@@ -386,6 +421,30 @@ pub async fn put(
     )
 }
 
+/// Send a JSON body with the session of [`create_session`], eg. to create or change an object.
+pub async fn write(app: &Router, method: Method, path: impl ToString, body: Value) -> Response {
+    Response(
+        app.clone()
+            .oneshot(request(
+                path,
+                method,
+                Some(Body::from(body.to_string())),
+                Some(SESSION_ID.to_string()),
+            ))
+            .await
+            .unwrap(),
+    )
+}
+
+pub async fn delete(app: &Router, path: impl ToString, auth: Option<String>) -> Response {
+    Response(
+        app.clone()
+            .oneshot(request(path, Method::DELETE, None, auth))
+            .await
+            .unwrap(),
+    )
+}
+
 fn request(
     path: impl ToString,
     method: Method,
@@ -426,6 +485,10 @@ impl Response {
         success.unwrap_or(false)
     }
 
+    pub fn headers(&self) -> &axum::http::HeaderMap {
+        self.0.headers()
+    }
+
     pub fn status(&self) -> axum::http::StatusCode {
         self.0.status()
     }
@@ -435,4 +498,67 @@ impl Response {
             .await
             .unwrap()
     }
+
+    pub fn events(self) -> Events {
+        Events {
+            stream: self.0.into_body().into_data_stream(),
+            buffer: String::new(),
+        }
+    }
+}
+
+/// A stream of server-sent events.
+pub struct Events {
+    stream: BodyDataStream,
+    buffer: String,
+}
+
+/// A single server-sent event.
+#[derive(Debug, Default)]
+pub struct Event {
+    pub id: Option<String>,
+    pub event: String,
+    pub data: Value,
+}
+
+impl Events {
+    /// Waits for the next event, skipping keep-alive comments.
+    pub async fn next(&mut self) -> Event {
+        loop {
+            if let Some((raw, rest)) = self.buffer.split_once("\n\n") {
+                let (raw, rest) = (raw.to_owned(), rest.to_owned());
+                self.buffer = rest;
+
+                let mut event = Event::default();
+                for line in raw.lines() {
+                    match line.split_once(':') {
+                        Some(("id", v)) => event.id = Some(v.trim().to_owned()),
+                        Some(("event", v)) => event.event = v.trim().to_owned(),
+                        Some(("data", v)) => event.data = serde_json::from_str(v).unwrap(),
+                        _ => {}
+                    }
+                }
+                if !event.event.is_empty() {
+                    return event;
+                }
+                continue;
+            }
+            let chunk = tokio::time::timeout(Duration::from_secs(10), self.stream.next())
+                .await
+                .expect("timed out waiting for event")
+                .unwrap()
+                .unwrap();
+            self.buffer.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+    }
+
+    /// Waits for the next event with the given name.
+    pub async fn next_named(&mut self, name: &str) -> Event {
+        loop {
+            let event = self.next().await;
+            if event.event == name {
+                return event;
+            }
+        }
+    }
 }
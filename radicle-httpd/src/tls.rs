@@ -0,0 +1,204 @@
+//! TLS termination.
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use axum_server::tls_rustls::RustlsConfig;
+
+/// ALPN protocol identifier for HTTP/2.
+const ALPN_H2: &[u8] = b"h2";
+/// ALPN protocol identifier for HTTP/1.1.
+const ALPN_HTTP1: &[u8] = b"http/1.1";
+
+#[derive(Debug, Clone)]
+pub struct TlsOptions {
+    /// Path to the PEM encoded certificate chain.
+    pub cert: PathBuf,
+    /// Path to the PEM encoded private key.
+    pub key: PathBuf,
+    /// Whether to offer HTTP/2 to clients during the handshake.
+    pub http2: bool,
+}
+
+/// Load the TLS configuration from the certificate and key files.
+pub async fn config(options: &TlsOptions) -> io::Result<RustlsConfig> {
+    let config = RustlsConfig::from_pem_file(&options.cert, &options.key).await?;
+    config.reload_from_config(server_config(&config, options));
+
+    Ok(config)
+}
+
+/// Re-read the certificate and key files, eg. after they were renewed.
+/// Connections that are already established keep using the previous certificate.
+pub async fn reload(config: &RustlsConfig, options: &TlsOptions) -> io::Result<()> {
+    config
+        .reload_from_pem_file(&options.cert, &options.key)
+        .await?;
+    config.reload_from_config(server_config(config, options));
+
+    Ok(())
+}
+
+/// Reload the TLS configuration every time the process receives `SIGHUP`.
+#[cfg(unix)]
+pub async fn reload_on_hangup(config: RustlsConfig, options: TlsOptions) -> io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = signal(SignalKind::hangup())?;
+    while hangup.recv().await.is_some() {
+        match reload(&config, &options).await {
+            Ok(()) => tracing::info!("reloaded TLS certificate from {}", options.cert.display()),
+            Err(err) => tracing::error!("Error reloading TLS certificate: {err}"),
+        }
+    }
+    Ok(())
+}
+
+/// Restrict the ALPN protocols offered, according to the options.
+fn server_config(config: &RustlsConfig, options: &TlsOptions) -> Arc<rustls::ServerConfig> {
+    let mut inner = (*config.get_inner()).clone();
+    inner.alpn_protocols = if options.http2 {
+        vec![ALPN_H2.to_vec(), ALPN_HTTP1.to_vec()]
+    } else {
+        vec![ALPN_HTTP1.to_vec()]
+    };
+    Arc::new(inner)
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::net::SocketAddr;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use axum::routing::get;
+    use axum::Router;
+    use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+    use tokio::net::TcpStream;
+    use tokio_rustls::rustls::{Certificate, ClientConfig, RootCertStore, ServerName};
+    use tokio_rustls::TlsConnector;
+
+    use super::TlsOptions;
+
+    /// Write a fresh self-signed certificate for `localhost` to `dir`, returning its DER encoding.
+    fn certificate(dir: &Path) -> Vec<u8> {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_owned()]).unwrap();
+
+        fs::write(dir.join("cert.pem"), cert.serialize_pem().unwrap()).unwrap();
+        fs::write(dir.join("key.pem"), cert.serialize_private_key_pem()).unwrap();
+
+        cert.serialize_der().unwrap()
+    }
+
+    async fn serve(options: &TlsOptions) -> (SocketAddr, axum_server::tls_rustls::RustlsConfig) {
+        let config = super::config(options).await.unwrap();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Router::new().route("/", get(|| async { "Hello TLS!" }));
+
+        tokio::spawn(
+            axum_server::from_tcp_rustls(listener, config.clone()).serve(app.into_make_service()),
+        );
+        (addr, config)
+    }
+
+    /// Connect to `addr`, trusting only `cert`. Returns the negotiated ALPN protocol and the
+    /// response to a plain HTTP/1.1 request, if HTTP/1.1 was negotiated.
+    async fn connect(addr: SocketAddr, cert: &[u8]) -> std::io::Result<(Vec<u8>, String)> {
+        let mut roots = RootCertStore::empty();
+        roots.add(&Certificate(cert.to_vec())).unwrap();
+
+        let mut config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+        let stream = TcpStream::connect(addr).await?;
+        let domain = ServerName::try_from("localhost").unwrap();
+        let mut stream = TlsConnector::from(Arc::new(config))
+            .connect(domain, stream)
+            .await?;
+        let alpn = stream
+            .get_ref()
+            .1
+            .alpn_protocol()
+            .unwrap_or_default()
+            .to_vec();
+        let mut response = String::new();
+
+        if alpn == b"http/1.1" {
+            stream
+                .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+                .await?;
+            stream.read_to_string(&mut response).await.ok();
+        }
+        Ok((alpn, response))
+    }
+
+    #[tokio::test]
+    async fn test_tls_http1() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cert = certificate(tmp.path());
+        let options = TlsOptions {
+            cert: tmp.path().join("cert.pem"),
+            key: tmp.path().join("key.pem"),
+            http2: false,
+        };
+        let (addr, _) = serve(&options).await;
+        let (alpn, response) = connect(addr, &cert).await.unwrap();
+
+        assert_eq!(alpn, b"http/1.1");
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("Hello TLS!"));
+    }
+
+    #[tokio::test]
+    async fn test_tls_http2() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cert = certificate(tmp.path());
+        let options = TlsOptions {
+            cert: tmp.path().join("cert.pem"),
+            key: tmp.path().join("key.pem"),
+            http2: true,
+        };
+        let (addr, _) = serve(&options).await;
+        let (alpn, _) = connect(addr, &cert).await.unwrap();
+
+        assert_eq!(alpn, b"h2");
+    }
+
+    #[tokio::test]
+    async fn test_tls_reload() {
+        let tmp = tempfile::tempdir().unwrap();
+        let old = certificate(tmp.path());
+        let options = TlsOptions {
+            cert: tmp.path().join("cert.pem"),
+            key: tmp.path().join("key.pem"),
+            http2: false,
+        };
+        let (addr, config) = serve(&options).await;
+        let new = certificate(tmp.path());
+
+        // The renewed certificate is only picked up after a reload.
+        assert!(connect(addr, &new).await.is_err());
+
+        super::reload(&config, &options).await.unwrap();
+
+        assert!(connect(addr, &old).await.is_err());
+        assert!(connect(addr, &new).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_tls_missing_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let options = TlsOptions {
+            cert: tmp.path().join("cert.pem"),
+            key: tmp.path().join("key.pem"),
+            http2: false,
+        };
+
+        assert!(super::config(&options).await.is_err());
+    }
+}
@@ -4,30 +4,94 @@ use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
 use axum::body::Body;
-use axum::extract::ConnectInfo;
-use axum::http::Request;
+use axum::extract::{ConnectInfo, State};
+use axum::http::header::HeaderName;
+use axum::http::{HeaderValue, Request};
 use axum::middleware::Next;
-use axum::response::IntoResponse;
+use axum::response::{IntoResponse, Response};
 use axum::Extension;
 use hyper::{Method, StatusCode, Uri, Version};
 
 pub use radicle_term::ansi::Paint;
 
+/// Header carrying the id of a request, from upstream proxies and back to clients.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+/// Max length of request ids accepted from upstream.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Generates request ids, made of a random prefix for the process and a counter.
 #[derive(Clone)]
-pub struct RequestId(Arc<AtomicU64>);
+pub struct RequestId {
+    prefix: Arc<str>,
+    counter: Arc<AtomicU64>,
+}
 
 impl RequestId {
     pub fn new() -> RequestId {
-        RequestId(Arc::new(0.into()))
+        RequestId {
+            prefix: format!("{:08x}", fastrand::u32(..)).into(),
+            counter: Arc::new(0.into()),
+        }
     }
 
-    pub fn next(&mut self) -> u64 {
-        self.0.fetch_add(1, Ordering::SeqCst)
+    pub fn next(&mut self) -> String {
+        format!(
+            "{}-{}",
+            self.prefix,
+            self.counter.fetch_add(1, Ordering::SeqCst)
+        )
     }
 }
 
+/// Whether a request id from upstream can be used as is.
+fn is_valid_request_id(id: &HeaderValue) -> bool {
+    let id = id.as_bytes();
+
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id
+            .iter()
+            .all(|c| c.is_ascii_alphanumeric() || b"-_.:".contains(c))
+}
+
+/// Make sure every request has an id, taking the one set by upstream proxies if it's valid,
+/// and return it to the client in the `X-Request-Id` header.
+pub async fn request_id_middleware(
+    State(mut ids): State<RequestId>,
+    mut request: Request<Body>,
+    next: Next,
+) -> Response {
+    let id = match request.headers().get(REQUEST_ID_HEADER) {
+        Some(id) if is_valid_request_id(id) => id.clone(),
+        _ => {
+            #[allow(clippy::unwrap_used)]
+            let id = HeaderValue::from_str(&ids.next()).unwrap();
+            request
+                .headers_mut()
+                .insert(HeaderName::from_static(REQUEST_ID_HEADER), id.clone());
+            id
+        }
+    };
+    let mut response = next.run(request).await;
+    response
+        .headers_mut()
+        .insert(HeaderName::from_static(REQUEST_ID_HEADER), id);
+
+    response
+}
+
+/// Get the id of a request, as set by [`request_id_middleware`].
+pub fn request_id<B>(request: &Request<B>) -> &str {
+    request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|id| id.to_str().ok())
+        .unwrap_or_default()
+}
+
 #[derive(Clone)]
 pub struct TracingInfo {
+    pub request_id: String,
     pub connect_info: ConnectInfo<SocketAddr>,
     pub method: Method,
     pub version: Version,
@@ -58,6 +122,7 @@ pub async fn tracing_middleware(request: Request<Body>, next: Next) -> impl Into
     let uri = request.uri().clone();
 
     let tracing_info = TracingInfo {
+        request_id: request_id(&request).to_owned(),
         connect_info,
         method,
         version,